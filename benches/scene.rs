@@ -0,0 +1,111 @@
+//! Time tessellation, prepare with each upload strategy and render pass
+//! encoding on generated scenes. Run with `cargo bench --features bench`; a
+//! benchmark name as argument runs only the benchmarks containing it.
+
+use std::time::{Duration, Instant};
+
+use wgpu::{RenderPassColorAttachment, CommandEncoderDescriptor, RenderPassDescriptor, TextureDescriptor, MultisampleState, TextureDimension, TextureUsages, TextureFormat, Operations, Extent3d, StoreOp, LoadOp, Color};
+
+use wgpu_cyat::bench::{self, SceneDescriptor, SceneGenerator, BoundDistribution};
+use wgpu_cyat::{CyatRenderer, CyatSceneGraph, ShapeId, UploadStrategy};
+
+const SIZE: u32 = 1024;
+const FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+/// Run `f` until a second has passed, after a warm up run, and print the
+/// mean time per run.
+fn measure(filter: &Option<String>, name: &str, mut f: impl FnMut()) {
+    if filter.as_ref().is_some_and(|filter| !name.contains(filter.as_str())) {return;}
+    f();
+    let (start, mut runs) = (Instant::now(), 0u32);
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        runs += 1;
+    }
+    println!("{name:<48} {:>12.3?}/iter ({runs} runs)", start.elapsed() / runs);
+}
+
+fn scenes() -> Vec<(String, SceneDescriptor)> {
+    let mut scenes = Vec::new();
+    for shape_count in [100, 1000, 10000] {
+        for vertices in [4, 64] {
+            scenes.push((format!("{shape_count}x{vertices}"), SceneDescriptor::new(shape_count).with_vertices_per_shape(vertices)));
+        }
+    }
+    scenes.push(("1000x16 clustered".to_string(), SceneDescriptor::new(1000).with_bounds(BoundDistribution::Clustered(8))));
+    scenes.push(("1000x16 full screen".to_string(), SceneDescriptor::new(1000).with_bounds(BoundDistribution::FullScreen)));
+    scenes
+}
+
+fn main() {
+    // `cargo bench` passes `--bench` before any filter.
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+
+    for (name, descriptor) in scenes() {
+        let generator = SceneGenerator::new(descriptor, SIZE, SIZE);
+        measure(&filter, &format!("tessellate/{name}"), || {bench::tessellate(generator.shapes());});
+    }
+
+    let Some((device, queue)) = bench::device() else {
+        eprintln!("skipping the GPU benchmarks: no adapter");
+        return;
+    };
+    let target = device.create_texture(&TextureDescriptor{
+        label: None,
+        size: Extent3d{width: SIZE, height: SIZE, depth_or_array_layers: 1},
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    let view = target.create_view(&Default::default());
+
+    for (name, descriptor) in scenes() {
+        let mut generator = SceneGenerator::new(descriptor.clone(), SIZE, SIZE);
+        let mut renderer = CyatRenderer::new(&device, &FORMAT, MultisampleState::default(), None);
+        renderer.resize(SIZE, SIZE);
+        measure(&filter, &format!("prepare/{name}"), || {
+            generator.advance();
+            renderer.prepare(&device, &queue, generator.shapes()).unwrap();
+        });
+
+        let mut generator = SceneGenerator::new(descriptor.clone(), SIZE, SIZE);
+        let mut renderer = CyatRenderer::new(&device, &FORMAT, MultisampleState::default(), None);
+        renderer.resize(SIZE, SIZE);
+        renderer.set_upload_strategy(UploadStrategy::Mapped);
+        measure(&filter, &format!("prepare_mapped/{name}"), || {
+            generator.advance();
+            renderer.prepare(&device, &queue, generator.shapes()).unwrap();
+        });
+
+        let mut generator = SceneGenerator::new(descriptor, SIZE, SIZE);
+        let mut renderer = CyatRenderer::new(&device, &FORMAT, MultisampleState::default(), None);
+        renderer.resize(SIZE, SIZE);
+        let mut scene = CyatSceneGraph::new(renderer);
+        measure(&filter, &format!("scene_graph_prepare/{name}"), || {
+            for &index in generator.changed() {scene.insert(ShapeId(index), generator.shape(index));}
+            scene.prepare(&device, &queue).unwrap();
+            generator.advance();
+        });
+
+        measure(&filter, &format!("render/{name}"), || {
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+            {
+                let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor{
+                    label: None,
+                    color_attachments: &[Some(RenderPassColorAttachment{
+                        view: &view,
+                        resolve_target: None,
+                        ops: Operations{load: LoadOp::Clear(Color::BLACK), store: StoreOp::Store},
+                    })],
+                    ..RenderPassDescriptor::default()
+                });
+                scene.render(&mut render_pass);
+            }
+            queue.submit([encoder.finish()]);
+        });
+        device.poll(wgpu::Maintain::Wait);
+    }
+}
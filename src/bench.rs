@@ -0,0 +1,220 @@
+//! Deterministic synthetic scenes for benchmarking [`CyatRenderer::prepare`]
+//! and [`CyatRenderer::render`], so results compare across machines and
+//! changes. See `benches/scene.rs` for the benchmarks built on them.
+
+use wgpu::{Device, Queue, Instance};
+
+use cyat::{Shape, DrawCommand};
+
+use crate::{CyatRenderer, ShapeArea, ScissorRect, DefaultAttributes, DEFAULT_TOLERANCE, block_on};
+
+/// Where the generated shapes are placed on the target.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BoundDistribution {
+    /// Spread evenly over the whole target.
+    Uniform,
+    /// Gathered around this many points, so many bounds overlap.
+    Clustered(usize),
+    /// Spread evenly, with every bound the whole target.
+    FullScreen,
+}
+
+/// Parameters of a [`SceneGenerator`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneDescriptor {
+    pub shape_count: usize,
+    /// Corners of each shape's polygon, at least 3.
+    pub vertices_per_shape: usize,
+    pub bounds: BoundDistribution,
+    /// Fraction of shapes moved by every [`SceneGenerator::advance`].
+    pub changing: f32,
+    /// Radius of the shapes in NDC.
+    pub radius: f32,
+    pub seed: u64,
+}
+
+impl SceneDescriptor {
+    pub fn new(shape_count: usize) -> Self {
+        SceneDescriptor{
+            shape_count,
+            vertices_per_shape: 16,
+            bounds: BoundDistribution::Uniform,
+            changing: 0.1,
+            radius: 0.05,
+            seed: 0,
+        }
+    }
+
+    pub fn with_vertices_per_shape(mut self, vertices_per_shape: usize) -> Self {
+        self.vertices_per_shape = vertices_per_shape;
+        self
+    }
+
+    pub fn with_bounds(mut self, bounds: BoundDistribution) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    pub fn with_changing(mut self, changing: f32) -> Self {
+        self.changing = changing;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// A generated shape, kept to build it again after it moves.
+#[derive(Copy, Clone, Debug)]
+struct Placed {
+    center: [f32; 2],
+    color: [f32; 3],
+    rotation: f32,
+}
+
+/// Regular polygons placed by a seeded generator, the same for the same
+/// [`SceneDescriptor`] and target size on every machine.
+pub struct SceneGenerator {
+    descriptor: SceneDescriptor,
+    size: (u32, u32),
+    rng: SplitMix64,
+    centers: Vec<[f32; 2]>,
+    shapes: Vec<Placed>,
+    changed: Vec<usize>,
+}
+
+impl SceneGenerator {
+    /// Panics if `vertices_per_shape` is below 3 or `changing` is outside `0.0..=1.0`.
+    pub fn new(descriptor: SceneDescriptor, width: u32, height: u32) -> Self {
+        assert!(descriptor.vertices_per_shape >= 3, "shapes need at least 3 vertices");
+        assert!((0.0..=1.0).contains(&descriptor.changing), "changing must be a fraction of the shapes");
+        let mut rng = SplitMix64(descriptor.seed);
+        let clusters = match descriptor.bounds {
+            BoundDistribution::Clustered(count) => count.max(1),
+            _ => 0,
+        };
+        let centers = (0..clusters).map(|_| [rng.range(-0.8, 0.8), rng.range(-0.8, 0.8)]).collect();
+        let mut generator = SceneGenerator{descriptor, size: (width, height), rng, centers, shapes: Vec::new(), changed: Vec::new()};
+        generator.shapes = (0..generator.descriptor.shape_count).map(|_| generator.place()).collect();
+        generator.changed = (0..generator.shapes.len()).collect();
+        generator
+    }
+
+    pub fn descriptor(&self) -> &SceneDescriptor {
+        &self.descriptor
+    }
+
+    /// Move [`SceneDescriptor::changing`] of the shapes to new places.
+    pub fn advance(&mut self) {
+        let count = (self.shapes.len() as f32 * self.descriptor.changing).round() as usize;
+        self.changed.clear();
+        for _ in 0..count {
+            let index = self.rng.next() as usize % self.shapes.len();
+            self.shapes[index] = self.place();
+            self.changed.push(index);
+        }
+        self.changed.sort_unstable();
+        self.changed.dedup();
+    }
+
+    /// Indices of the shapes moved by the last [`Self::advance`], or all of
+    /// them before the first.
+    pub fn changed(&self) -> &[usize] {
+        &self.changed
+    }
+
+    pub fn shape(&self, index: usize) -> ShapeArea {
+        let Placed{center: [cx, cy], color, rotation} = self.shapes[index];
+        let attributes = DefaultAttributes{color, z: 0.0, palette: 0};
+        let (radius, corners) = (self.descriptor.radius, self.descriptor.vertices_per_shape);
+        let point = |i: usize| {
+            let angle = rotation + i as f32 / corners as f32 * std::f32::consts::TAU;
+            [cx + radius * angle.cos(), cy + radius * angle.sin()]
+        };
+        let commands = (1..corners).map(|i| {
+            let [x, y] = point(i);
+            DrawCommand::LineTo(attributes, x, y)
+        }).collect();
+        let [x, y] = point(0);
+        ShapeArea::shape(Shape::Draw(attributes, x, y, commands), None, self.bound(cx, cy))
+    }
+
+    /// Every shape of the current frame.
+    pub fn shapes(&self) -> Vec<ShapeArea> {
+        (0..self.shapes.len()).map(|index| self.shape(index)).collect()
+    }
+
+    fn place(&mut self) -> Placed {
+        let rng = &mut self.rng;
+        let center = match self.centers.len() {
+            0 => [rng.range(-1.0, 1.0), rng.range(-1.0, 1.0)],
+            clusters => {
+                let [x, y] = self.centers[rng.next() as usize % clusters];
+                [x + rng.range(-0.2, 0.2), y + rng.range(-0.2, 0.2)]
+            }
+        };
+        Placed{center, color: [rng.range(0.0, 1.0), rng.range(0.0, 1.0), rng.range(0.0, 1.0)], rotation: rng.range(0.0, std::f32::consts::TAU)}
+    }
+
+    /// Pixel bound of the square around the shape at `cx`, `cy`.
+    fn bound(&self, cx: f32, cy: f32) -> ScissorRect {
+        let (width, height) = self.size;
+        if self.descriptor.bounds == BoundDistribution::FullScreen {return ScissorRect::from_pos_size(0, 0, width, height);}
+        let radius = self.descriptor.radius;
+        let x = |x: f32| (((x + 1.0) / 2.0 * width as f32).clamp(0.0, width as f32)) as u32;
+        let y = |y: f32| (((1.0 - y) / 2.0 * height as f32).clamp(0.0, height as f32)) as u32;
+        ScissorRect::from_min_max([x(cx - radius), y(cy + radius)], [x(cx + radius).saturating_add(1).min(width), y(cy - radius).saturating_add(1).min(height)])
+    }
+}
+
+/// Tessellate the geometry of `shapes` like [`CyatRenderer::prepare`] does,
+/// returning the number of vertices, to time tessellation without a device.
+pub fn tessellate(shapes: Vec<ShapeArea>) -> usize {
+    shapes.into_iter().map(|shape| {
+        let tolerance = shape.tolerance.unwrap_or(DEFAULT_TOLERANCE);
+        shape.geometry.into_mesh(tolerance, shape.fill_rule).vertices.len()
+    }).sum()
+}
+
+/// Device and queue of the adapter [`CyatRenderer::select_adapter`] picks,
+/// `None` when there is none.
+pub fn device() -> Option<(Device, Queue)> {
+    let instance = Instance::default();
+    let adapter = block_on(CyatRenderer::select_adapter(&instance, None))?;
+    block_on(CyatRenderer::request_device(&adapter)).ok()
+}
+
+/// Small seeded generator, so scenes do not depend on an RNG crate's version.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (self.next() >> 40) as f32 / (1u64 << 24) as f32 * (max - min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_scene() {
+        let frames = |seed| {
+            let mut generator = SceneGenerator::new(SceneDescriptor::new(100).with_changing(0.2).with_seed(seed), 64, 64);
+            (0..3).map(|_| {generator.advance(); generator.changed().to_vec()}).collect::<Vec<_>>()
+        };
+        assert_eq!(frames(7), frames(7));
+        assert_ne!(frames(7), frames(8));
+        assert!(frames(7).iter().all(|changed| !changed.is_empty() && changed.len() <= 20));
+    }
+}
@@ -0,0 +1,180 @@
+use wgpu::{PipelineCompilationOptions, RenderPipelineDescriptor, PipelineLayoutDescriptor, BindGroupLayoutDescriptor, BindGroupLayoutEntry, RenderPassColorAttachment, RenderPassDescriptor, TextureSampleType, TextureViewDimension, SamplerBindingType, TextureDescriptor, SamplerDescriptor, BindGroupDescriptor, BufferBindingType, BindGroupLayout, MultisampleState, CommandEncoder, TextureDimension, RenderPipeline, PrimitiveState, TextureUsages, TextureFormat, BindGroupEntry, BindingResource, FragmentState, BufferUsages, ShaderStages, BindingType, TextureView, VertexState, FilterMode, StoreOp, Operations, Texture, Sampler, LoadOp, Device};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::ScissorRect;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlurUniforms {
+    direction: [f32; 2],
+    radius: f32,
+    _padding: f32,
+}
+
+/// Two pass separable Gaussian blur of part of an already rendered texture,
+/// for frosted glass panels.
+///
+/// Blur the backdrop under a panel's bound, then draw the panel over it with
+/// a translucent [`ShapeUniforms::tint`](crate::ShapeUniforms::tint) and a
+/// blending renderer. The blur is limited to axis aligned rects.
+pub struct BackdropBlur {
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    format: TextureFormat,
+    intermediate: Option<(Texture, TextureView)>,
+}
+
+impl BackdropBlur {
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let texture_entry = BindGroupLayoutEntry{
+            binding: 0,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture{
+                sample_type: TextureSampleType::Float{filterable: true},
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let sampler_entry = BindGroupLayoutEntry{
+            binding: 1,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None,
+        };
+        let uniform_entry = BindGroupLayoutEntry{
+            binding: 2,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer{ty: BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None},
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[texture_entry, sampler_entry, uniform_entry],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("blur.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor{
+            bind_group_layouts: &[&layout],
+            ..PipelineLayoutDescriptor::default()
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor{
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: VertexState{
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &[]
+            },
+            fragment: Some(FragmentState{
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[Some(format.into())],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache: None
+        });
+
+        let sampler = device.create_sampler(&SamplerDescriptor{
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+
+        BackdropBlur{pipeline, layout, sampler, format, intermediate: None}
+    }
+
+    /// Blur `rect` of `source` by a Gaussian with a `radius` in pixels and
+    /// write the result to the same rect of `target`, leaving the rest of
+    /// `target` untouched. `target` may be a view of `source`.
+    ///
+    /// `source` needs [`TextureUsages::TEXTURE_BINDING`] and must have the
+    /// format this blur was created with. The intermediate texture is
+    /// recreated whenever the source size changes.
+    pub fn blur(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        source: &Texture,
+        target: &TextureView,
+        rect: ScissorRect,
+        radius: f32,
+    ) {
+        let size = source.size();
+        let Some(rect) = rect.intersect(&ScissorRect::from_pos_size(0, 0, size.width, size.height)) else {return;};
+
+        if self.intermediate.as_ref().is_none_or(|(texture, _)| texture.size() != size) {
+            let texture = device.create_texture(&TextureDescriptor{
+                label: None,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: self.format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&Default::default());
+            self.intermediate = Some((texture, view));
+        }
+        let (_, intermediate) = self.intermediate.as_ref().unwrap();
+
+        // The vertical pass reads `radius` pixels above and below the rect.
+        let margin = radius.ceil() as u32;
+        let horizontal_rect = ScissorRect::from_min_max(
+            [rect.x, rect.y.saturating_sub(margin)],
+            [rect.max()[0], (rect.max()[1] + margin).min(size.height)],
+        );
+
+        let source_view = source.create_view(&Default::default());
+        self.pass(device, encoder, &source_view, intermediate, horizontal_rect, [1.0, 0.0], radius);
+        self.pass(device, encoder, intermediate, target, rect, [0.0, 1.0], radius);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn pass(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        source: &TextureView,
+        target: &TextureView,
+        rect: ScissorRect,
+        direction: [f32; 2],
+        radius: f32,
+    ) {
+        let uniforms = device.create_buffer_init(&BufferInitDescriptor{
+            label: None,
+            contents: bytemuck::bytes_of(&BlurUniforms{direction, radius, _padding: 0.0}),
+            usage: BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor{
+            label: None,
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry{binding: 0, resource: BindingResource::TextureView(source)},
+                BindGroupEntry{binding: 1, resource: BindingResource::Sampler(&self.sampler)},
+                BindGroupEntry{binding: 2, resource: uniforms.as_entire_binding()},
+            ],
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor{
+            label: None,
+            color_attachments: &[Some(RenderPassColorAttachment{
+                view: target,
+                resolve_target: None,
+                ops: Operations{load: LoadOp::Load, store: StoreOp::Store},
+            })],
+            ..RenderPassDescriptor::default()
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.set_scissor_rect(rect.x, rect.y, rect.width, rect.height);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,44 @@
+struct BlurUniforms {
+    direction: vec2<f32>,
+    radius: f32,
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> blur: BlurUniforms;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// One triangle covering the whole target; the scissor rect limits the blur.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(source));
+    let uv = in.clip_position.xy / size;
+    let step = blur.direction / size;
+    let sigma = max(blur.radius / 2.0, 0.0001);
+    let taps = i32(ceil(blur.radius));
+
+    var color = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var i = -taps; i <= taps; i += 1) {
+        let x = f32(i);
+        let weight = exp(-x * x / (2.0 * sigma * sigma));
+        color += textureSampleLevel(source, source_sampler, uv + step * x, 0.0) * weight;
+        total += weight;
+    }
+    return color / total;
+}
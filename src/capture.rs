@@ -0,0 +1,512 @@
+//! Capture the shapes of a frame to a file and load them again, to
+//! reproduce a user's frame with the [`harness`](crate::harness).
+//!
+//! The file starts with [`MAGIC`], the format version and the shape count.
+//! Each shape is a list of fields, each a `u16` tag and a `u32` byte length
+//! before its data, ending with tag 0. Readers skip fields with tags they do
+//! not know, so later versions can add fields without breaking older readers.
+//! Numbers are little endian.
+
+use std::path::{Path, PathBuf};
+
+use cyat::{VertexBuffers, Shape, DrawCommand};
+
+use crate::{CyatRenderer, CyatError, ShapeArea, Geometry, ScissorRect, Topology, RoundedClip, ColorEffect, ClipMode, DefaultAttributes, DefaultVertex, GradientType, ConicalGradient, MeshGradient, NoiseFill, GradientStop, GradientInterpolation, FillRule};
+
+/// First bytes of every capture file.
+pub const MAGIC: [u8; 8] = *b"CYATCAP\0";
+
+/// Version of the format written. It only grows when existing fields
+/// change, so files of later versions are refused. Version 2 added the
+/// palette slot to attributes and mesh vertices.
+pub const CAPTURE_VERSION: u32 = 2;
+
+const END: u16 = 0;
+const GEOMETRY: u16 = 1;
+const BOUND: u16 = 2;
+const TOLERANCE: u16 = 3;
+const LABEL: u16 = 4;
+const TOPOLOGY: u16 = 5;
+const UNIFORMS: u16 = 6;
+const PARENT: u16 = 7;
+const Z_INDEX: u16 = 8;
+const MASK: u16 = 9;
+const FLAGS: u16 = 10;
+const GRADIENT: u16 = 11;
+const PIPELINE: u16 = 12;
+const ROUNDED_CLIP: u16 = 13;
+const EFFECT: u16 = 14;
+const VIEWPORT: u16 = 15;
+const STENCIL_REFERENCE: u16 = 16;
+const FILL_RULE: u16 = 17;
+const USER_DATA: u16 = 18;
+const GROUP_OPACITY: u16 = 19;
+
+impl CyatRenderer {
+    /// Write the shapes of the next [`Self::prepare`] or
+    /// [`Self::prepare_ref`] to `path` for [`replay`], then stop capturing.
+    ///
+    /// Shapes built from a [`ShapeBuilder`](cyat::ShapeBuilder) are
+    /// tessellated when captured and prepared from the resulting mesh, and
+    /// shapes without a tolerance are captured with the renderer's. Sprite
+    /// textures live on the GPU and are left out. Only available with the
+    /// `capture` feature; the write fails the prepare with
+    /// [`CyatError::Capture`].
+    pub fn capture_next_frame(&mut self, path: impl Into<PathBuf>) {
+        self.capture_path = Some(path.into());
+    }
+
+    /// Capture `shapes` when a capture is pending, meshing their builders.
+    pub(crate) fn capture_owned(&mut self, shapes: &mut [ShapeArea]) -> Result<(), CyatError> {
+        if self.capture_path.is_none() {return Ok(());}
+        for shape in shapes.iter_mut() {
+            let tolerance = shape.tolerance.unwrap_or(self.tolerance);
+            if shape.geometry.needs_owned() {
+                let geometry = std::mem::replace(&mut shape.geometry, Geometry::Mesh(VertexBuffers::new()));
+                shape.geometry = Geometry::Mesh(geometry.into_mesh(tolerance, shape.fill_rule));
+            }
+            if let Some(mask) = shape.mask.as_mut().filter(|mask| mask.needs_owned()) {
+                let geometry = std::mem::replace(mask, Geometry::Mesh(VertexBuffers::new()));
+                *mask = Geometry::Mesh(geometry.into_mesh(tolerance, shape.fill_rule));
+            }
+        }
+        self.capture(shapes)
+    }
+
+    /// Capture `shapes` when a capture is pending; none may hold a builder.
+    pub(crate) fn capture(&mut self, shapes: &[ShapeArea]) -> Result<(), CyatError> {
+        let Some(path) = self.capture_path.take() else {return Ok(());};
+        std::fs::write(&path, encode(shapes, self.tolerance))
+            .map_err(|error| CyatError::Capture(format!("failed to write {}: {error}", path.display())))
+    }
+}
+
+/// Load the shapes captured by [`CyatRenderer::capture_next_frame`].
+pub fn replay(path: impl AsRef<Path>) -> std::io::Result<Vec<ShapeArea>> {
+    decode(&std::fs::read(path)?)
+}
+
+fn encode(shapes: &[ShapeArea], tolerance: f32) -> Vec<u8> {
+    let mut writer = Writer(MAGIC.to_vec());
+    writer.u32(CAPTURE_VERSION);
+    writer.u32(shapes.len() as u32);
+    for shape in shapes {
+        writer.field(GEOMETRY, |w| w.geometry(&shape.geometry, shape.tolerance.unwrap_or(tolerance)));
+        if let Some(bound) = shape.bound {writer.field(BOUND, |w| for value in [bound.x, bound.y, bound.width, bound.height] {w.u32(value);});}
+        writer.field(TOLERANCE, |w| w.f32(shape.tolerance.unwrap_or(tolerance)));
+        if let Some(label) = &shape.label {writer.field(LABEL, |w| w.0.extend(label.as_bytes()));}
+        writer.field(TOPOLOGY, |w| w.u8(shape.topology as u8));
+        writer.field(UNIFORMS, |w| w.f32s(bytemuck::cast_slice(&[shape.uniforms])));
+        if let Some(parent) = shape.parent {writer.field(PARENT, |w| w.u32(parent as u32));}
+        writer.field(Z_INDEX, |w| w.0.extend(shape.z_index.to_le_bytes()));
+        if let Some(mask) = &shape.mask {writer.field(MASK, |w| w.geometry(mask, shape.tolerance.unwrap_or(tolerance)));}
+        writer.field(FLAGS, |w| w.u8(shape.overlay as u8 | (shape.opaque as u8) << 1 | ((shape.clip_mode == ClipMode::Outside) as u8) << 2));
+        if let Some(gradient) = &shape.gradient {writer.field(GRADIENT, |w| w.gradient(gradient));}
+        if let Some(pipeline) = &shape.pipeline {writer.field(PIPELINE, |w| w.0.extend(pipeline.as_bytes()));}
+        if let Some(clip) = shape.rounded_clip {
+            writer.field(ROUNDED_CLIP, |w| {
+                w.f32s(&clip.radii);
+                w.u8(clip.antialias as u8);
+            });
+        }
+        writer.field(EFFECT, |w| w.f32s(&[shape.effect.saturation, shape.effect.brightness]));
+        if shape.stencil_reference != 0 {writer.field(STENCIL_REFERENCE, |w| w.u32(shape.stencil_reference));}
+        if shape.fill_rule == FillRule::NonZero {writer.field(FILL_RULE, |w| w.u8(1));}
+        if shape.user_data != 0 {writer.field(USER_DATA, |w| w.u64(shape.user_data));}
+        if shape.group_opacity != 1.0 {writer.field(GROUP_OPACITY, |w| w.f32s(&[shape.group_opacity]));}
+        if let Some(viewport) = shape.viewport {writer.field(VIEWPORT, |w| for value in [viewport.x, viewport.y, viewport.width, viewport.height] {w.u32(value);});}
+        writer.0.extend(END.to_le_bytes());
+    }
+    writer.0
+}
+
+fn decode(bytes: &[u8]) -> std::io::Result<Vec<ShapeArea>> {
+    let mut reader = Reader(bytes, CAPTURE_VERSION);
+    if reader.take(MAGIC.len())? != MAGIC {return Err(invalid("not a wgpu_cyat capture"));}
+    let version = reader.u32()?;
+    if version > CAPTURE_VERSION {return Err(invalid(format!("capture version {version} is newer than {CAPTURE_VERSION}")));}
+    reader.1 = version;
+    let count = reader.u32()? as usize;
+    let mut shapes = Vec::with_capacity(count.min(bytes.len()));
+    for _ in 0..count {
+        let mut shape = None::<ShapeArea>;
+        let mut fields = Vec::new();
+        loop {
+            let tag = u16::from_le_bytes(reader.array()?);
+            if tag == END {break;}
+            let len = reader.u32()? as usize;
+            let mut data = Reader(reader.take(len)?, version);
+            match tag {
+                GEOMETRY => shape = Some(ShapeArea::from_geometry(data.geometry()?, None)),
+                _ => fields.push((tag, data)),
+            }
+        }
+        let mut shape = shape.ok_or_else(|| invalid("shape without geometry"))?;
+        for (tag, mut data) in fields {
+            match tag {
+                BOUND => shape.bound = Some(ScissorRect::from_pos_size(data.u32()?, data.u32()?, data.u32()?, data.u32()?)),
+                TOLERANCE => shape.tolerance = Some(data.f32()?),
+                LABEL => shape.label = Some(data.string()?),
+                TOPOLOGY => shape.topology = match data.u8()? {
+                    0 => Topology::TriangleList,
+                    1 => Topology::LineList,
+                    2 => Topology::PointList,
+                    topology => return Err(invalid(format!("unknown topology {topology}"))),
+                },
+                UNIFORMS => shape.uniforms = bytemuck::cast(data.f32s::<8>()?),
+                PARENT => shape.parent = Some(data.u32()? as usize),
+                Z_INDEX => shape.z_index = i32::from_le_bytes(data.array()?),
+                MASK => shape.mask = Some(data.geometry()?),
+                FLAGS => {
+                    let flags = data.u8()?;
+                    (shape.overlay, shape.opaque) = (flags & 1 != 0, flags & 2 != 0);
+                    if flags & 4 != 0 {shape.clip_mode = ClipMode::Outside;}
+                }
+                GRADIENT => shape.gradient = Some(data.gradient()?),
+                PIPELINE => shape.pipeline = Some(data.string()?),
+                ROUNDED_CLIP => shape.rounded_clip = Some(RoundedClip{radii: data.f32s()?, antialias: data.u8()? != 0}),
+                EFFECT => {
+                    let [saturation, brightness] = data.f32s()?;
+                    shape.effect = ColorEffect{saturation, brightness};
+                }
+                VIEWPORT => shape.viewport = Some(ScissorRect::from_pos_size(data.u32()?, data.u32()?, data.u32()?, data.u32()?)),
+                STENCIL_REFERENCE => shape.stencil_reference = data.u32()?,
+                FILL_RULE => shape.fill_rule = match data.u8()? {
+                    0 => FillRule::EvenOdd,
+                    1 => FillRule::NonZero,
+                    rule => return Err(invalid(format!("unknown fill rule {rule}"))),
+                },
+                USER_DATA => shape.user_data = data.u64()?,
+                GROUP_OPACITY => [shape.group_opacity] = data.f32s()?,
+                // Added by a later version.
+                _ => {}
+            }
+        }
+        shapes.push(shape);
+    }
+    Ok(shapes)
+}
+
+/// The palette slot of version 1 files, which stored slot `n` as a red
+/// channel of `-n - 1`.
+fn legacy_palette(red: f32) -> u32 {
+    if red < 0.0 {(-red).round() as u32} else {0}
+}
+
+fn invalid(message: impl Into<String>) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.into())
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn f32s(&mut self, values: &[f32]) {
+        for &value in values {self.f32(value);}
+    }
+
+    /// A field tagged `tag` holding what `write` writes.
+    fn field(&mut self, tag: u16, write: impl FnOnce(&mut Writer)) {
+        self.0.extend(tag.to_le_bytes());
+        let start = self.0.len();
+        self.u32(0);
+        write(self);
+        let len = (self.0.len() - start - 4) as u32;
+        self.0[start..start + 4].copy_from_slice(&len.to_le_bytes());
+    }
+
+    fn attributes(&mut self, attributes: &DefaultAttributes) {
+        self.f32s(&attributes.color);
+        self.f32(attributes.z);
+        self.u32(attributes.palette);
+    }
+
+    /// Geometry without builders, see [`CyatRenderer::capture_owned`]. Fill
+    /// geometry is captured as the mesh it appends with `tolerance`.
+    fn geometry(&mut self, geometry: &Geometry, tolerance: f32) {
+        match geometry {
+            Geometry::Mesh(mesh) => {
+                self.u8(0);
+                self.u32(mesh.vertices.len() as u32);
+                for vertex in &mesh.vertices {
+                    self.f32s(&vertex.position);
+                    self.f32s(&vertex.color);
+                    self.f32(vertex.z);
+                    self.u32(vertex.palette);
+                }
+                self.u32(mesh.indices.len() as u32);
+                for &index in &mesh.indices {self.0.extend(index.to_le_bytes());}
+            }
+            Geometry::Shape(shape, stroke_width) => {
+                self.u8(1);
+                match shape {
+                    Shape::Draw(attributes, x, y, commands) => {
+                        self.u8(0);
+                        self.attributes(attributes);
+                        self.f32s(&[*x, *y]);
+                        self.u32(commands.len() as u32);
+                        for command in commands {
+                            match command {
+                                DrawCommand::LineTo(attributes, x, y) => {
+                                    self.u8(0);
+                                    self.attributes(attributes);
+                                    self.f32s(&[*x, *y]);
+                                }
+                                DrawCommand::QuadraticBezierTo(attributes, x, y, cx, cy) => {
+                                    self.u8(1);
+                                    self.attributes(attributes);
+                                    self.f32s(&[*x, *y, *cx, *cy]);
+                                }
+                                DrawCommand::CubicBezierTo(attributes, x, y, cx, cy, cx2, cy2) => {
+                                    self.u8(2);
+                                    self.attributes(attributes);
+                                    self.f32s(&[*x, *y, *cx, *cy, *cx2, *cy2]);
+                                }
+                            }
+                        }
+                    }
+                    Shape::RoundedRectangle(attributes, x, y, w, h, rx, ry) => {
+                        self.u8(1);
+                        self.attributes(attributes);
+                        self.f32s(&[*x, *y, *w, *h, *rx, *ry]);
+                    }
+                    Shape::Rectangle(attributes, x, y, x2, y2) => {
+                        self.u8(2);
+                        self.attributes(attributes);
+                        self.f32s(&[*x, *y, *x2, *y2]);
+                    }
+                    Shape::Ellipse(attributes, x, y, w, h) => {
+                        self.u8(3);
+                        self.attributes(attributes);
+                        self.f32s(&[*x, *y, *w, *h]);
+                    }
+                }
+                match stroke_width {
+                    Some(width) => {
+                        self.u8(1);
+                        self.f32(*width);
+                    }
+                    None => self.u8(0),
+                }
+            }
+            Geometry::Scaled(geometry, scale) => {
+                self.u8(2);
+                self.f32s(scale);
+                self.geometry(geometry, tolerance);
+            }
+            Geometry::Fill(_) => {
+                let mut mesh = VertexBuffers::new();
+                geometry.build_ref(tolerance, FillRule::EvenOdd, &mut mesh);
+                self.geometry(&Geometry::Mesh(mesh), tolerance);
+            }
+            Geometry::Builder(_) => unreachable!("builders are meshed before capture"),
+        }
+    }
+
+    fn gradient(&mut self, gradient: &GradientType) {
+        match gradient {
+            GradientType::Conical(ConicalGradient{center, start_angle, stops, interpolation}) => {
+                self.u8(0);
+                self.f32s(center);
+                self.f32(*start_angle);
+                self.u32(stops.len() as u32);
+                for stop in stops {
+                    self.f32(stop.offset);
+                    self.f32s(&stop.color);
+                }
+                self.u8(*interpolation as u8);
+            }
+            GradientType::Mesh(MeshGradient{corners}) => {
+                self.u8(1);
+                self.f32s(corners.as_flattened());
+            }
+            GradientType::Noise(NoiseFill{scale, octaves, seed, color_a, color_b}) => {
+                self.u8(2);
+                self.f32(*scale);
+                self.u32(*octaves);
+                self.u32(*seed);
+                self.f32s(color_a);
+                self.f32s(color_b);
+            }
+        }
+    }
+}
+
+/// The bytes left to read and the version of the file they are from.
+struct Reader<'a>(&'a [u8], u32);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> std::io::Result<&'a [u8]> {
+        if len > self.0.len() {return Err(invalid("capture ends early"));}
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> std::io::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> std::io::Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> std::io::Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> std::io::Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> std::io::Result<f32> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn f32s<const N: usize>(&mut self) -> std::io::Result<[f32; N]> {
+        let mut values = [0.0; N];
+        for value in &mut values {*value = self.f32()?;}
+        Ok(values)
+    }
+
+    /// A count followed by that many items, refusing counts the remaining
+    /// bytes cannot hold.
+    fn list<T>(&mut self, item_size: usize, mut item: impl FnMut(&mut Self) -> std::io::Result<T>) -> std::io::Result<Vec<T>> {
+        let count = self.u32()? as usize;
+        if count.saturating_mul(item_size) > self.0.len() {return Err(invalid("capture ends early"));}
+        (0..count).map(|_| item(self)).collect()
+    }
+
+    fn string(&mut self) -> std::io::Result<String> {
+        String::from_utf8(self.take(self.0.len())?.to_vec()).map_err(|_| invalid("string is not UTF-8"))
+    }
+
+    fn attributes(&mut self) -> std::io::Result<DefaultAttributes> {
+        let [r, g, b, z] = self.f32s()?;
+        match self.1 {
+            1 => Ok(DefaultAttributes{color: [r.max(0.0), g, b], z, palette: legacy_palette(r)}),
+            _ => Ok(DefaultAttributes{color: [r, g, b], z, palette: self.u32()?}),
+        }
+    }
+
+    fn geometry(&mut self) -> std::io::Result<Geometry> {
+        match self.u8()? {
+            0 => {
+                let vertices = match self.1 {
+                    1 => self.list(24, |r| {
+                        let [x, y, red, g, b, z] = r.f32s()?;
+                        Ok(DefaultVertex{position: [x, y], color: [red.max(0.0), g, b], z, palette: legacy_palette(red)})
+                    })?,
+                    _ => self.list(28, |r| {
+                        let [x, y, red, g, b, z] = r.f32s()?;
+                        Ok(DefaultVertex{position: [x, y], color: [red, g, b], z, palette: r.u32()?})
+                    })?,
+                };
+                let indices = self.list(2, |r| Ok(u16::from_le_bytes(r.array()?)))?;
+                if indices.iter().any(|&index| index as usize >= vertices.len()) {return Err(invalid("mesh index out of range"));}
+                Ok(Geometry::Mesh(VertexBuffers{vertices, indices}))
+            }
+            1 => {
+                let shape = match self.u8()? {
+                    0 => {
+                        let attributes = self.attributes()?;
+                        let [x, y] = self.f32s()?;
+                        let commands = self.list(21, |r| {
+                            Ok(match r.u8()? {
+                                0 => {
+                                    let attributes = r.attributes()?;
+                                    let [x, y] = r.f32s()?;
+                                    DrawCommand::LineTo(attributes, x, y)
+                                }
+                                1 => {
+                                    let attributes = r.attributes()?;
+                                    let [x, y, cx, cy] = r.f32s()?;
+                                    DrawCommand::QuadraticBezierTo(attributes, x, y, cx, cy)
+                                }
+                                2 => {
+                                    let attributes = r.attributes()?;
+                                    let [x, y, cx, cy, cx2, cy2] = r.f32s()?;
+                                    DrawCommand::CubicBezierTo(attributes, x, y, cx, cy, cx2, cy2)
+                                }
+                                command => return Err(invalid(format!("unknown draw command {command}"))),
+                            })
+                        })?;
+                        Shape::Draw(attributes, x, y, commands)
+                    }
+                    1 => {
+                        let attributes = self.attributes()?;
+                        let [x, y, w, h, rx, ry] = self.f32s()?;
+                        Shape::RoundedRectangle(attributes, x, y, w, h, rx, ry)
+                    }
+                    2 => {
+                        let attributes = self.attributes()?;
+                        let [x, y, x2, y2] = self.f32s()?;
+                        Shape::Rectangle(attributes, x, y, x2, y2)
+                    }
+                    3 => {
+                        let attributes = self.attributes()?;
+                        let [x, y, w, h] = self.f32s()?;
+                        Shape::Ellipse(attributes, x, y, w, h)
+                    }
+                    shape => return Err(invalid(format!("unknown shape {shape}"))),
+                };
+                let stroke_width = match self.u8()? {
+                    0 => None,
+                    _ => Some(self.f32()?),
+                };
+                Ok(Geometry::Shape(shape, stroke_width))
+            }
+            2 => {
+                let scale = self.f32s()?;
+                Ok(Geometry::Scaled(Box::new(self.geometry()?), scale))
+            }
+            geometry => Err(invalid(format!("unknown geometry {geometry}"))),
+        }
+    }
+
+    fn gradient(&mut self) -> std::io::Result<GradientType> {
+        match self.u8()? {
+            0 => {
+                let center = self.f32s()?;
+                let start_angle = self.f32()?;
+                let stops = self.list(20, |r| {
+                    let [offset, red, green, blue, alpha] = r.f32s()?;
+                    Ok(GradientStop{offset, color: [red, green, blue, alpha]})
+                })?;
+                // Captures before the interpolation end with the stops.
+                let interpolation = match if self.0.is_empty() {0} else {self.u8()?} {
+                    0 => GradientInterpolation::LinearLight,
+                    1 => GradientInterpolation::Srgb,
+                    2 => GradientInterpolation::OklabPerceptual,
+                    interpolation => return Err(invalid(format!("unknown gradient interpolation {interpolation}"))),
+                };
+                Ok(GradientType::Conical(ConicalGradient{center, start_angle, stops, interpolation}))
+            }
+            1 => {
+                let corners: [f32; 16] = self.f32s()?;
+                Ok(GradientType::Mesh(MeshGradient{corners: bytemuck::cast(corners)}))
+            }
+            2 => {
+                let scale = self.f32()?;
+                let (octaves, seed) = (self.u32()?, self.u32()?);
+                Ok(GradientType::Noise(NoiseFill{scale, octaves, seed, color_a: self.f32s()?, color_b: self.f32s()?}))
+            }
+            gradient => Err(invalid(format!("unknown gradient {gradient}"))),
+        }
+    }
+}
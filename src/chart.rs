@@ -0,0 +1,267 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use cyat::{Shape, DrawCommand, VertexBuffers, Vertex};
+
+use crate::{ShapeArea, ScissorRect, DefaultAttributes, DefaultVertex, Geometry};
+
+/// One value of a [`ShapeArea::pie_chart`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PieSlice {
+    /// Share of the chart relative to the other slices; not positive values
+    /// get no slice.
+    pub value: f32,
+    pub color: [f32; 3],
+}
+
+/// Options of a [`ShapeArea::pie_chart`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PieChart {
+    /// Angle left empty between neighbouring slices.
+    pub gap_degrees: f32,
+    /// Radius of the hole in the middle, making a donut chart when positive.
+    pub inner_radius: f32,
+}
+
+/// One value of a [`ShapeArea::bar_chart`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BarData {
+    pub value: f32,
+    pub color: [f32; 3],
+}
+
+/// Options of a [`ShapeArea::bar_chart`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BarChartOptions {
+    /// Space between neighbouring bars.
+    pub bar_gap: f32,
+    /// Where bars start, as a y value, or an x value when `horizontal`.
+    /// Clamped to the chart area.
+    pub baseline: f32,
+    /// Bars grow along x and are laid out from the top down.
+    pub horizontal: bool,
+}
+
+/// Options of a [`ShapeArea::line_chart`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LineChartOptions {
+    pub stroke_width: f32,
+    pub stroke_color: [f32; 3],
+    /// Fill the area between the line and the bottom of the chart with this
+    /// color at half opacity, which shows with a
+    /// [`CyatRendererDescriptor::blend`](crate::CyatRendererDescriptor::blend).
+    pub fill_under: Option<[f32; 3]>,
+    /// Data indices at the left and right edges of the chart. An empty range
+    /// spans every value.
+    pub x_range: [f32; 2],
+    /// Values at the bottom and top edges of the chart. An empty range spans
+    /// the smallest to the largest value.
+    pub y_range: [f32; 2],
+}
+
+/// Marker drawn at every point of a [`ShapeArea::scatter_plot`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MarkerShape {
+    /// An octagon, round enough at marker sizes.
+    Circle,
+    Square,
+    Diamond,
+    /// A plus sign with arms a third of the size thick.
+    Cross,
+}
+
+impl MarkerShape {
+    fn vertex_count(self) -> usize {
+        match self {
+            MarkerShape::Circle | MarkerShape::Cross => 8,
+            MarkerShape::Square | MarkerShape::Diamond => 4,
+        }
+    }
+
+    /// Most markers of this shape one scatter plot can hold, as a shape's
+    /// vertices are indexed with 16 bits.
+    pub fn max_markers(self) -> usize {
+        (u16::MAX as usize + 1) / self.vertex_count()
+    }
+}
+
+/// Data values at the edges of a [`ShapeArea::scatter_plot`], mapped to the
+/// edges of the viewport.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DataRange {
+    /// Values at the left and right edges.
+    pub x: [f32; 2],
+    /// Values at the bottom and top edges.
+    pub y: [f32; 2],
+}
+
+impl ShapeArea {
+    /// A bar chart of `data` filling `rect`, given as corners `[x, y, x2, y2]`
+    /// in the same space as vertex positions, with one
+    /// [`ShapeArea::rect`] per bar in the same order, all scissored to
+    /// `bound`.
+    ///
+    /// Bars run left to right from the baseline, up for positive values and
+    /// down for negative ones. They share one scale, chosen so the longest
+    /// bar on either side reaches the edge of `rect`.
+    pub fn bar_chart(rect: [f32; 4], data: &[BarData], options: BarChartOptions, bound: impl Into<ScissorRect>) -> Vec<ShapeArea> {
+        let bound = bound.into();
+        // Work with bars growing along y, swapping axes back at the end.
+        let [x, y, x2, y2] = if options.horizontal {[rect[1], rect[0], rect[3], rect[2]]} else {rect};
+        let ([left, right], [bottom, top]) = ([x.min(x2), x.max(x2)], [y.min(y2), y.max(y2)]);
+        let baseline = options.baseline.clamp(bottom, top);
+
+        let max_positive = data.iter().map(|bar| bar.value).fold(0.0, f32::max);
+        let max_negative = data.iter().map(|bar| -bar.value).fold(0.0, f32::max);
+        let scale = [(top - baseline, max_positive), (baseline - bottom, max_negative)].into_iter()
+            .filter(|&(_, max)| max > 0.0)
+            .map(|(extent, max)| extent / max)
+            .fold(f32::INFINITY, f32::min);
+
+        let count = data.len() as f32;
+        let width = ((right - left - options.bar_gap * (count - 1.0)) / count).max(0.0);
+        data.iter().enumerate().map(|(i, bar)| {
+            let start = left + i as f32 * (width + options.bar_gap);
+            let end = if scale.is_finite() {baseline + bar.value * scale} else {baseline};
+            let corners = if options.horizontal {
+                // Laid out from the top, which is the largest y.
+                let (top, bottom) = (right - (start - left), right - (start - left) - width);
+                [baseline.min(end), bottom, baseline.max(end), top]
+            } else {
+                [start, baseline.min(end), start + width, baseline.max(end)]
+            };
+            ShapeArea::rect(corners, bar.color, bound)
+        }).collect()
+    }
+
+    /// A line through `data`, evenly spaced along x, in `rect` given as
+    /// corners `[x, y, x2, y2]` in the same space as vertex positions. Returns
+    /// the filled area under the line first if there is one, then the line,
+    /// all scissored to `bound`. Needs at least two values.
+    ///
+    /// cyat closes every path, so the line runs through the points and back
+    /// and may blend twice with a translucent tint.
+    pub fn line_chart(rect: [f32; 4], data: &[f32], options: LineChartOptions, bound: impl Into<ScissorRect>) -> Vec<ShapeArea> {
+        let bound = bound.into();
+        if data.len() < 2 {return Vec::new();}
+        let ([left, right], [bottom, top]) = ([rect[0].min(rect[2]), rect[0].max(rect[2])], [rect[1].min(rect[3]), rect[1].max(rect[3])]);
+        let fit = |range: [f32; 2], min: f32, max: f32| if range[0] == range[1] {[min, max]} else {range};
+        let x_range = fit(options.x_range, 0.0, (data.len() - 1) as f32);
+        let y_range = fit(options.y_range, data.iter().copied().fold(f32::INFINITY, f32::min), data.iter().copied().fold(f32::NEG_INFINITY, f32::max));
+        let map = |value: f32, range: [f32; 2], min: f32, max: f32| {
+            let t = if range[0] == range[1] {0.5} else {(value - range[0]) / (range[1] - range[0])};
+            min + t * (max - min)
+        };
+        let points: Vec<[f32; 2]> = data.iter().enumerate()
+            .map(|(i, &value)| [map(i as f32, x_range, left, right), map(value, y_range, bottom, top)])
+            .collect();
+
+        let mut shapes = Vec::new();
+        if let Some(color) = options.fill_under {
+            let attributes = DefaultAttributes{color, z: 0.0, palette: 0};
+            let (first, last) = (points[0], points[points.len() - 1]);
+            let mut commands: Vec<_> = points.iter().map(|&[x, y]| DrawCommand::LineTo(attributes, x, y)).collect();
+            commands.push(DrawCommand::LineTo(attributes, last[0], bottom));
+            shapes.push(ShapeArea::shape(Shape::Draw(attributes, first[0], bottom, commands), None, bound).with_opacity(0.5));
+        }
+        let attributes = DefaultAttributes{color: options.stroke_color, z: 0.0, palette: 0};
+        let commands = points.iter().skip(1).chain(points.iter().rev().skip(1))
+            .map(|&[x, y]| DrawCommand::LineTo(attributes, x, y))
+            .collect();
+        shapes.push(ShapeArea::shape(Shape::Draw(attributes, points[0][0], points[0][1], commands), Some(options.stroke_width), bound));
+        shapes
+    }
+
+    /// One shape with a `marker` of `size` in NDC at each of `points`, given
+    /// in data coordinates mapped to the viewport by `range`. The markers are
+    /// built straight into a single mesh, so large point sets cost one shape.
+    ///
+    /// Panics with more than [`MarkerShape::max_markers`] points; split
+    /// larger sets into several plots.
+    pub fn scatter_plot(points: &[[f32; 2]], marker: MarkerShape, size: f32, color: [f32; 3], range: DataRange, bound: impl Into<ScissorRect>) -> ShapeArea {
+        assert!(points.len() <= marker.max_markers(), "{} points exceed the {} {marker:?} markers of a scatter plot", points.len(), marker.max_markers());
+        let outline: Vec<[f32; 2]> = match marker {
+            MarkerShape::Circle => (0..8).map(|i| {
+                let angle = i as f32 * TAU / 8.0;
+                [angle.cos() / 2.0, angle.sin() / 2.0]
+            }).collect(),
+            MarkerShape::Square => vec![[-0.5, -0.5], [0.5, -0.5], [0.5, 0.5], [-0.5, 0.5]],
+            MarkerShape::Diamond => vec![[0.0, -0.5], [0.5, 0.0], [0.0, 0.5], [-0.5, 0.0]],
+            MarkerShape::Cross => {
+                let arm = 1.0 / 6.0;
+                vec![[-0.5, -arm], [0.5, -arm], [0.5, arm], [-0.5, arm], [-arm, -0.5], [arm, -0.5], [arm, 0.5], [-arm, 0.5]]
+            }
+        };
+        // Convex outlines are fans; the cross is two quads.
+        let marker_indices: Vec<u16> = match marker {
+            MarkerShape::Cross => vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7],
+            _ => (1..outline.len() as u16 - 1).flat_map(|i| [0, i, i + 1]).collect(),
+        };
+
+        let map = |value: f32, range: [f32; 2]| if range[0] == range[1] {0.0} else {(value - range[0]) / (range[1] - range[0]) * 2.0 - 1.0};
+        let mut mesh = VertexBuffers::with_capacity(points.len() * outline.len(), points.len() * marker_indices.len());
+        let attributes = DefaultAttributes{color, z: 0.0, palette: 0};
+        for &[x, y] in points {
+            let (cx, cy) = (map(x, range.x), map(y, range.y));
+            let base = mesh.vertices.len() as u16;
+            mesh.vertices.extend(outline.iter().map(|&[dx, dy]| DefaultVertex::construct([cx + dx * size, cy + dy * size], attributes)));
+            mesh.indices.extend(marker_indices.iter().map(|i| base + i));
+        }
+        ShapeArea::from_geometry(Geometry::Mesh(mesh), Some(bound.into()))
+    }
+
+    /// A pie chart of `data` around `cx`, `cy` in NDC, with one shape per
+    /// slice in the same order, so each can be styled, hit tested or culled on
+    /// its own. Slices run clockwise from the top, sized by their share of
+    /// the total value, and are all scissored to `bound`.
+    ///
+    /// Like other shapes in NDC the chart is only round on square targets.
+    pub fn pie_chart(cx: f32, cy: f32, radius: f32, data: &[PieSlice], options: PieChart, bound: impl Into<ScissorRect>) -> Vec<ShapeArea> {
+        let bound = bound.into();
+        let total: f32 = data.iter().map(|slice| slice.value.max(0.0)).sum();
+        let gap = options.gap_degrees.to_radians();
+        let mut start = FRAC_PI_2;
+        data.iter().map(|slice| {
+            let sweep = if total > 0.0 {slice.value.max(0.0) / total * TAU} else {0.0};
+            let end = start - sweep;
+            // Split the gap between both sides of every slice, unless a
+            // single slice fills the whole circle.
+            let inset = if sweep < TAU {gap / 2.0} else {0.0};
+            let (from, to) = (start - inset, end + inset);
+            start = end;
+
+            if from <= to {return ShapeArea::from_geometry(Geometry::Mesh(VertexBuffers::new()), Some(bound));}
+            let attributes = DefaultAttributes{color: slice.color, z: 0.0, palette: 0};
+            let point = |radius: f32, angle: f32| [cx + radius * angle.cos(), cy + radius * angle.sin()];
+            let [x, y] = point(radius, from);
+            let mut commands = Vec::new();
+            arc(&mut commands, attributes, [cx, cy], radius, from, to);
+            if options.inner_radius > 0.0 {
+                let [x, y] = point(options.inner_radius, to);
+                commands.push(DrawCommand::LineTo(attributes, x, y));
+                arc(&mut commands, attributes, [cx, cy], options.inner_radius, to, from);
+            } else {
+                commands.push(DrawCommand::LineTo(attributes, cx, cy));
+            }
+            ShapeArea::shape(Shape::Draw(attributes, x, y, commands), None, bound)
+        }).collect()
+    }
+}
+
+/// Cubic Beziers approximating the arc from angle `from` to `to` in radians,
+/// starting at the current point, in segments of at most a quarter turn.
+fn arc(commands: &mut Vec<DrawCommand<DefaultAttributes>>, attributes: DefaultAttributes, center: [f32; 2], radius: f32, from: f32, to: f32) {
+    let segments = ((to - from).abs() / FRAC_PI_2).ceil().max(1.0) as usize;
+    let step = (to - from) / segments as f32;
+    // Control point distance along the tangent for a circular arc of `step`.
+    let k = 4.0 / 3.0 * (step / 4.0).tan() * radius;
+    for i in 0..segments {
+        let (a0, a1) = (from + step * i as f32, from + step * (i + 1) as f32);
+        let (x0, y0) = (center[0] + radius * a0.cos(), center[1] + radius * a0.sin());
+        let (x1, y1) = (center[0] + radius * a1.cos(), center[1] + radius * a1.sin());
+        commands.push(DrawCommand::CubicBezierTo(
+            attributes, x1, y1,
+            x0 - k * a0.sin(), y0 + k * a0.cos(),
+            x1 + k * a1.sin(), y1 - k * a1.cos(),
+        ));
+    }
+}
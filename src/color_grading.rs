@@ -0,0 +1,102 @@
+use wgpu::{BindGroupLayoutEntry, BindGroupDescriptor, TextureViewDimension, TextureSampleType, SamplerBindingType, TextureDescriptor, SamplerDescriptor, TextureDimension, TextureUsages, TextureFormat, BindGroupEntry, BindingResource, ShaderStages, BindingType, TextureView, AddressMode, FilterMode, Extent3d, Sampler, Device, Queue};
+use wgpu::util::{DeviceExt, TextureDataOrder};
+
+use crate::{CyatRenderer, Uniforms};
+
+/// The 3D lookup table of [`CyatRenderer::set_color_lut`] at bindings 3 and
+/// 4 of bind group 0.
+pub(crate) struct ColorLut {
+    view: TextureView,
+    sampler: Sampler,
+}
+
+impl ColorLut {
+    /// A 1x1x1 placeholder, which the shaders take as no color grading.
+    pub(crate) fn new(device: &Device) -> Self {
+        let texture = device.create_texture(&Self::descriptor(1));
+        Self::from_view(device, texture.create_view(&Default::default()))
+    }
+
+    fn from_view(device: &Device, view: TextureView) -> Self {
+        let sampler = device.create_sampler(&SamplerDescriptor{
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+        ColorLut{view, sampler}
+    }
+
+    fn descriptor(size: u32) -> TextureDescriptor<'static> {
+        TextureDescriptor{
+            label: None,
+            size: Extent3d{width: size, height: size, depth_or_array_layers: size},
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D3,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        }
+    }
+
+    pub(crate) fn layout_entries() -> [BindGroupLayoutEntry; 2] {
+        [
+            BindGroupLayoutEntry{
+                binding: 3,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture{
+                    sample_type: TextureSampleType::Float{filterable: true},
+                    view_dimension: TextureViewDimension::D3,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry{
+                binding: 4,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            },
+        ]
+    }
+
+    pub(crate) fn entries(&self) -> [BindGroupEntry<'_>; 2] {
+        [
+            BindGroupEntry{binding: 3, resource: BindingResource::TextureView(&self.view)},
+            BindGroupEntry{binding: 4, resource: BindingResource::Sampler(&self.sampler)},
+        ]
+    }
+}
+
+impl CyatRenderer {
+    /// Grade the color of every fragment of the built-in shaders through a
+    /// `size` x `size` x `size` lookup table, e.g. 16 or 32, in place of any
+    /// previous one. `lut` holds RGBA8 texels with red varying fastest and
+    /// blue slowest, and is indexed by the linear color the shader outputs
+    /// before the [`SHADER_CONSTANTS`](crate::SHADER_CONSTANTS) apply.
+    ///
+    /// Only available with the `color_grading` feature. Panics if `size` is
+    /// below 2 or `lut` does not hold `size`³ texels.
+    pub fn set_color_lut(&mut self, device: &Device, queue: &Queue, lut: &[u8], size: u32) {
+        assert!(size >= 2, "a color LUT needs at least 2 texels per side");
+        assert_eq!(lut.len(), (size as usize).pow(3) * 4, "color LUT data does not match {size}x{size}x{size} RGBA8 texels");
+        let texture = device.create_texture_with_data(queue, &ColorLut::descriptor(size), TextureDataOrder::LayerMajor, lut);
+        self.set_lut(device, ColorLut::from_view(device, texture.create_view(&Default::default())));
+    }
+
+    /// Go back to ungraded colors, see [`Self::set_color_lut`].
+    pub fn clear_color_lut(&mut self, device: &Device) {
+        self.set_lut(device, ColorLut::new(device));
+    }
+
+    fn set_lut(&mut self, device: &Device, color_lut: ColorLut) {
+        let Uniforms{layout, globals, projections, palette, ..} = &self.uniforms;
+        let mut entries = Uniforms::buffer_entries(globals, projections.as_ref(), palette);
+        entries.extend(color_lut.entries());
+        self.uniforms.bind_group = device.create_bind_group(&BindGroupDescriptor{label: None, layout, entries: &entries});
+        self.uniforms.color_lut = color_lut;
+    }
+}
@@ -0,0 +1,17 @@
+
+@group(0) @binding(3)
+var color_lut: texture_3d<f32>;
+@group(0) @binding(4)
+var color_lut_sampler: sampler;
+
+// The color looked up in the LUT set by CyatRenderer::set_color_lut, unchanged
+// while the LUT is the 1x1x1 placeholder.
+fn grade(rgb: vec3<f32>) -> vec3<f32> {
+    let size = f32(textureDimensions(color_lut).x);
+    if size < 2.0 {
+        return rgb;
+    }
+    // Through the centers of the outermost texels.
+    let uvw = clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0)) * (size - 1.0) / size + 0.5 / size;
+    return textureSampleLevel(color_lut, color_lut_sampler, uvw, 0.0).rgb;
+}
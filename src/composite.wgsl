@@ -0,0 +1,26 @@
+struct CompositeUniforms {
+    // Top left corner of the layer on the target in pixels.
+    origin: vec2<f32>,
+    opacity: f32,
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> composite: CompositeUniforms;
+
+// Premultiplied colors of the group, the size of its bound.
+@group(0) @binding(1)
+var layer: texture_2d<f32>;
+
+// One triangle covering the whole target, scissored to the layer's rect.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(floor(position.xy - composite.origin));
+    return textureLoad(layer, pixel, 0) * composite.opacity;
+}
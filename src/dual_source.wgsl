@@ -0,0 +1,135 @@
+// Globals followed by the range of CyatRenderer::set_depth_range.
+struct GlobalData {
+    // Time and user values, which the built-in shaders ignore.
+    globals: vec4<f32>,
+    depth_range: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> global_data: GlobalData;
+
+// The depth of `clip` mapped from 0..1 into the depth range, unchanged at
+// the default 0..1.
+fn remap_depth(clip: vec4<f32>) -> f32 {
+    let range = global_data.depth_range;
+    return range.x * clip.w + (range.y - range.x) * clip.z;
+}
+
+@group(0) @binding(2)
+var<uniform> palette: array<vec4<f32>, 64>;
+
+// A nonzero `slot` names palette slot `slot - 1` in place of a color.
+fn vertex_color(color: vec3<f32>, slot: u32) -> vec4<f32> {
+    if slot > 0u {
+        return palette[(slot - 1u) % 64u];
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+struct ShapeUniforms {
+    tint: vec4<f32>,
+    user: vec4<f32>,
+    // Left, top, right and bottom in pixels of the rounded clip.
+    clip_rect: vec4<f32>,
+    // Clockwise from the top left.
+    clip_radii: vec4<f32>,
+    clip_fade: f32,
+    saturation: f32,
+    brightness: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> shape: ShapeUniforms;
+
+// The color after the shape's saturation and brightness, unchanged at their
+// defaults.
+fn apply_effect(color: vec4<f32>) -> vec4<f32> {
+    if shape.saturation == 1.0 && shape.brightness == 1.0 {
+        return color;
+    }
+    let luminance = vec3<f32>(dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722)));
+    return vec4<f32>(mix(luminance, color.rgb, shape.saturation) * shape.brightness, color.a);
+}
+
+// Pipeline-overridable constants, see CyatRendererDescriptor::with_constant.
+// Encode the output as sRGB, for targets without an sRGB format.
+override OUTPUT_SRGB: bool = false;
+// Add up to half an 8-bit step of noise to hide banding in smooth fills.
+override DITHER: bool = false;
+// Negate y of vertex positions, set by CyatRendererDescriptor::y_axis.
+override Y_DOWN: bool = false;
+
+// The fragment color as written to the target after color grading and the
+// pipeline constants.
+fn encode_output(color: vec4<f32>, position: vec2<f32>) -> vec4<f32> {
+    var rgb = grade(color.rgb);
+    if OUTPUT_SRGB {
+        let c = max(rgb, vec3<f32>(0.0));
+        rgb = select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+    }
+    if DITHER {
+        let noise = fract(52.9829189 * fract(dot(position, vec2<f32>(0.06711056, 0.00583715))));
+        rgb += (noise - 0.5) / 255.0;
+    }
+    return vec4<f32>(rgb, color.a);
+}
+
+// How much of the pixel at `position` is inside the rounded clip.
+fn clip_coverage(position: vec2<f32>) -> f32 {
+    let center = (shape.clip_rect.xy + shape.clip_rect.zw) * 0.5;
+    let half_size = (shape.clip_rect.zw - shape.clip_rect.xy) * 0.5;
+    let p = position - center;
+    let sides = select(shape.clip_radii.xw, shape.clip_radii.yz, p.x > 0.0);
+    let radius = select(sides.x, sides.y, p.y > 0.0);
+    let q = abs(p) - half_size + radius;
+    let distance = min(max(q.x, q.y), 0.0) + length(max(q, vec2<f32>(0.0))) - radius;
+    if shape.clip_fade > 0.0 {
+        return clamp(0.5 - distance / shape.clip_fade, 0.0, 1.0);
+    }
+    return select(0.0, 1.0, distance <= 0.0);
+}
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) z: f32,
+    @location(3) palette: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(0) @second_blend_source coverage: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.color = vertex_color(model.color, model.palette);
+    out.clip_position = vec4<f32>(model.position, model.z, 1.0);
+    if Y_DOWN {
+        out.clip_position.y = -out.clip_position.y;
+    }
+    out.clip_position.z = remap_depth(out.clip_position);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let coverage = clip_coverage(in.clip_position.xy);
+    if coverage <= 0.0 {
+        discard;
+    }
+    var out: FragmentOutput;
+    out.color = encode_output(apply_effect(in.color * shape.tint), in.clip_position.xy);
+    // Tessellated shapes fully cover their pixels; a shader sampling a
+    // subpixel glyph mask writes its per-channel coverage here instead.
+    out.coverage = vec4<f32>(coverage);
+    return out;
+}
@@ -0,0 +1,5 @@
+
+// Without the color_grading feature colors are not graded.
+fn grade(rgb: vec3<f32>) -> vec3<f32> {
+    return rgb;
+}
@@ -0,0 +1,281 @@
+use wgpu::{PipelineLayoutDescriptor, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindGroupDescriptor, PrimitiveTopology, BufferBindingType, BindGroupLayout, RenderPipeline, BufferDescriptor, BindGroupEntry, BindingResource, BufferBinding, BufferUsages, ShaderStages, BindingType, BindGroup, Buffer, Device, Queue};
+
+use std::num::NonZeroU64;
+
+use crate::{PipelineState, DefaultVertex, srgb_to_linear, linear_to_srgb};
+
+/// Most stops a gradient can have; further stops are ignored.
+pub const MAX_GRADIENT_STOPS: usize = 8;
+
+/// Color of a gradient at `offset` in `0.0..=1.0` along it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GradientStop {
+    pub offset: f32,
+    /// sRGB encoded RGBA, like [`DefaultAttributes::color`](crate::DefaultAttributes::color).
+    pub color: [f32; 4],
+}
+
+/// Colors sweeping around `center`, like CSS `conic-gradient`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConicalGradient {
+    /// In the same NDC space as vertex positions.
+    pub center: [f32; 2],
+    /// Radians counterclockwise from the positive x axis, measured in pixels
+    /// with y pointing up, where the gradient starts.
+    pub start_angle: f32,
+    /// Sorted by offset, at most [`MAX_GRADIENT_STOPS`].
+    pub stops: Vec<GradientStop>,
+    /// Color space the stops are blended in.
+    pub interpolation: GradientInterpolation,
+}
+
+/// Color space a gradient blends between its stops in.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GradientInterpolation {
+    /// Physically even blends, which look bright around the middle of a
+    /// gradient from a dark color.
+    #[default]
+    LinearLight,
+    /// The sRGB encoded values, like CSS gradients, darker in the middle.
+    Srgb,
+    /// Oklab, perceptually even in lightness and keeping the hue of
+    /// saturated colors through the blend.
+    OklabPerceptual,
+}
+
+impl GradientInterpolation {
+    /// `color`, linear RGBA, in the space stops are blended in.
+    fn encode(self, [r, g, b, a]: [f32; 4]) -> [f32; 4] {
+        match self {
+            GradientInterpolation::LinearLight => [r, g, b, a],
+            GradientInterpolation::Srgb => [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a],
+            GradientInterpolation::OklabPerceptual => {
+                let l = (0.41222146 * r + 0.53633255 * g + 0.051445995 * b).cbrt();
+                let m = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+                let s = (0.08830246 * r + 0.28171885 * g + 0.6299787 * b).cbrt();
+                [
+                    0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+                    1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+                    0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+                    a,
+                ]
+            }
+        }
+    }
+}
+
+/// Colors blended bilinearly between the corners of the shape's bounding
+/// box, like a four point mesh gradient.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshGradient {
+    /// sRGB encoded RGBA of the top-left, top-right, bottom-left and
+    /// bottom-right corners.
+    pub corners: [[f32; 4]; 4],
+}
+
+/// Organic pattern of multi-octave value noise blending between two colors,
+/// computed in the fragment shader without textures.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NoiseFill {
+    /// Size in pixels of the largest features.
+    pub scale: f32,
+    /// Layers of noise, each at twice the frequency and half the amplitude
+    /// of the last, at most [`MAX_NOISE_OCTAVES`].
+    pub octaves: u32,
+    /// Change every frame to animate the noise.
+    pub seed: u32,
+    /// sRGB encoded, like [`DefaultAttributes::color`](crate::DefaultAttributes::color),
+    /// where the noise is 0.
+    pub color_a: [f32; 3],
+    /// Where the noise is 1.
+    pub color_b: [f32; 3],
+}
+
+/// Most octaves a [`NoiseFill`] can have; further octaves are ignored.
+pub const MAX_NOISE_OCTAVES: u32 = 8;
+
+/// Fill replacing a shape's vertex colors, see
+/// [`ShapeArea::with_gradient`](crate::ShapeArea::with_gradient).
+#[derive(Clone, Debug, PartialEq)]
+pub enum GradientType {
+    Conical(ConicalGradient),
+    Mesh(MeshGradient),
+    Noise(NoiseFill),
+}
+
+impl GradientType {
+    const CONICAL: u32 = 0;
+    const MESH: u32 = 1;
+    const NOISE: u32 = 2;
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GradientUniforms {
+    center: [f32; 2],
+    scale: [f32; 2],
+    start_angle: f32,
+    stop_count: u32,
+    kind: u32,
+    seed: u32,
+    /// Left, bottom, right and top of the shape's vertices, for mesh gradients.
+    bounds: [f32; 4],
+    offsets: [f32; MAX_GRADIENT_STOPS],
+    colors: [[f32; 4]; MAX_GRADIENT_STOPS],
+    /// [`GradientInterpolation`] of the colors, converted back to linear
+    /// light after blending.
+    interpolation: u32,
+    _padding: [u32; 3],
+}
+
+/// Uniforms of the gradients of the prepared frame, each at a multiple of
+/// `stride`, created the first time a shape has a gradient.
+pub(crate) struct Gradients {
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    buffer: Buffer,
+    bind_group: BindGroup,
+    stride: u64,
+    staging: Vec<u8>,
+    previous: Vec<u8>,
+}
+
+impl Gradients {
+    const SIZE: u64 = std::mem::size_of::<GradientUniforms>() as u64;
+
+    pub(crate) fn new(device: &Device, state: &PipelineState, uniforms_layout: &BindGroupLayout, shape_layout: &BindGroupLayout) -> Self {
+        debug_assert!(!state.dual_source_blending && state.bind_group_count <= 2, "prepare checks the renderer supports gradients");
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[BindGroupLayoutEntry{
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer{ty: BufferBindingType::Uniform, has_dynamic_offset: true, min_binding_size: NonZeroU64::new(Self::SIZE)},
+                count: None,
+            }],
+        });
+        let pipeline = Self::create_pipeline(device, state, uniforms_layout, shape_layout, &layout);
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let stride = Self::SIZE.div_ceil(alignment) * alignment;
+        let (buffer, bind_group) = Self::create(device, &layout, stride);
+        Gradients{pipeline, layout, buffer, bind_group, stride, staging: Vec::new(), previous: Vec::new()}
+    }
+
+    fn create_pipeline(device: &Device, state: &PipelineState, uniforms_layout: &BindGroupLayout, shape_layout: &BindGroupLayout, layout: &BindGroupLayout) -> RenderPipeline {
+        let shader = builtin_shader!(device, "gradient.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor{
+            bind_group_layouts: &[uniforms_layout, shape_layout, layout],
+            ..PipelineLayoutDescriptor::default()
+        });
+        state.create_with(device, PrimitiveTopology::TriangleList, &shader, &pipeline_layout, [Some("vs_main"), Some("fs_main")])
+    }
+
+    /// Create the pipeline again for a changed `state`, keeping the uniforms.
+    pub(crate) fn recreate_pipeline(&mut self, device: &Device, state: &PipelineState, uniforms_layout: &BindGroupLayout, shape_layout: &BindGroupLayout) {
+        self.pipeline = Self::create_pipeline(device, state, uniforms_layout, shape_layout, &self.layout);
+    }
+
+    fn create(device: &Device, layout: &BindGroupLayout, size: u64) -> (Buffer, BindGroup) {
+        let buffer = device.create_buffer(&BufferDescriptor{
+            label: None,
+            size,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor{
+            label: None,
+            layout,
+            entries: &[BindGroupEntry{binding: 0, resource: BindingResource::Buffer(BufferBinding{
+                buffer: &buffer,
+                offset: 0,
+                size: NonZeroU64::new(Self::SIZE),
+            })}],
+        });
+        (buffer, bind_group)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        std::mem::swap(&mut self.staging, &mut self.previous);
+        self.staging.clear();
+    }
+
+    /// Add the uniforms of `gradient` for a `target_size` target and a shape
+    /// tessellated into `vertices`, returning its index.
+    pub(crate) fn push(&mut self, gradient: &GradientType, target_size: (u32, u32), vertices: &[DefaultVertex]) -> usize {
+        let mut uniforms = GradientUniforms{
+            center: [0.0; 2],
+            scale: [target_size.0 as f32 / 2.0, target_size.1 as f32 / 2.0],
+            start_angle: 0.0,
+            stop_count: 0,
+            kind: GradientType::CONICAL,
+            seed: 0,
+            bounds: [0.0; 4],
+            offsets: [0.0; MAX_GRADIENT_STOPS],
+            colors: [[0.0; 4]; MAX_GRADIENT_STOPS],
+            interpolation: 0,
+            _padding: [0; 3],
+        };
+        let linear = |[r, g, b, a]: [f32; 4]| [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a];
+        match gradient {
+            GradientType::Conical(conical) => {
+                uniforms.center = conical.center;
+                uniforms.start_angle = conical.start_angle;
+                uniforms.stop_count = conical.stops.len().min(MAX_GRADIENT_STOPS) as u32;
+                uniforms.interpolation = conical.interpolation as u32;
+                for (i, stop) in conical.stops.iter().take(MAX_GRADIENT_STOPS).enumerate() {
+                    uniforms.offsets[i] = stop.offset;
+                    uniforms.colors[i] = conical.interpolation.encode(linear(stop.color));
+                }
+            }
+            GradientType::Mesh(mesh) => {
+                uniforms.kind = GradientType::MESH;
+                uniforms.bounds = vertices.iter().fold([f32::MAX, f32::MAX, f32::MIN, f32::MIN], |[l, b, r, t], v| {
+                    [l.min(v.position[0]), b.min(v.position[1]), r.max(v.position[0]), t.max(v.position[1])]
+                });
+                for (color, corner) in uniforms.colors.iter_mut().zip(mesh.corners) {
+                    *color = linear(corner);
+                }
+            }
+            // The scale goes in the first offset and the octaves in the stop count.
+            GradientType::Noise(noise) => {
+                uniforms.kind = GradientType::NOISE;
+                uniforms.offsets[0] = noise.scale;
+                uniforms.stop_count = noise.octaves.min(MAX_NOISE_OCTAVES);
+                uniforms.seed = noise.seed;
+                let [[r, g, b], [r2, g2, b2]] = [noise.color_a, noise.color_b];
+                uniforms.colors[0] = linear([r, g, b, 1.0]);
+                uniforms.colors[1] = linear([r2, g2, b2, 1.0]);
+            }
+        }
+
+        let index = self.staging.len() / self.stride as usize;
+        self.staging.resize((index + 1) * self.stride as usize, 0);
+        self.staging[index * self.stride as usize..][..Self::SIZE as usize].copy_from_slice(bytemuck::bytes_of(&uniforms));
+        index
+    }
+
+    /// Upload the gradients pushed since the last clear, returning whether
+    /// they differ from the previous frame's.
+    pub(crate) fn write(&mut self, device: &Device, queue: &Queue) -> bool {
+        if self.staging == self.previous {return false;}
+        if self.staging.len() as u64 > self.buffer.size() {
+            (self.buffer, self.bind_group) = Self::create(device, &self.layout, (self.staging.len() as u64).next_power_of_two());
+        }
+        if !self.staging.is_empty() {queue.write_buffer(&self.buffer, 0, &self.staging);}
+        true
+    }
+
+    pub(crate) fn pipeline(&self) -> &RenderPipeline {
+        &self.pipeline
+    }
+
+    pub(crate) fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
+
+    pub(crate) fn offset(&self, gradient: usize) -> u32 {
+        (gradient as u64 * self.stride) as u32
+    }
+}
@@ -0,0 +1,245 @@
+// Globals followed by the range of CyatRenderer::set_depth_range.
+struct GlobalData {
+    // Time and user values, which the built-in shaders ignore.
+    globals: vec4<f32>,
+    depth_range: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> global_data: GlobalData;
+
+// The depth of `clip` mapped from 0..1 into the depth range, unchanged at
+// the default 0..1.
+fn remap_depth(clip: vec4<f32>) -> f32 {
+    let range = global_data.depth_range;
+    return range.x * clip.w + (range.y - range.x) * clip.z;
+}
+
+struct ShapeUniforms {
+    tint: vec4<f32>,
+    user: vec4<f32>,
+    // Left, top, right and bottom in pixels of the rounded clip.
+    clip_rect: vec4<f32>,
+    // Clockwise from the top left.
+    clip_radii: vec4<f32>,
+    clip_fade: f32,
+    saturation: f32,
+    brightness: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> shape: ShapeUniforms;
+
+// The color after the shape's saturation and brightness, unchanged at their
+// defaults.
+fn apply_effect(color: vec4<f32>) -> vec4<f32> {
+    if shape.saturation == 1.0 && shape.brightness == 1.0 {
+        return color;
+    }
+    let luminance = vec3<f32>(dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722)));
+    return vec4<f32>(mix(luminance, color.rgb, shape.saturation) * shape.brightness, color.a);
+}
+
+// Pipeline-overridable constants, see CyatRendererDescriptor::with_constant.
+// Encode the output as sRGB, for targets without an sRGB format.
+override OUTPUT_SRGB: bool = false;
+// Add up to half an 8-bit step of noise to hide banding in smooth fills.
+override DITHER: bool = false;
+// Negate y of vertex positions, set by CyatRendererDescriptor::y_axis.
+override Y_DOWN: bool = false;
+
+// The fragment color as written to the target after color grading and the
+// pipeline constants.
+fn encode_output(color: vec4<f32>, position: vec2<f32>) -> vec4<f32> {
+    var rgb = grade(color.rgb);
+    if OUTPUT_SRGB {
+        let c = max(rgb, vec3<f32>(0.0));
+        rgb = select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+    }
+    if DITHER {
+        let noise = fract(52.9829189 * fract(dot(position, vec2<f32>(0.06711056, 0.00583715))));
+        rgb += (noise - 0.5) / 255.0;
+    }
+    return vec4<f32>(rgb, color.a);
+}
+
+// How much of the pixel at `position` is inside the rounded clip.
+fn clip_coverage(position: vec2<f32>) -> f32 {
+    let center = (shape.clip_rect.xy + shape.clip_rect.zw) * 0.5;
+    let half_size = (shape.clip_rect.zw - shape.clip_rect.xy) * 0.5;
+    let p = position - center;
+    let sides = select(shape.clip_radii.xw, shape.clip_radii.yz, p.x > 0.0);
+    let radius = select(sides.x, sides.y, p.y > 0.0);
+    let q = abs(p) - half_size + radius;
+    let distance = min(max(q.x, q.y), 0.0) + length(max(q, vec2<f32>(0.0))) - radius;
+    if shape.clip_fade > 0.0 {
+        return clamp(0.5 - distance / shape.clip_fade, 0.0, 1.0);
+    }
+    return select(0.0, 1.0, distance <= 0.0);
+}
+
+const MAX_STOPS: u32 = 8u;
+const TAU: f32 = 6.283185307179586;
+const MESH: u32 = 1u;
+const NOISE: u32 = 2u;
+const SRGB: u32 = 1u;
+const OKLAB: u32 = 2u;
+
+struct GradientUniforms {
+    center: vec2<f32>,
+    // Half the target size, to measure angles in pixels rather than NDC.
+    scale: vec2<f32>,
+    start_angle: f32,
+    stop_count: u32,
+    kind: u32,
+    seed: u32,
+    // Left, bottom, right and top of the shape, for mesh gradients.
+    bounds: vec4<f32>,
+    offsets: array<vec4<f32>, 2>,
+    colors: array<vec4<f32>, MAX_STOPS>,
+    // Space the colors are blended in, converted back to linear light.
+    interpolation: u32,
+};
+
+@group(2) @binding(0)
+var<uniform> gradient: GradientUniforms;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) z: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) position: vec2<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = screen(model.position);
+    out.clip_position = vec4<f32>(model.position, model.z, 1.0);
+    if Y_DOWN {
+        out.clip_position.y = -out.clip_position.y;
+    }
+    out.clip_position.z = remap_depth(out.clip_position);
+    return out;
+}
+
+// A vertex position space point with y pointing up on the target.
+fn screen(position: vec2<f32>) -> vec2<f32> {
+    return select(position, vec2<f32>(position.x, -position.y), Y_DOWN);
+}
+
+fn stop_offset(i: u32) -> f32 {
+    return gradient.offsets[i / 4u][i % 4u];
+}
+
+// Color at `t` in 0..1 between the sorted stops.
+fn sample(t: f32) -> vec4<f32> {
+    var color = gradient.colors[0];
+    for (var i = 1u; i < gradient.stop_count; i++) {
+        let previous = stop_offset(i - 1u);
+        let offset = stop_offset(i);
+        if t >= offset {
+            color = gradient.colors[i];
+        } else if t > previous {
+            color = mix(gradient.colors[i - 1u], gradient.colors[i], (t - previous) / (offset - previous));
+        }
+    }
+    return color;
+}
+
+// Linear light of a color blended in the gradient's interpolation space.
+fn decode(color: vec4<f32>) -> vec4<f32> {
+    if gradient.interpolation == SRGB {
+        let c = color.rgb;
+        return vec4<f32>(select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045)), color.a);
+    }
+    if gradient.interpolation == OKLAB {
+        let l = color.x + 0.3963377774 * color.y + 0.2158037573 * color.z;
+        let m = color.x - 0.1055613458 * color.y - 0.0638541728 * color.z;
+        let s = color.x - 0.0894841775 * color.y - 1.2914855480 * color.z;
+        let lms = vec3<f32>(l * l * l, m * m * m, s * s * s);
+        return vec4<f32>(
+            dot(lms, vec3<f32>(4.0767416621, -3.3077115913, 0.2309699292)),
+            dot(lms, vec3<f32>(-1.2684380046, 2.6097574011, -0.3413193965)),
+            dot(lms, vec3<f32>(-0.0041960863, -0.7034186147, 1.7076147010)),
+            color.a,
+        );
+    }
+    return color;
+}
+
+fn conical(position: vec2<f32>) -> vec4<f32> {
+    let d = (position - screen(gradient.center)) * gradient.scale;
+    let angle = atan2(d.y, d.x) - gradient.start_angle;
+    return decode(sample(fract(angle / TAU)));
+}
+
+// Corners blended by the position within the bounds, from the top-left.
+fn mesh(position: vec2<f32>) -> vec4<f32> {
+    let low = select(gradient.bounds.xy, vec2<f32>(gradient.bounds.x, -gradient.bounds.w), Y_DOWN);
+    let high = select(gradient.bounds.zw, vec2<f32>(gradient.bounds.z, -gradient.bounds.y), Y_DOWN);
+    let size = max(high - low, vec2<f32>(1e-6));
+    let uv = clamp(vec2<f32>(position.x - low.x, high.y - position.y) / size, vec2<f32>(0.0), vec2<f32>(1.0));
+    let top = mix(gradient.colors[0], gradient.colors[1], uv.x);
+    let bottom = mix(gradient.colors[2], gradient.colors[3], uv.x);
+    return mix(top, bottom, uv.y);
+}
+
+// Pseudo-random value in 0..1 for the lattice point `p` and the seed.
+fn hash(p: vec2<i32>) -> f32 {
+    var h = (bitcast<u32>(p.x) * 0x8da6b343u) ^ (bitcast<u32>(p.y) * 0xd8163841u) ^ (gradient.seed * 0xcb1ab31fu);
+    h = (h ^ (h >> 16u)) * 0x7feb352du;
+    h = (h ^ (h >> 15u)) * 0x846ca68bu;
+    h = h ^ (h >> 16u);
+    return f32(h) / 4294967295.0;
+}
+
+// Smoothly interpolated hashes of the surrounding lattice points.
+fn value_noise(p: vec2<f32>) -> f32 {
+    let cell = vec2<i32>(floor(p));
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    let top = mix(hash(cell), hash(cell + vec2<i32>(1, 0)), u.x);
+    let bottom = mix(hash(cell + vec2<i32>(0, 1)), hash(cell + vec2<i32>(1, 1)), u.x);
+    return mix(top, bottom, u.y);
+}
+
+// Octaves of value noise, the scale in the first offset and the octaves in
+// the stop count.
+fn noise(position: vec2<f32>) -> vec4<f32> {
+    var p = position * gradient.scale / max(stop_offset(0u), 1e-6);
+    var amplitude = 0.5;
+    var total = 0.0;
+    var n = 0.0;
+    for (var i = 0u; i < max(gradient.stop_count, 1u); i++) {
+        n += amplitude * value_noise(p);
+        total += amplitude;
+        // Offset the octaves so their lattices do not line up at the origin.
+        p = p * 2.0 + vec2<f32>(17.0, 31.0);
+        amplitude *= 0.5;
+    }
+    return mix(gradient.colors[0], gradient.colors[1], n / total);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color: vec4<f32>;
+    if gradient.kind == MESH {
+        color = mesh(in.position);
+    } else if gradient.kind == NOISE {
+        color = noise(in.position);
+    } else {
+        color = conical(in.position);
+    }
+    let coverage = clip_coverage(in.clip_position.xy);
+    if coverage <= 0.0 {
+        discard;
+    }
+    return encode_output(apply_effect(color * vec4<f32>(1.0, 1.0, 1.0, coverage) * shape.tint), in.clip_position.xy);
+}
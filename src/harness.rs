@@ -0,0 +1,253 @@
+//! Render shapes on a software adapter and compare them against golden PNGs,
+//! for pixel exact tests on machines without a GPU.
+
+use wgpu::{RenderPassDepthStencilAttachment, RenderPassColorAttachment, CommandEncoderDescriptor, RequestAdapterOptions, RenderPassDescriptor, TexelCopyTextureInfo, TexelCopyBufferLayout, TexelCopyBufferInfo, InstanceDescriptor, DeviceDescriptor, TextureDescriptor, BufferDescriptor, MultisampleState, TextureDimension, TextureUsages, TextureFormat, TextureAspect, BufferUsages, Operations, Origin3d, Extent3d, Features, Backends, Instance, Maintain, MapMode, RenderPass, StoreOp, LoadOp, Color, Adapter, Device, Queue};
+
+use std::path::{Path, PathBuf};
+
+use crate::{CyatRenderer, ShapeArea, CyatError, block_on};
+
+/// Format of the offscreen target, so pixels come back as 8 bit RGBA.
+pub const FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+/// Set to write the rendered pixels to the golden path instead of comparing.
+pub const UPDATE_ENV: &str = "WGPU_CYAT_UPDATE_GOLDEN";
+
+/// Device and queue of a fallback adapter on any backend, like lavapipe or
+/// WARP. `None` when the machine has none.
+pub fn software_device() -> Option<(Device, Queue)> {
+    software_device_with(Features::empty())
+}
+
+/// Like [`software_device`], enabling those of `features` the adapter has.
+pub fn software_device_with(features: Features) -> Option<(Device, Queue)> {
+    let adapter = software_adapter()?;
+    let required_features = adapter.features() & features;
+    block_on(adapter.request_device(&DeviceDescriptor{required_features, ..DeviceDescriptor::default()}, None)).ok()
+}
+
+/// The adapter [`software_device`] requests its device from.
+pub fn software_adapter() -> Option<Adapter> {
+    let instance = Instance::new(&InstanceDescriptor{backends: Backends::all(), ..InstanceDescriptor::default()});
+    block_on(instance.request_adapter(&RequestAdapterOptions{
+        force_fallback_adapter: true,
+        ..RequestAdapterOptions::default()
+    }))
+}
+
+/// Prepare and render `shapes` with a new renderer to a `width` x `height`
+/// [`FORMAT`] texture cleared to opaque black, returning its pixels as
+/// tightly packed rows.
+pub fn render(device: &Device, queue: &Queue, shapes: Vec<ShapeArea>, width: u32, height: u32) -> Result<Vec<u8>, CyatError> {
+    render_with(device, queue, &mut CyatRenderer::new(device, &FORMAT, MultisampleState::default(), None), shapes, width, height)
+}
+
+/// Like [`render`] with a renderer configured by the caller for a [`FORMAT`]
+/// target without multisampling. With a depth format the pass gets a depth
+/// buffer cleared to 1.0, and any stencil cleared to 0.
+pub fn render_with(device: &Device, queue: &Queue, renderer: &mut CyatRenderer, shapes: Vec<ShapeArea>, width: u32, height: u32) -> Result<Vec<u8>, CyatError> {
+    draw_with(device, queue, renderer, shapes, width, height, Draw::Render)
+}
+
+/// Like [`render_with`] preparing with [`CyatRenderer::prepare_indirect`]
+/// and drawing with [`CyatRenderer::render_indirect`].
+pub fn render_indirect_with(device: &Device, queue: &Queue, renderer: &mut CyatRenderer, shapes: Vec<ShapeArea>, width: u32, height: u32) -> Result<Vec<u8>, CyatError> {
+    draw_with(device, queue, renderer, shapes, width, height, Draw::Indirect)
+}
+
+/// Like [`render_with`] drawing with [`CyatRenderer::render_overdraw`], for
+/// which overdraw visualization is enabled.
+pub fn render_overdraw_with(device: &Device, queue: &Queue, renderer: &mut CyatRenderer, shapes: Vec<ShapeArea>, width: u32, height: u32) -> Result<Vec<u8>, CyatError> {
+    renderer.set_overdraw_visualization(true);
+    draw_with(device, queue, renderer, shapes, width, height, Draw::Pass(&CyatRenderer::render_overdraw))
+}
+
+/// Like [`render_with`] first writing depth with
+/// [`CyatRenderer::render_depth_prepass`] in a depth only pass, which the
+/// main pass loads. The renderer needs a depth format.
+pub fn render_depth_prepass_with(device: &Device, queue: &Queue, renderer: &mut CyatRenderer, shapes: Vec<ShapeArea>, width: u32, height: u32) -> Result<Vec<u8>, CyatError> {
+    draw_with(device, queue, renderer, shapes, width, height, Draw::DepthPrepass)
+}
+
+/// Like [`render_with`] calling `draw` with the render pass in place of
+/// [`CyatRenderer::render`], to draw more around the prepared frame.
+pub fn render_pass_with(
+    device: &Device,
+    queue: &Queue,
+    renderer: &mut CyatRenderer,
+    shapes: Vec<ShapeArea>,
+    width: u32,
+    height: u32,
+    draw: &dyn Fn(&CyatRenderer, &mut RenderPass<'_>),
+) -> Result<Vec<u8>, CyatError> {
+    draw_with(device, queue, renderer, shapes, width, height, Draw::Pass(draw))
+}
+
+/// How [`draw_with`] prepares and draws the shapes.
+enum Draw<'a> {
+    Render,
+    Indirect,
+    DepthPrepass,
+    /// Prepare as usual and draw with this in the pass.
+    Pass(&'a dyn Fn(&CyatRenderer, &mut RenderPass<'_>)),
+}
+
+fn draw_with(
+    device: &Device,
+    queue: &Queue,
+    renderer: &mut CyatRenderer,
+    shapes: Vec<ShapeArea>,
+    width: u32,
+    height: u32,
+    draw: Draw<'_>,
+) -> Result<Vec<u8>, CyatError> {
+    renderer.resize(width, height);
+    match draw {
+        Draw::Indirect => renderer.prepare_indirect(device, queue, shapes)?,
+        Draw::Render | Draw::DepthPrepass | Draw::Pass(_) => renderer.prepare(device, queue, shapes)?,
+    };
+
+    let size = Extent3d{width, height, depth_or_array_layers: 1};
+    let texture = device.create_texture(&TextureDescriptor{
+        label: None,
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&Default::default());
+    let depth = renderer.depth_texture_format().map(|_| renderer.create_depth_texture(device, width, height).create_view(&Default::default()));
+
+    let row = width * 4;
+    let padded_row = row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let buffer = device.create_buffer(&BufferDescriptor{
+        label: None,
+        size: padded_row as u64 * height as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+    let prepass = matches!(draw, Draw::DepthPrepass);
+    if prepass {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor{
+            label: None,
+            color_attachments: &[],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment{
+                view: depth.as_ref().expect("a depth prepass needs a depth format"),
+                depth_ops: Some(Operations{load: LoadOp::Clear(1.0), store: StoreOp::Store}),
+                stencil_ops: None,
+            }),
+            ..RenderPassDescriptor::default()
+        });
+        renderer.render_depth_prepass(&mut render_pass);
+    }
+    match draw {
+        Draw::Render => renderer.render_to_view(&mut encoder, &view, LoadOp::Clear(Color::BLACK), depth.as_ref()),
+        Draw::Indirect | Draw::DepthPrepass | Draw::Pass(_) => {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor{
+                label: None,
+                color_attachments: &[Some(RenderPassColorAttachment{
+                    view: &view,
+                    resolve_target: None,
+                    ops: Operations{load: LoadOp::Clear(Color::BLACK), store: StoreOp::Store},
+                })],
+                depth_stencil_attachment: depth.as_ref().map(|view| RenderPassDepthStencilAttachment{
+                    view,
+                    depth_ops: Some(Operations{load: if prepass {LoadOp::Load} else {LoadOp::Clear(1.0)}, store: StoreOp::Discard}),
+                    stencil_ops: renderer.depth_texture_format().filter(TextureFormat::has_stencil_aspect)
+                        .map(|_| Operations{load: LoadOp::Clear(0), store: StoreOp::Discard}),
+                }),
+                ..RenderPassDescriptor::default()
+            });
+            match draw {
+                Draw::Pass(draw) => draw(renderer, &mut render_pass),
+                Draw::Indirect => renderer.render_indirect(&mut render_pass),
+                _ => renderer.render(&mut render_pass),
+            }
+        }
+    }
+    encoder.copy_texture_to_buffer(
+        TexelCopyTextureInfo{texture: &texture, mip_level: 0, origin: Origin3d::ZERO, aspect: TextureAspect::All},
+        TexelCopyBufferInfo{buffer: &buffer, layout: TexelCopyBufferLayout{offset: 0, bytes_per_row: Some(padded_row), rows_per_image: None}},
+        size,
+    );
+    queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    slice.map_async(MapMode::Read, |result| result.expect("failed to map the readback buffer"));
+    device.poll(Maintain::Wait);
+    let mapped = slice.get_mapped_range();
+    let pixels = mapped.chunks_exact(padded_row as usize).flat_map(|r| &r[..row as usize]).copied().collect();
+    drop(mapped);
+    buffer.unmap();
+    Ok(pixels)
+}
+
+/// Why [`compare_png`] failed.
+#[derive(Debug)]
+pub enum GoldenError {
+    Io(std::io::Error),
+    /// The golden file is not a PNG this harness can read.
+    Decode(String),
+    Size{expected: (u32, u32), actual: (u32, u32)},
+    /// `pixels` differ by more than the tolerance, highlighted in `diff`.
+    Mismatch{pixels: usize, diff: PathBuf},
+}
+
+impl std::fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GoldenError::Io(error) => write!(f, "{error}"),
+            GoldenError::Decode(error) => write!(f, "invalid golden PNG: {error}"),
+            GoldenError::Size{expected, actual} => write!(f, "golden is {}x{} but the render is {}x{}", expected.0, expected.1, actual.0, actual.1),
+            GoldenError::Mismatch{pixels, diff} => write!(f, "{pixels} pixels differ, see {}", diff.display()),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+impl From<std::io::Error> for GoldenError {
+    fn from(error: std::io::Error) -> Self {
+        GoldenError::Io(error)
+    }
+}
+
+/// Compare RGBA `pixels` against the PNG at `golden`, allowing each channel
+/// to differ by up to `tolerance`.
+///
+/// On a mismatch a diff image is written next to the golden with a `.diff.png`
+/// extension, showing differing pixels in red over a dimmed copy of the
+/// golden. With [`UPDATE_ENV`] set the pixels are written to `golden` instead.
+pub fn compare_png(pixels: &[u8], width: u32, height: u32, golden: impl AsRef<Path>, tolerance: u8) -> Result<(), GoldenError> {
+    let golden = golden.as_ref();
+    if std::env::var_os(UPDATE_ENV).is_some() {
+        std::fs::write(golden, crate::png::encode(width, height, pixels))?;
+        return Ok(());
+    }
+
+    let (expected_width, expected_height, expected) = crate::png::decode(&std::fs::read(golden)?).map_err(GoldenError::Decode)?;
+    if (expected_width, expected_height) != (width, height) {
+        return Err(GoldenError::Size{expected: (expected_width, expected_height), actual: (width, height)});
+    }
+
+    let mut mismatched = 0;
+    let diff: Vec<u8> = expected.chunks_exact(4).zip(pixels.chunks_exact(4)).flat_map(|(e, a)| {
+        if e.iter().zip(a).any(|(e, a)| e.abs_diff(*a) > tolerance) {
+            mismatched += 1;
+            [255, 0, 0, 255]
+        } else {
+            let luma = ((e[0] as u32 * 2 + e[1] as u32 * 5 + e[2] as u32) / 32) as u8;
+            [luma, luma, luma, 255]
+        }
+    }).collect();
+    if mismatched == 0 {return Ok(());}
+
+    let diff_path = golden.with_extension("diff.png");
+    std::fs::write(&diff_path, crate::png::encode(width, height, &diff))?;
+    Err(GoldenError::Mismatch{pixels: mismatched, diff: diff_path})
+}
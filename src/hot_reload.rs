@@ -0,0 +1,71 @@
+use wgpu::{ShaderModuleDescriptor, ShaderSource, ErrorFilter, Device};
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::sync::Arc;
+
+use crate::{CyatRenderer, CyatError, ShaderModule, block_on};
+
+/// Shader file replacing the renderer's shader and when it was last read.
+pub(crate) struct WatchedShader {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl CyatRenderer {
+    /// Load the renderer's shader from the WGSL file at `path` and reload it
+    /// on every [`Self::prepare`] after the file was modified, so a custom
+    /// shader can be edited while the application runs.
+    ///
+    /// Only available with the `hot-reload` feature in debug builds. See
+    /// [`Self::reload_shader`] for how errors are handled.
+    pub fn watch_shader(&mut self, device: &Device, path: impl Into<PathBuf>) -> Result<(), CyatError> {
+        self.watched_shader = Some(WatchedShader{path: path.into(), modified: None});
+        self.reload_shader(device).map(|_| ())
+    }
+
+    /// Read the watched shader file again and recreate every pipeline of the
+    /// renderer with it. Returns false when there is no watched shader, and
+    /// fails with [`CyatError::ShaderReload`] when it cannot be read or fails
+    /// to compile, keeping the previous pipelines.
+    ///
+    /// Reloads on prepare keep the previous pipelines the same way, and log
+    /// their errors as warnings with the `tracing` feature.
+    ///
+    /// Masked shapes keep their built-in shader.
+    pub fn reload_shader(&mut self, device: &Device) -> Result<bool, CyatError> {
+        let Some(watched) = &mut self.watched_shader else {return Ok(false);};
+        watched.modified = std::fs::metadata(&watched.path).and_then(|m| m.modified()).ok();
+        let path = watched.path.clone();
+        let source = std::fs::read_to_string(&path)
+            .map_err(|error| CyatError::ShaderReload(format!("failed to read {}: {error}", path.display())))?;
+
+        // Compile into a copy of the state so a broken shader leaves the
+        // current pipelines untouched.
+        device.push_error_scope(ErrorFilter::Validation);
+        let mut state = self.pipeline_state.clone();
+        state.shader = ShaderModule(Arc::new(device.create_shader_module(ShaderModuleDescriptor{
+            label: path.to_str(),
+            source: ShaderSource::Wgsl(source.into()),
+        })));
+        let pipelines = self.create_pipelines(device, &state);
+        if let Some(error) = block_on(device.pop_error_scope()) {
+            return Err(CyatError::ShaderReload(format!("failed to reload {}: {error}", path.display())));
+        }
+
+        self.set_pipelines(state, pipelines);
+        Ok(true)
+    }
+
+    /// Reload the watched shader when its file changed since it was last read.
+    pub(crate) fn reload_shader_if_modified(&mut self, device: &Device) {
+        let Some(watched) = &self.watched_shader else {return;};
+        let modified = std::fs::metadata(&watched.path).and_then(|m| m.modified()).ok();
+        if modified == watched.modified {return;}
+        let result = self.reload_shader(device);
+        #[cfg(feature = "tracing")]
+        if let Err(error) = result {tracing::warn!(target: "wgpu_cyat", "{error}");}
+        #[cfg(not(feature = "tracing"))]
+        let _ = result;
+    }
+}
@@ -0,0 +1,336 @@
+use wgpu::{PipelineCompilationOptions, RenderPipelineDescriptor, PipelineLayoutDescriptor, BindGroupLayoutDescriptor, BindGroupLayoutEntry, RenderPassDepthStencilAttachment, RenderPassColorAttachment, CommandEncoderDescriptor, RenderPassDescriptor, TextureSampleType, TextureViewDimension, DepthStencilState, ColorTargetState, TextureDescriptor, BindGroupDescriptor, BufferBindingType, BindGroupLayout, CompareFunction, TextureDimension, RenderPipeline, PrimitiveState, TextureUsages, TextureFormat, BindGroupEntry, BindingResource, FragmentState, StencilState, BufferUsages, ShaderStages, BindingType, IndexFormat, TextureView, VertexState, BlendState, RenderPass, BindGroup, Extent3d, StoreOp, Operations, Buffer, Color, LoadOp, Device, Queue};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use wgpu_dyn_buffer::{DynamicBufferDescriptor, DynamicBuffer};
+
+use cyat::VertexBuffers;
+
+use crate::{DefaultVertex, PipelineState, PreparedShape, ScissorRect, YAxis};
+
+/// A shape's [`ShapeArea::group_opacity`](crate::ShapeArea::group_opacity)
+/// group, named by the shape that set it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Group {
+    pub(crate) root: usize,
+    /// The root's visible bound on the target, the rect of the layer.
+    pub(crate) rect: ScissorRect,
+    pub(crate) opacity: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CompositeUniforms {
+    origin: [f32; 2],
+    opacity: f32,
+    _padding: f32,
+}
+
+/// A group of the prepared frame and the shape it is composited in place of.
+struct Layer {
+    group: Group,
+    first: usize,
+}
+
+/// A shape drawn into a layer, with its vertices moved into the layer.
+struct LayerDraw {
+    layer: usize,
+    shape: usize,
+    start: usize,
+    end: usize,
+    vertex_start: usize,
+    /// The shape's bound within the layer's rect, relative to it.
+    scissor: ScissorRect,
+}
+
+/// What a target can be reused for.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct TargetKey {
+    size: (u32, u32),
+    format: TextureFormat,
+    sample_count: u32,
+    depth_format: Option<TextureFormat>,
+}
+
+/// A texture of the renderer's format covering one layer's rect, with a
+/// multisampled texture resolving into it and depth as the renderer uses.
+struct LayerTarget {
+    key: TargetKey,
+    view: TextureView,
+    resolve_target: Option<TextureView>,
+    depth: Option<TextureView>,
+    uniforms: Buffer,
+    bind_group: BindGroup,
+}
+
+/// Groups of the prepared frame and the textures they are rendered to,
+/// created the first time a shape uses
+/// [`ShapeArea::with_group_opacity`](crate::ShapeArea::with_group_opacity).
+pub(crate) struct Layers {
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+    buffers: VertexBuffers<DefaultVertex, u16>,
+    layers: Vec<Layer>,
+    draws: Vec<LayerDraw>,
+    /// One target per layer, reused across frames by size and format.
+    pool: Vec<LayerTarget>,
+}
+
+impl Layers {
+    /// Whether layers can be drawn with the renderer's pipelines of `state`,
+    /// which the layer passes bind no extra groups or views for.
+    pub(crate) fn supported(state: &PipelineState) -> bool {
+        state.quantize.is_none() && state.multiview.is_none() && !state.dual_source_blending && state.bind_group_count <= 2
+    }
+
+    /// Expects [`Self::supported`] to hold for `state`.
+    pub(crate) fn new(device: &Device, state: &PipelineState) -> Self {
+        debug_assert!(Self::supported(state), "prepare checks the renderer supports layers");
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry{
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer{ty: BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None},
+                    count: None,
+                },
+                BindGroupLayoutEntry{
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture{
+                        sample_type: TextureSampleType::Float{filterable: false},
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        Layers{
+            pipeline: Self::create_pipeline(device, state, &layout),
+            layout,
+            vertex_buffer: DynamicBuffer::new(device, &DynamicBufferDescriptor {
+                label: None,
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            }),
+            index_buffer: DynamicBuffer::new(device, &DynamicBufferDescriptor {
+                label: None,
+                usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+            }),
+            buffers: VertexBuffers::new(),
+            layers: Vec::new(),
+            draws: Vec::new(),
+            pool: Vec::new(),
+        }
+    }
+
+    /// The pipeline blending a layer's premultiplied colors onto the target
+    /// with its opacity, for any blend state of the renderer's. Depth is
+    /// neither tested nor written.
+    fn create_pipeline(device: &Device, state: &PipelineState, layout: &BindGroupLayout) -> RenderPipeline {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("composite.wgsl"));
+        device.create_render_pipeline(&RenderPipelineDescriptor{
+            label: Some("wgpu_cyat::composite"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor{
+                bind_group_layouts: &[layout],
+                ..PipelineLayoutDescriptor::default()
+            })),
+            vertex: VertexState{module: &shader, entry_point: Some("vs_main"), compilation_options: PipelineCompilationOptions::default(), buffers: &[]},
+            fragment: Some(FragmentState{
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[Some(ColorTargetState{blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING), ..state.target.clone()})],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: state.depth_stencil.clone().map(|depth| DepthStencilState{
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState::default(),
+                ..depth
+            }),
+            multisample: state.multisample,
+            multiview: None,
+            cache: state.cache.as_ref(),
+        })
+    }
+
+    /// Create the composite pipeline again for a changed `state`. The
+    /// rendered layers are kept, so they composite until the next prepare
+    /// renders them with the new state.
+    pub(crate) fn recreate_pipeline(&mut self, device: &Device, state: &PipelineState) {
+        self.pipeline = Self::create_pipeline(device, state, &self.layout);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.buffers.clear();
+        self.layers.clear();
+        self.draws.clear();
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Copy the vertices of every shape in one of `groups` from `buffers`,
+    /// moved from the target of `target_size` into their layer.
+    pub(crate) fn push(&mut self, groups: &[Group], shapes: &[PreparedShape], buffers: &VertexBuffers<DefaultVertex, u16>, target_size: (u32, u32), y_axis: YAxis) {
+        self.layers = groups.iter().enumerate().map(|(layer, &group)| Layer{
+            group,
+            first: shapes.iter().position(|shape| shape.layer == Some(layer)).expect("groups have a prepared shape"),
+        }).collect();
+        let (width, height) = (target_size.0 as f32, target_size.1 as f32);
+        for (i, shape) in shapes.iter().enumerate() {
+            let Some(layer) = shape.layer else {continue;};
+            let rect = groups[layer].rect;
+            let Some(scissor) = shape.bound.intersect(&rect) else {continue;};
+            let [x, y, w, h] = [rect.x as f32, rect.y as f32, rect.width as f32, rect.height as f32];
+            // The pixel a vertex lands on stays the same relative to the
+            // layer's corner instead of the target's.
+            let move_into = |[px, py]: [f32; 2]| [
+                ((px + 1.0) * width - 2.0 * x) / w - 1.0,
+                match y_axis {
+                    YAxis::Up => 1.0 - ((1.0 - py) * height - 2.0 * y) / h,
+                    YAxis::Down => ((1.0 + py) * height - 2.0 * y) / h - 1.0,
+                },
+            ];
+            self.draws.push(LayerDraw{
+                layer,
+                shape: i,
+                start: self.buffers.indices.len(),
+                end: self.buffers.indices.len() + shape.end - shape.start,
+                vertex_start: self.buffers.vertices.len(),
+                scissor: ScissorRect::from_pos_size(scissor.x - rect.x, scissor.y - rect.y, scissor.width, scissor.height),
+            });
+            self.buffers.vertices.extend(buffers.vertices[shape.vertex_start..shape.vertex_end].iter().map(|vertex| DefaultVertex{position: move_into(vertex.position), ..*vertex}));
+            self.buffers.indices.extend_from_slice(&buffers.indices[shape.start..shape.end]);
+        }
+    }
+
+    /// Take a target for every layer pushed since the last clear and upload
+    /// their geometry. Returns false when there were none.
+    pub(crate) fn write(&mut self, device: &Device, queue: &Queue, state: &PipelineState) -> bool {
+        if self.layers.is_empty() {return false;}
+
+        let mut previous = std::mem::take(&mut self.pool);
+        for layer in &self.layers {
+            let rect = layer.group.rect;
+            let key = TargetKey{
+                size: (rect.width, rect.height),
+                format: state.target.format,
+                sample_count: state.multisample.count,
+                depth_format: state.depth_stencil.as_ref().map(|depth| depth.format),
+            };
+            let target = match previous.iter().position(|t| t.key == key) {
+                Some(i) => previous.swap_remove(i),
+                None => self.create_target(device, key),
+            };
+            let uniforms = CompositeUniforms{origin: [rect.x as f32, rect.y as f32], opacity: layer.group.opacity, _padding: 0.0};
+            queue.write_buffer(&target.uniforms, 0, bytemuck::bytes_of(&uniforms));
+            self.pool.push(target);
+        }
+
+        if !self.buffers.vertices.is_empty() && !self.buffers.indices.is_empty() {
+            self.vertex_buffer.write_buffer(device, queue, bytemuck::cast_slice(&self.buffers.vertices));
+            self.index_buffer.write_buffer(device, queue, bytemuck::cast_slice(&self.buffers.indices));
+        }
+        true
+    }
+
+    fn create_target(&self, device: &Device, key: TargetKey) -> LayerTarget {
+        let texture = |format, sample_count, usage| device.create_texture(&TextureDescriptor{
+            label: None,
+            size: Extent3d{width: key.size.0, height: key.size.1, depth_or_array_layers: 1},
+            mip_level_count: 1,
+            sample_count,
+            dimension: TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        }).create_view(&Default::default());
+        let resolved = texture(key.format, 1, TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING);
+        let multisampled = (key.sample_count > 1).then(|| texture(key.format, key.sample_count, TextureUsages::RENDER_ATTACHMENT));
+        let depth = key.depth_format.map(|format| texture(format, key.sample_count, TextureUsages::RENDER_ATTACHMENT));
+        let uniforms = device.create_buffer_init(&BufferInitDescriptor{
+            label: None,
+            contents: bytemuck::bytes_of(&CompositeUniforms{origin: [0.0; 2], opacity: 1.0, _padding: 0.0}),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor{
+            label: None,
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry{binding: 0, resource: uniforms.as_entire_binding()},
+                BindGroupEntry{binding: 1, resource: BindingResource::TextureView(&resolved)},
+            ],
+        });
+        let (view, resolve_target) = match multisampled {
+            Some(multisampled) => (multisampled, Some(resolved)),
+            None => (resolved, None),
+        };
+        LayerTarget{key, view, resolve_target, depth, uniforms, bind_group}
+    }
+
+    /// Render the shapes of every layer into its target, cleared to
+    /// transparent, and submit the work, inside a `wgpu_cyat::prepare` debug
+    /// group if `debug_group` is set. `bind` sets the pipeline, bind groups
+    /// and stencil reference the renderer draws the shape at the index with.
+    pub(crate) fn render(&self, device: &Device, queue: &Queue, state: &PipelineState, debug_group: bool, bind: impl Fn(&mut RenderPass<'_>, usize)) {
+        // Depth is cleared to the farthest value the test passes for.
+        let depth_clear = match state.depth_stencil.as_ref().map(|depth| depth.depth_compare) {
+            Some(CompareFunction::Greater | CompareFunction::GreaterEqual) => 0.0,
+            _ => 1.0,
+        };
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        if debug_group {encoder.push_debug_group("wgpu_cyat::prepare");}
+        for (layer, target) in self.pool.iter().enumerate() {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor{
+                label: None,
+                color_attachments: &[Some(RenderPassColorAttachment{
+                    view: &target.view,
+                    resolve_target: target.resolve_target.as_ref(),
+                    ops: Operations{load: LoadOp::Clear(Color::TRANSPARENT), store: StoreOp::Store},
+                })],
+                depth_stencil_attachment: target.depth.as_ref().zip(target.key.depth_format).map(|(view, format)| RenderPassDepthStencilAttachment{
+                    view,
+                    depth_ops: format.has_depth_aspect().then_some(Operations{load: LoadOp::Clear(depth_clear), store: StoreOp::Discard}),
+                    stencil_ops: format.has_stencil_aspect().then_some(Operations{load: LoadOp::Clear(0), store: StoreOp::Discard}),
+                }),
+                ..RenderPassDescriptor::default()
+            });
+            if self.buffers.indices.is_empty() {continue;}
+            render_pass.set_vertex_buffer(state.vertex_buffer_slot, self.vertex_buffer.as_ref().slice(..));
+            render_pass.set_index_buffer(self.index_buffer.as_ref().slice(..), IndexFormat::Uint16);
+            for draw in self.draws.iter().filter(|draw| draw.layer == layer) {
+                bind(&mut render_pass, draw.shape);
+                render_pass.set_scissor_rect(draw.scissor.x, draw.scissor.y, draw.scissor.width, draw.scissor.height);
+                render_pass.draw_indexed(draw.start as u32..draw.end as u32, draw.vertex_start as i32, 0..1);
+            }
+        }
+        if debug_group {encoder.pop_debug_group();}
+        queue.submit([encoder.finish()]);
+    }
+
+    /// Whether the shape at `index` of the prepared frame is drawn by
+    /// [`Self::draw`] in place of the shapes of `layer`.
+    pub(crate) fn composites_at(&self, layer: usize, index: usize) -> bool {
+        self.layers[layer].first == index
+    }
+
+    pub(crate) fn rect(&self, layer: usize) -> ScissorRect {
+        self.layers[layer].group.rect
+    }
+
+    /// Blend `layer` onto the target within `scissor`, leaving bind group 0
+    /// and the pipeline for the caller to set again.
+    pub(crate) fn draw(&self, render_pass: &mut RenderPass<'_>, layer: usize, scissor: ScissorRect) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.pool[layer].bind_group, &[]);
+        render_pass.set_scissor_rect(scissor.x, scissor.y, scissor.width, scissor.height);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -1,155 +1,4559 @@
-use wgpu::{PipelineCompilationOptions, RenderPipelineDescriptor, PipelineLayoutDescriptor, VertexBufferLayout, DepthStencilState, MultisampleState, RenderPipeline, PrimitiveState, VertexStepMode, FragmentState, TextureFormat, BufferAddress, BufferUsages, IndexFormat, VertexState, RenderPass, Device, Queue};
+//! With the `tracing` feature enabled the renderer emits these spans:
+//!
+//! - `wgpu_cyat::prepare` (INFO) with `vertex_count` and `index_count` fields
+//! - `wgpu_cyat::build_shape` (DEBUG) around the tessellation of each shape
+//! - `wgpu_cyat::upload` (DEBUG) around the vertex and index buffer writes
+//! - `wgpu_cyat::render` (INFO) with a `draw_calls` field
+//!
+//! All of them use the `wgpu_cyat` target, so `wgpu_cyat=debug` selects them.
+//! The `debug_checks` feature adds a WARN event in debug builds for every
+//! pair of partly overlapping bounds, see [`CyatRenderer::check_scissor_overlaps`].
+
+use wgpu::{PipelineCompilationOptions, RenderPipelineDescriptor, PipelineLayoutDescriptor, PrimitiveTopology, VertexBufferLayout, DepthStencilState, MultisampleState, PipelineLayout, RenderPipeline, PrimitiveState, VertexStepMode, FragmentState, TextureFormat, BufferAddress, BufferUsages, TextureViewDescriptor, TextureViewDimension, TextureView, Texture, ColorTargetState, ColorWrites, BlendState, BlendComponent, BlendFactor, BlendOperation, BindGroupLayoutDescriptor, BindGroupLayout, BufferDescriptor, BufferBinding, BindingResource, BindGroupLayoutEntry, BindGroupDescriptor, BindGroupEntry, BufferBindingType, ShaderStages, BindingType, BindGroup, Features, Buffer, CompareFunction, StencilState, StencilFaceState, StencilOperation, DepthBiasState, TextureDescriptor, TextureDimension, TextureUsages, Extent3d, PipelineCache, IndexFormat, VertexState, RenderPass, Device, Queue, RequestDeviceError, DeviceDescriptor, MemoryHints, Instance, Surface, Adapter, Limits, Color, FrontFace, Maintain, CommandEncoder, RenderPassDescriptor, RenderPassColorAttachment, RenderPassDepthStencilAttachment, Operations, LoadOp, StoreOp, QuerySet, QuerySetDescriptor, QueryType, PipelineStatisticsTypes, CommandEncoderDescriptor, MapMode, PresentMode};
+#[cfg(not(target_arch = "wasm32"))]
+use wgpu::{DeviceType, Backends, Backend};
+#[cfg(target_arch = "wasm32")]
+use wgpu::{RequestAdapterOptions, PowerPreference};
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::future::Future;
+use std::num::{NonZeroU32, NonZeroU64};
+use std::ops::Range;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt, DrawIndexedIndirectArgs};
 
 use wgpu_dyn_buffer::{DynamicBufferDescriptor, DynamicBuffer};
 
-use ordered_float::OrderedFloat;
+use lyon_tessellation::{FillVertexConstructor, FillTessellator, BuffersBuilder, FillOptions, FillVertex};
+use lyon_tessellation::path::builder::PathBuilder;
+use lyon_tessellation::math::point;
+
+pub use cyat;
+use cyat::{VertexBuffers, ShapeBuilder, Shape, DrawCommand, Vertex};
+
+/// Compile a built-in shader followed by the `grade` function of the
+/// `color_grading` feature, or one returning colors unchanged without it.
+macro_rules! builtin_shader {
+    ($device:expr, $path:literal) => {
+        $device.create_shader_module(wgpu::ShaderModuleDescriptor{
+            label: Some($path),
+            source: wgpu::ShaderSource::Wgsl(concat!(include_str!($path), grade_source!()).into()),
+        })
+    };
+}
+
+#[cfg(feature = "color_grading")]
+macro_rules! grade_source {() => {include_str!("color_grading.wgsl")};}
+#[cfg(not(feature = "color_grading"))]
+macro_rules! grade_source {() => {include_str!("grade.wgsl")};}
+
+mod blur;
+pub use blur::BackdropBlur;
+
+mod mask;
+use mask::Masks;
+
+mod layer;
+use layer::{Layers, Group};
+
+mod gradient;
+pub use gradient::{GradientStop, ConicalGradient, GradientInterpolation, MeshGradient, NoiseFill, GradientType, MAX_GRADIENT_STOPS, MAX_NOISE_OCTAVES};
+use gradient::Gradients;
+
+mod sprite;
+pub use sprite::{SpriteTexture, Sprite, TexturedVertex, GlyphBatch};
+use sprite::Sprites;
+
+mod path_ops;
+pub use path_ops::PathOps;
+
+mod scene;
+pub use scene::{CyatSceneGraph, ShapeId};
+
+mod chart;
+pub use chart::{PieSlice, PieChart, BarData, BarChartOptions, LineChartOptions, MarkerShape, DataRange};
+
+mod macros;
+#[doc(hidden)]
+pub use macros::ShapeOptions;
+
+mod palette;
+pub use palette::Palette;
+
+#[cfg(feature = "test-harness")]
+pub mod harness;
+#[cfg(feature = "test-harness")]
+mod png;
+
+#[cfg(all(feature = "hot-reload", debug_assertions))]
+mod hot_reload;
+
+#[cfg(feature = "bench")]
+pub mod bench;
+
+#[cfg(feature = "capture")]
+mod capture;
+#[cfg(feature = "capture")]
+pub use capture::{replay, MAGIC, CAPTURE_VERSION};
+
+#[cfg(feature = "color_grading")]
+mod color_grading;
+#[cfg(feature = "color_grading")]
+use color_grading::ColorLut;
+
+/// Tolerance used for [`Geometry::Shape`] when neither the shape nor the
+/// renderer specify one, in the same NDC units as vertex positions.
+pub const DEFAULT_TOLERANCE: f32 = 0.001;
+
+/// Most views a multiview renderer can draw to, see
+/// [`CyatRendererDescriptor::multiview`].
+pub const MAX_VIEWS: u32 = 4;
+
+/// Number of colors in the palette set by [`CyatRenderer::set_palette`].
+pub const PALETTE_SIZE: usize = 64;
+
+/// Override constants the built-in shaders declare, see
+/// [`CyatRendererDescriptor::with_constant`]:
+///
+/// - `OUTPUT_SRGB`: encode the output as sRGB, for targets without an sRGB
+///   format. Off by default.
+/// - `DITHER`: add up to half an 8-bit step of noise to every pixel, hiding
+///   banding in gradients and other smooth fills. Off by default.
+///
+/// - `Y_DOWN`: negate y of vertex positions, set by
+///   [`CyatRendererDescriptor::y_axis`].
+///
+/// All are booleans, passed as `0.0` or `1.0`.
+pub const SHADER_CONSTANTS: &[&str] = &["OUTPUT_SRGB", "DITHER", "Y_DOWN"];
+
+/// Which way y points in the NDC vertex positions of shapes, see
+/// [`CyatRendererDescriptor::y_axis`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum YAxis {
+    /// +1.0 is the top of the target, as in wgpu.
+    #[default]
+    Up,
+    /// +1.0 is the bottom of the target, as in texture coordinates.
+    Down,
+}
+
+/// Rect in framebuffer pixels with its origin at the top left.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ScissorRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ScissorRect {
+    pub fn from_pos_size(x: u32, y: u32, width: u32, height: u32) -> Self {
+        ScissorRect{x, y, width, height}
+    }
+
+    /// Rect from `min` up to but excluding `max`, empty when `max` is not
+    /// past `min` on both axes.
+    pub fn from_min_max(min: [u32; 2], max: [u32; 2]) -> Self {
+        ScissorRect{x: min[0], y: min[1], width: max[0].saturating_sub(min[0]), height: max[1].saturating_sub(min[1])}
+    }
+
+    /// Exclusive bottom right corner.
+    pub fn max(&self) -> [u32; 2] {
+        [self.x.saturating_add(self.width), self.y.saturating_add(self.height)]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn contains_point(&self, point: [u32; 2]) -> bool {
+        let max = self.max();
+        point[0] >= self.x && point[0] < max[0] && point[1] >= self.y && point[1] < max[1]
+    }
+
+    /// Overlap of both rects, `None` when it is empty.
+    pub fn intersect(&self, other: &ScissorRect) -> Option<ScissorRect> {
+        let (max, other_max) = (self.max(), other.max());
+        let rect = ScissorRect::from_min_max(
+            [self.x.max(other.x), self.y.max(other.y)],
+            [max[0].min(other_max[0]), max[1].min(other_max[1])],
+        );
+        (!rect.is_empty()).then_some(rect)
+    }
+
+    /// Parts of this rect outside `other`, as up to four rects.
+    pub fn subtract(&self, other: &ScissorRect) -> Vec<ScissorRect> {
+        let Some(inner) = self.intersect(other) else {return vec![*self];};
+        let (max, inner_max) = (self.max(), inner.max());
+        [
+            ScissorRect::from_min_max([self.x, self.y], [max[0], inner.y]),
+            ScissorRect::from_min_max([self.x, inner_max[1]], max),
+            ScissorRect::from_min_max([self.x, inner.y], [inner.x, inner_max[1]]),
+            ScissorRect::from_min_max([inner_max[0], inner.y], [max[0], inner_max[1]]),
+        ].into_iter().filter(|rect| !rect.is_empty()).collect()
+    }
+}
+
+/// `(x, y, width, height)`, the form bounds took before [`ScissorRect`].
+impl From<(u32, u32, u32, u32)> for ScissorRect {
+    fn from((x, y, width, height): (u32, u32, u32, u32)) -> Self {
+        ScissorRect{x, y, width, height}
+    }
+}
+
+/// Axis aligned bounding box in the same NDC space as vertex positions.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb2D {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Aabb2D {
+    /// Bounding box of `points`, `None` when there are none.
+    pub fn from_points(points: impl IntoIterator<Item = [f32; 2]>) -> Option<Self> {
+        points.into_iter().fold(None, |aabb: Option<Aabb2D>, p| Some(match aabb {
+            Some(a) => Aabb2D{
+                min: [a.min[0].min(p[0]), a.min[1].min(p[1])],
+                max: [a.max[0].max(p[0]), a.max[1].max(p[1])],
+            },
+            None => Aabb2D{min: p, max: p},
+        }))
+    }
+
+    pub fn contains(&self, point: [f32; 2]) -> bool {
+        point[0] >= self.min[0] && point[0] <= self.max[0] &&
+        point[1] >= self.min[1] && point[1] <= self.max[1]
+    }
+
+    pub fn intersects(&self, other: &Aabb2D) -> bool {
+        self.min[0] <= other.max[0] && other.min[0] <= self.max[0] &&
+        self.min[1] <= other.max[1] && other.min[1] <= self.max[1]
+    }
+
+    pub fn union(&self, other: &Aabb2D) -> Aabb2D {
+        Aabb2D{
+            min: [self.min[0].min(other.min[0]), self.min[1].min(other.min[1])],
+            max: [self.max[0].max(other.max[0]), self.max[1].max(other.max[1])],
+        }
+    }
+}
+
+/// Geometry from a source other than cyat, like another crate's path type
+/// or a hand-written mesh, to draw as a [`Geometry::Fill`].
+pub trait FillGeometry: Send + Sync {
+    /// Append triangles to `out`, flattening curves to within `tolerance`.
+    /// Indices count from the first vertex appended; the renderer offsets
+    /// them by whatever `out` held before.
+    fn append(&self, tolerance: f32, out: &mut VertexBuffers<DefaultVertex, u16>);
+}
+
+impl FillGeometry for VertexBuffers<DefaultVertex, u16> {
+    fn append(&self, _tolerance: f32, out: &mut VertexBuffers<DefaultVertex, u16>) {
+        out.vertices.extend_from_slice(&self.vertices);
+        out.indices.extend_from_slice(&self.indices);
+    }
+}
+
+impl FillGeometry for Shape<DefaultAttributes> {
+    fn append(&self, tolerance: f32, out: &mut VertexBuffers<DefaultVertex, u16>) {
+        ShapeBuilder::new(copy_shape(self), None, tolerance).build(out);
+    }
+}
+
+pub enum Geometry {
+    /// A prebuilt shape, tessellated with the tolerance it was created with.
+    Builder(ShapeBuilder<DefaultAttributes>),
+    /// A shape and optional stroke width, tessellated at prepare time with the
+    /// area's tolerance or the renderer default.
+    Shape(Shape<DefaultAttributes>, Option<f32>),
+    /// Already tessellated vertices and shape local indices.
+    Mesh(VertexBuffers<DefaultVertex, u16>),
+    /// Another geometry with its positions scaled about the NDC origin.
+    Scaled(Box<Geometry>, [f32; 2]),
+    /// Geometry appended by a [`FillGeometry`] at prepare time with the
+    /// area's tolerance or the renderer default.
+    Fill(Box<dyn FillGeometry>),
+}
+
+impl Geometry {
+    fn build(self, tolerance: f32, fill_rule: FillRule, buffer: &mut VertexBuffers<DefaultVertex, u16>) {
+        match self {
+            Geometry::Builder(builder) => builder.build(buffer),
+            Geometry::Scaled(geometry, scale) => {
+                let start = buffer.vertices.len();
+                geometry.build(tolerance, fill_rule, buffer);
+                scale_vertices(&mut buffer.vertices[start..], scale);
+            }
+            geometry => geometry.build_ref(tolerance, fill_rule, buffer),
+        }
+    }
+
+    /// Build without consuming the geometry. cyat only builds shapes by
+    /// value, so a [`Geometry::Shape`] is copied and a
+    /// [`Geometry::Builder`] cannot be built at all, see [`Self::needs_owned`].
+    fn build_ref(&self, tolerance: f32, fill_rule: FillRule, buffer: &mut VertexBuffers<DefaultVertex, u16>) {
+        match self {
+            Geometry::Builder(_) => unreachable!("builders are built by value"),
+            Geometry::Shape(shape @ Shape::Draw(..), None) if fill_rule == FillRule::NonZero => fill_non_zero(shape, tolerance, buffer),
+            Geometry::Shape(shape, stroke_width) => ShapeBuilder::new(copy_shape(shape), *stroke_width, tolerance).build(buffer),
+            Geometry::Mesh(mesh) => {
+                let base = buffer.vertices.len() as u16;
+                buffer.vertices.extend_from_slice(&mesh.vertices);
+                buffer.indices.extend(mesh.indices.iter().map(|i| base + i));
+            }
+            Geometry::Scaled(geometry, scale) => {
+                let start = buffer.vertices.len();
+                geometry.build_ref(tolerance, fill_rule, buffer);
+                scale_vertices(&mut buffer.vertices[start..], *scale);
+            }
+            Geometry::Fill(fill) => {
+                let (base, start) = (buffer.vertices.len() as u16, buffer.indices.len());
+                fill.append(tolerance, buffer);
+                for index in &mut buffer.indices[start..] {*index += base;}
+            }
+        }
+    }
+
+    /// Build `taken`, the geometry prepare moved out of the shape, or else
+    /// this one by reference.
+    fn build_or(&self, taken: Option<Geometry>, tolerance: f32, fill_rule: FillRule, buffer: &mut VertexBuffers<DefaultVertex, u16>) {
+        match taken {
+            Some(geometry) => geometry.build(tolerance, fill_rule, buffer),
+            None => self.build_ref(tolerance, fill_rule, buffer),
+        }
+    }
+
+    /// The depth of every vertex when the geometry has a single one, so it
+    /// can be checked without looking at the vertices.
+    fn constant_z(&self) -> Option<f32> {
+        match self {
+            Geometry::Shape(Shape::Rectangle(attrs, ..) | Shape::RoundedRectangle(attrs, ..) | Shape::Ellipse(attrs, ..), _) => Some(attrs.z),
+            Geometry::Scaled(geometry, _) => geometry.constant_z(),
+            Geometry::Shape(Shape::Draw(..), _) | Geometry::Builder(_) | Geometry::Mesh(_) | Geometry::Fill(_) => None,
+        }
+    }
+
+    /// Whether the geometry holds a [`Geometry::Builder`], which can only be
+    /// built by value.
+    fn needs_owned(&self) -> bool {
+        match self {
+            Geometry::Builder(_) => true,
+            Geometry::Scaled(geometry, _) => geometry.needs_owned(),
+            Geometry::Shape(..) | Geometry::Mesh(_) | Geometry::Fill(_) => false,
+        }
+    }
+
+    fn into_mesh(self, tolerance: f32, fill_rule: FillRule) -> VertexBuffers<DefaultVertex, u16> {
+        match self {
+            Geometry::Mesh(mesh) => mesh,
+            geometry => {
+                let mut mesh = VertexBuffers::new();
+                geometry.build(tolerance, fill_rule, &mut mesh);
+                mesh
+            }
+        }
+    }
+}
+
+/// How the indices of a [`ShapeArea`](struct@ShapeArea) are assembled into primitives.
+///
+/// wgpu has no line width or point size, so [`Topology::LineList`] and
+/// [`Topology::PointList`] always rasterize one pixel wide; they are meant
+/// for debug overlays rather than strokes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Topology {
+    #[default]
+    TriangleList,
+    /// Indices are read as pairs of vertices.
+    LineList,
+    /// Every indexed vertex is drawn as a point.
+    PointList,
+}
+
+impl From<Topology> for PrimitiveTopology {
+    fn from(topology: Topology) -> Self {
+        match topology {
+            Topology::TriangleList => PrimitiveTopology::TriangleList,
+            Topology::LineList => PrimitiveTopology::LineList,
+            Topology::PointList => PrimitiveTopology::PointList,
+        }
+    }
+}
+
+/// How [`CyatRenderer::prepare`] writes the frame's vertices and indices to
+/// the GPU, see [`CyatRenderer::set_upload_strategy`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum UploadStrategy {
+    /// `Queue::write_buffer`, copying the data into a staging buffer.
+    #[default]
+    WriteBuffer,
+    /// `Queue::write_buffer_with`, writing the data straight into the
+    /// mapped staging memory, which saves a copy on unified-memory GPUs.
+    /// Writes it cannot map fall back to [`Self::WriteBuffer`].
+    Mapped,
+}
+
+impl UploadStrategy {
+    /// [`Self::Mapped`] when `device` has [`Features::MAPPABLE_PRIMARY_BUFFERS`],
+    /// which unified-memory GPUs report, [`Self::WriteBuffer`] otherwise.
+    pub fn for_device(device: &Device) -> Self {
+        match device.features().contains(Features::MAPPABLE_PRIMARY_BUFFERS) {
+            true => UploadStrategy::Mapped,
+            false => UploadStrategy::WriteBuffer,
+        }
+    }
+}
+
+/// What [`CyatRenderer::prepare`] does with an empty list of shapes, see
+/// [`CyatRenderer::set_empty_frame`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum EmptyFrame {
+    /// Prepare an empty frame, so [`CyatRenderer::render`] draws nothing.
+    #[default]
+    Clear,
+    /// Leave the previous frame's buffers untouched, so it is drawn again,
+    /// and report it with [`FrameStats::stale`]. For callers that may miss
+    /// producing a frame, to avoid a flash of the clear color.
+    KeepPrevious,
+}
+
+/// A shape and how to draw it. Start from a constructor like
+/// [`ShapeArea::auto_bound`] and chain the `with_*` methods for the optional
+/// settings, e.g. `ShapeArea::auto_bound(builder).with_bound((x, y, w, h)).with_z_index(2).with_label("toolbar")`.
+pub struct ShapeArea {
+    pub geometry: Geometry,
+    /// Scissor rect; `None` computes it from the tessellated geometry, see
+    /// [`ShapeArea::auto_bound`].
+    pub bound: Option<ScissorRect>,
+    /// Overrides the renderer tolerance for [`Geometry::Shape`], see
+    /// [`ShapeArea::with_tolerance`].
+    tolerance: Option<f32>,
+    /// Appended to the name of the debug group around this shape's draw, see
+    /// [`CyatRenderer::set_debug_groups`].
+    pub label: Option<String>,
+    pub topology: Topology,
+    pub uniforms: ShapeUniforms,
+    /// Index of another shape in the same [`CyatRenderer::prepare`] call. The
+    /// bound is then relative to the parent bound's origin and clipped to the
+    /// parent's visible rect, or for an auto bound only clipped.
+    pub parent: Option<usize>,
+    /// Draw order: shapes are drawn by ascending `z_index`, keeping their
+    /// order within the same value. Independent of the depth value
+    /// [`DefaultAttributes::z`].
+    pub z_index: i32,
+    /// Only the parts of the shape inside this geometry are drawn, see
+    /// [`ShapeArea::masked_by`].
+    pub mask: Option<Geometry>,
+    /// Draw after every other shape, see [`ShapeArea::as_overlay`].
+    pub overlay: bool,
+    /// The shape fills its whole bound with opaque pixels, see
+    /// [`CyatRenderer::set_occlusion_culling`].
+    pub opaque: bool,
+    /// Fill in place of the vertex colors, see [`ShapeArea::with_gradient`].
+    pub gradient: Option<GradientType>,
+    /// Name of the pipeline variant to draw with, see
+    /// [`CyatRenderer::register_pipeline`]. `None` uses the default pipeline.
+    pub pipeline: Option<String>,
+    /// Clip to the bound with rounded corners, see
+    /// [`ShapeArea::with_rounded_clip`].
+    pub rounded_clip: Option<RoundedClip>,
+    /// Desaturate or darken the shape after its colors are computed, see
+    /// [`ShapeArea::with_saturation`].
+    pub effect: ColorEffect,
+    /// Texture multiplying the vertex colors, see [`ShapeArea::with_sprite`].
+    pub sprite: Option<Sprite>,
+    /// Which side of the [`Self::mask`] is drawn.
+    pub clip_mode: ClipMode,
+    /// Pixel rect of the target the shape's NDC is mapped into, see
+    /// [`ShapeArea::with_viewport`]. `None` maps it to the whole target.
+    pub viewport: Option<ScissorRect>,
+    /// Reference value of the stencil test while drawing the shape, see
+    /// [`ShapeArea::with_stencil_reference`].
+    pub stencil_reference: u32,
+    /// Which parts of a self intersecting path are filled, see
+    /// [`ShapeArea::with_fill_rule`].
+    pub fill_rule: FillRule,
+    /// The caller's own value for the shape, see [`ShapeArea::with_user_data`].
+    pub user_data: u64,
+    /// Opacity of the shape and its descendants as one layer, see
+    /// [`ShapeArea::with_group_opacity`].
+    pub group_opacity: f32,
+}
+
+/// Which parts of a self intersecting or looping path lie inside it, see
+/// [`ShapeArea::with_fill_rule`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FillRule {
+    /// Inside where a ray from the point crosses the outline an odd number
+    /// of times, so the center of a star or a second loop is left empty.
+    #[default]
+    EvenOdd,
+    /// Inside where the outline winds around the point, so loops in the same
+    /// direction stay filled and only loops in opposite directions cut holes.
+    NonZero,
+}
+
+/// Whether a masked shape is drawn inside or outside its mask, see
+/// [`ShapeArea::masked_outside`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ClipMode {
+    #[default]
+    Inside,
+    /// Only the parts of the shape's bound the mask does not cover, e.g. a
+    /// background with a window cut out of it.
+    Outside,
+}
+
+/// Adjustments the built-in shaders apply to a shape's final color, which
+/// can change every frame without tessellating again.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorEffect {
+    /// 0.0 gives grayscale, 1.0 the original colors.
+    pub saturation: f32,
+    /// Multiplies the color channels.
+    pub brightness: f32,
+}
+
+impl Default for ColorEffect {
+    fn default() -> Self {
+        ColorEffect{saturation: 1.0, brightness: 1.0}
+    }
+}
+
+/// Corner radii in pixels of a [`ShapeArea::rounded_clip`], clockwise from
+/// the top left.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RoundedClip {
+    pub radii: [f32; 4],
+    /// Fade the edge over a pixel, which shows with a
+    /// [`CyatRendererDescriptor::blend`]. Otherwise pixels are kept or
+    /// discarded whole.
+    pub antialias: bool,
+}
+
+/// The tuple struct constructor `ShapeArea(builder, bound)` of earlier
+/// versions, kept so existing code still builds. Same as [`ShapeArea::new`].
+#[allow(non_snake_case)]
+pub fn ShapeArea(builder: ShapeBuilder<DefaultAttributes>, bound: impl Into<ScissorRect>) -> ShapeArea {
+    ShapeArea::new(builder, bound)
+}
+
+impl ShapeArea {
+    fn from_geometry(geometry: Geometry, bound: Option<ScissorRect>) -> Self {
+        ShapeArea{geometry, bound, tolerance: None, label: None, topology: Topology::TriangleList, uniforms: ShapeUniforms::default(), parent: None, z_index: 0, mask: None, overlay: false, opaque: false, gradient: None, pipeline: None, rounded_clip: None, effect: ColorEffect::default(), sprite: None, clip_mode: ClipMode::Inside, viewport: None, stencil_reference: 0, fill_rule: FillRule::EvenOdd, user_data: 0, group_opacity: 1.0}
+    }
+
+    pub fn new(builder: ShapeBuilder<DefaultAttributes>, bound: impl Into<ScissorRect>) -> Self {
+        Self::from_geometry(Geometry::Builder(builder), Some(bound.into()))
+    }
+
+    pub fn shape(shape: Shape<DefaultAttributes>, stroke_width: Option<f32>, bound: impl Into<ScissorRect>) -> Self {
+        Self::from_geometry(Geometry::Shape(shape, stroke_width), Some(bound.into()))
+    }
+
+    /// Geometry of another source, see [`FillGeometry`].
+    pub fn fill(geometry: impl FillGeometry + 'static, bound: impl Into<ScissorRect>) -> Self {
+        Self::from_geometry(Geometry::Fill(Box::new(geometry)), Some(bound.into()))
+    }
+
+    /// A filled axis aligned rectangle between the corners `[x, y, x2, y2]`.
+    pub fn rect(rect: [f32; 4], color: [f32; 3], bound: impl Into<ScissorRect>) -> Self {
+        let [x, y, x2, y2] = rect;
+        Self::shape(Shape::Rectangle(DefaultAttributes{color, z: 0.0, palette: 0}, x, y, x2, y2), None, bound)
+    }
+
+    /// A filled ellipse around `center` with `radii` along x and y.
+    pub fn ellipse(center: [f32; 2], radii: [f32; 2], color: [f32; 3], bound: impl Into<ScissorRect>) -> Self {
+        Self::shape(Shape::Ellipse(DefaultAttributes{color, z: 0.0, palette: 0}, center[0], center[1], radii[0], radii[1]), None, bound)
+    }
+
+    /// A filled circle, round in NDC and so stretched on a target that is
+    /// not square.
+    pub fn circle(center: [f32; 2], radius: f32, color: [f32; 3], bound: impl Into<ScissorRect>) -> Self {
+        Self::ellipse(center, [radius; 2], color, bound)
+    }
+
+    /// A filled rectangle between the corners `[x, y, x2, y2]` with corners
+    /// rounded by `radius`, at most half its shorter side. cyat does not
+    /// tessellate [`Shape::RoundedRectangle`], so the corners are cubic
+    /// curves of a [`Shape::Draw`].
+    pub fn rounded_rect(rect: [f32; 4], radius: f32, color: [f32; 3], bound: impl Into<ScissorRect>) -> Self {
+        let a = DefaultAttributes{color, z: 0.0, palette: 0};
+        let (x0, x1) = (rect[0].min(rect[2]), rect[0].max(rect[2]));
+        let (y0, y1) = (rect[1].min(rect[3]), rect[1].max(rect[3]));
+        let r = radius.min((x1 - x0) / 2.0).min((y1 - y0) / 2.0).max(0.0);
+        // Control points of a cubic closest to a quarter circle.
+        let k = r * (1.0 - 0.552_284_8);
+        let commands = vec![
+            DrawCommand::LineTo(a, x1 - r, y0),
+            DrawCommand::CubicBezierTo(a, x1, y0 + r, x1 - k, y0, x1, y0 + k),
+            DrawCommand::LineTo(a, x1, y1 - r),
+            DrawCommand::CubicBezierTo(a, x1 - r, y1, x1, y1 - k, x1 - k, y1),
+            DrawCommand::LineTo(a, x0 + r, y1),
+            DrawCommand::CubicBezierTo(a, x0, y1 - r, x0 + k, y1, x0, y1 - k),
+            DrawCommand::LineTo(a, x0, y0 + r),
+            DrawCommand::CubicBezierTo(a, x0 + r, y0, x0, y0 + k, x0 + k, y0),
+        ];
+        Self::shape(Shape::Draw(a, x0 + r, y0, commands), None, bound)
+    }
+
+    /// An open line through `points`, `width` wide in NDC. Each segment is a
+    /// separate quad, so sharp turns show a notch on their outside.
+    ///
+    /// A shape's vertices are indexed with 16 bits, so preparing a line of
+    /// more than 16384 segments fails with [`CyatError::TooManyVertices`].
+    pub fn polyline(points: &[[f32; 2]], width: f32, color: [f32; 3], bound: impl Into<ScissorRect>) -> Self {
+        let attributes = DefaultAttributes{color, z: 0.0, palette: 0};
+        let mut mesh = VertexBuffers::new();
+        for segment in points.windows(2) {
+            let ([ax, ay], [bx, by]) = (segment[0], segment[1]);
+            let length = (bx - ax).hypot(by - ay);
+            if length == 0.0 {continue;}
+            let (nx, ny) = (-(by - ay) / length * width / 2.0, (bx - ax) / length * width / 2.0);
+            // Wraps past 16384 segments, which prepare rejects.
+            let base = mesh.vertices.len() as u16;
+            mesh.vertices.extend([[ax + nx, ay + ny], [ax - nx, ay - ny], [bx - nx, by - ny], [bx + nx, by + ny]].map(|p| DefaultVertex::construct(p, attributes)));
+            mesh.indices.extend([0, 1, 2, 0, 2, 3].map(|i| base + i));
+        }
+        Self::from_geometry(Geometry::Mesh(mesh), Some(bound.into()))
+    }
+
+    pub fn labeled(label: impl Into<String>, builder: ShapeBuilder<DefaultAttributes>, bound: impl Into<ScissorRect>) -> Self {
+        Self::new(builder, bound).with_label(label)
+    }
+
+    /// Scissor to the pixel bounding box of the tessellated vertices, culling
+    /// the shape when it lies entirely outside the target set by
+    /// [`CyatRenderer::resize`].
+    pub fn auto_bound(builder: ShapeBuilder<DefaultAttributes>) -> Self {
+        Self::from_geometry(Geometry::Builder(builder), None)
+    }
+
+    /// Tessellate `builder` now and scissor to the pixel bounding box of its
+    /// vertices on a target of `viewport` width and height, with y up in
+    /// NDC. Unlike [`Self::auto_bound`] the bound is known before
+    /// [`CyatRenderer::prepare`], so the shape can be a parent. A shape
+    /// entirely outside the target gets an empty bound and is culled.
+    pub fn auto_scissor(builder: ShapeBuilder<DefaultAttributes>, viewport: (u32, u32)) -> Self {
+        let mesh = Geometry::Builder(builder).into_mesh(DEFAULT_TOLERANCE, FillRule::EvenOdd);
+        let target = ScissorRect::from_pos_size(0, 0, viewport.0, viewport.1);
+        let bound = Aabb2D::from_points(mesh.vertices.iter().map(|v| v.position)).and_then(|aabb| pixel_rect(aabb, target));
+        Self::from_geometry(Geometry::Mesh(mesh), Some(bound.unwrap_or_default()))
+    }
+
+    /// Keep the proportions of a shape designed in NDC for a
+    /// `design_width` x `design_height` target when drawn to the viewport,
+    /// scaling by `min(vw/dw, vh/dh)` and centering. See
+    /// [`ShapeArea::letterbox_bars`] for the uncovered margins.
+    pub fn aspect_preserved(
+        builder: ShapeBuilder<DefaultAttributes>,
+        bound: impl Into<ScissorRect>,
+        viewport_width: u32,
+        viewport_height: u32,
+        design_width: u32,
+        design_height: u32,
+    ) -> Self {
+        let (width, height) = aspect_fit(viewport_width, viewport_height, design_width, design_height);
+        let scale = [width / viewport_width as f32, height / viewport_height as f32];
+        Self::from_geometry(Geometry::Scaled(Box::new(Geometry::Builder(builder)), scale), Some(bound.into()))
+    }
+
+    /// Black bars over the parts of the viewport left uncovered by
+    /// [`ShapeArea::aspect_preserved`]; empty when the aspect ratios match.
+    pub fn letterbox_bars(viewport_width: u32, viewport_height: u32, design_width: u32, design_height: u32) -> Vec<ShapeArea> {
+        let (width, height) = aspect_fit(viewport_width, viewport_height, design_width, design_height);
+        let (vw, vh) = (viewport_width as f32, viewport_height as f32);
+        let x = ((vw - width) / 2.0).round() as u32;
+        let y = ((vh - height) / 2.0).round() as u32;
+        let bars = [
+            ScissorRect::from_pos_size(0, 0, x, viewport_height),
+            ScissorRect::from_pos_size(viewport_width - x, 0, x, viewport_height),
+            ScissorRect::from_pos_size(0, 0, viewport_width, y),
+            ScissorRect::from_pos_size(0, viewport_height - y, viewport_width, y),
+        ];
+        bars.into_iter().filter(|bar| !bar.is_empty()).map(|bar| {
+            let max = bar.max();
+            let (x0, x1) = (bar.x as f32 / vw * 2.0 - 1.0, max[0] as f32 / vw * 2.0 - 1.0);
+            let (y0, y1) = (1.0 - bar.y as f32 / vh * 2.0, 1.0 - max[1] as f32 / vh * 2.0);
+            let black = DefaultAttributes{color: [0.0; 3], z: 0.0, palette: 0};
+            let vertices = [[x0, y0], [x1, y0], [x1, y1], [x0, y1]].map(|p| DefaultVertex::construct(p, black));
+            Self::from_geometry(Geometry::Mesh(VertexBuffers{vertices: vertices.to_vec(), indices: vec![0, 1, 2, 0, 2, 3]}), Some(bar))
+        }).collect()
+    }
+
+    /// Scissor to `bound` instead of the current bound.
+    pub fn with_bound(mut self, bound: impl Into<ScissorRect>) -> Self {
+        self.bound = Some(bound.into());
+        self
+    }
+
+    /// Set the alpha of [`ShapeUniforms::tint`], which shows with a
+    /// [`CyatRendererDescriptor::blend`].
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.uniforms.tint[3] = opacity;
+        self
+    }
+
+    /// Clip the shape, and the shapes that have it as their
+    /// [`ShapeArea::parent`], to its bound with corners rounded by `radii`
+    /// in pixels, clockwise from the top left, fading the edge over a pixel.
+    /// Shapes use the clip of their nearest ancestor that has one, so clips
+    /// do not nest. Only the built-in shaders apply it.
+    pub fn with_rounded_clip(mut self, radii: [f32; 4]) -> Self {
+        self.rounded_clip = Some(RoundedClip{radii, antialias: true});
+        self
+    }
+
+    /// Mix the shape's colors towards their luminance, down to grayscale at
+    /// 0.0, e.g. for disabled controls. Multiplies with the saturation of
+    /// the shape's [`ShapeArea::parent`] chain, so setting it on a group
+    /// affects all of it.
+    pub fn with_saturation(mut self, saturation: f32) -> Self {
+        self.effect.saturation = saturation;
+        self
+    }
+
+    /// Scale the shape's colors, multiplying with the brightness of its
+    /// [`ShapeArea::parent`] chain like [`ShapeArea::with_saturation`].
+    pub fn with_brightness(mut self, brightness: f32) -> Self {
+        self.effect.brightness = brightness;
+        self
+    }
+
+    /// Lower values give smoother curves at the cost of more triangles.
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = Some(tolerance);
+        self
+    }
+
+    /// The tolerance set by [`Self::with_tolerance`], or `None` for the
+    /// renderer's.
+    pub fn tolerance(&self) -> Option<f32> {
+        self.tolerance
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_uniforms(mut self, uniforms: ShapeUniforms) -> Self {
+        self.uniforms = uniforms;
+        self
+    }
+
+    pub fn with_parent(mut self, parent: usize) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    pub fn with_z_index(mut self, z_index: i32) -> Self {
+        self.z_index = z_index;
+        self
+    }
+
+    /// Draw on top of everything else, for drag previews and context menus.
+    /// Overlay shapes come after all others, ordered among themselves by
+    /// `z_index`, and are not depth tested unless masked.
+    pub fn as_overlay(mut self) -> Self {
+        self.overlay = true;
+        self
+    }
+
+    /// Promise that the shape covers every pixel of its bound with an opaque
+    /// color, so shapes below it in the same bound can be skipped.
+    pub fn as_opaque(mut self) -> Self {
+        self.opaque = true;
+        self
+    }
+
+    /// Fill the shape and its mask by `fill_rule` instead of
+    /// [`FillRule::EvenOdd`]. Only filled [`Shape::Draw`] paths given as a
+    /// [`Geometry::Shape`] can self intersect and are affected. cyat keeps
+    /// the path of a [`Geometry::Builder`] to itself and always fills it
+    /// even-odd, so prepare fails with [`CyatError::UnsupportedFillRule`]
+    /// for one filled by [`FillRule::NonZero`].
+    pub fn with_fill_rule(mut self, fill_rule: FillRule) -> Self {
+        self.fill_rule = fill_rule;
+        self
+    }
+
+    /// Color the shape by `gradient` instead of its vertex colors, evaluated
+    /// per pixel. Only applies to [`Topology::TriangleList`] shapes that are
+    /// not masked; like masked shapes they are drawn with a built-in shader
+    /// without multiview projections or dual source blending.
+    pub fn with_gradient(mut self, gradient: GradientType) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    /// Multiply the vertex colors by `texture`, sampled at `uvs` given for
+    /// each tessellated vertex in order. Applies to the same shapes as
+    /// [`ShapeArea::with_gradient`], which takes precedence.
+    pub fn with_sprite(mut self, texture: Arc<SpriteTexture>, uvs: Vec<[f32; 2]>) -> Self {
+        self.sprite = Some(Sprite{texture, uvs});
+        self
+    }
+
+    /// Draw with the pipeline variant registered as `name`. Masked and
+    /// gradient shapes keep their own pipelines, and overlay shapes drawn
+    /// with a variant use its depth test.
+    pub fn with_pipeline(mut self, name: impl Into<String>) -> Self {
+        self.pipeline = Some(name.into());
+        self
+    }
+
+    /// Clip the shape to an arbitrary `mask` shape. The mask is rendered each
+    /// prepare into an offscreen texture covering this shape's bound, which
+    /// the shape then samples, so [`CyatRenderer::resize`] must be set.
+    ///
+    /// Masked shapes are always drawn as triangles with the built-in shader,
+    /// without multiview projections, and are not supported with dual source
+    /// blending or by [`CyatRenderer::render_with_pipeline`].
+    pub fn masked_by(mut self, mask: ShapeBuilder<DefaultAttributes>) -> Self {
+        self.mask = Some(Geometry::Builder(mask));
+        self
+    }
+
+    /// Like [`Self::masked_by`], but draw only the parts of the shape outside
+    /// `mask`, within the shape's bound.
+    pub fn masked_outside(self, mask: ShapeBuilder<DefaultAttributes>) -> Self {
+        self.masked_by(mask).with_clip_mode(ClipMode::Outside)
+    }
+
+    /// Draw the shape inside or outside its mask, see [`Self::masked_by`].
+    pub fn with_clip_mode(mut self, clip_mode: ClipMode) -> Self {
+        self.clip_mode = clip_mode;
+        self
+    }
+
+    /// Draw the shape scaled into `viewport`, a pixel rect of the target, as
+    /// if it were the whole target, e.g. to show the same geometry as a
+    /// thumbnail without tessellating it again. The bound still clips in
+    /// target pixels, so both can be combined to letterbox an embedded
+    /// canvas; an auto bound is computed within the viewport.
+    ///
+    /// The viewport must be non-empty and within the target set by
+    /// [`CyatRenderer::resize`], else prepare fails with
+    /// [`CyatError::InvalidViewport`]. Masked shapes ignore it.
+    pub fn with_viewport(mut self, viewport: impl Into<ScissorRect>) -> Self {
+        self.viewport = Some(viewport.into());
+        self
+    }
+
+    /// Attach an opaque value of the caller's to the shape, like a widget id
+    /// or entity handle, 0 by default. It stays with the shape through
+    /// sorting, grouping and culling, so [`CyatRenderer::user_data`] maps a
+    /// drawn shape back to it without a parallel list.
+    pub fn with_user_data(mut self, user_data: u64) -> Self {
+        self.user_data = user_data;
+        self
+    }
+
+    /// Draw the shape and every shape with it in its [`ShapeArea::parent`]
+    /// chain into an offscreen layer the size of its bound, then blend the
+    /// layer onto the target with `opacity`. Unlike [`ShapeArea::with_opacity`]
+    /// on each shape, overlapping shapes of the group do not show through
+    /// each other, e.g. when fading out a whole window.
+    ///
+    /// The layer is drawn where the first shape of the group would be, so
+    /// shapes of other groups sorted between its shapes by
+    /// [`ShapeArea::z_index`] end up above it, and the group's depth is not
+    /// written to the target. At 1.0, the default, the shapes are drawn
+    /// directly.
+    ///
+    /// Prepare fails with [`CyatError::UnboundGroup`] when the shape has no
+    /// explicit bound, [`CyatError::NestedGroup`] for a group within another,
+    /// [`CyatError::UnsupportedGroupShape`] when a shape of the group has a
+    /// mask, gradient, sprite, rounded clip or viewport, and
+    /// [`CyatError::UnsupportedGroup`] on renderers with quantized vertices,
+    /// multiview, dual source blending or extra bind group layouts.
+    pub fn with_group_opacity(mut self, opacity: f32) -> Self {
+        self.group_opacity = opacity;
+        self
+    }
+
+    /// Draw the shape with `reference` as the stencil reference, so it can
+    /// take part in a stencil scheme set up through
+    /// [`CyatRendererDescriptor::depth_stencil`]. The renderer only sets the
+    /// reference, 0 by default, and leaves the stencil ops to the caller.
+    pub fn with_stencil_reference(mut self, reference: u32) -> Self {
+        self.stencil_reference = reference;
+        self
+    }
+
+    /// Set [`DefaultAttributes::z`] of every vertex to `z`. Geometry other
+    /// than a [`Geometry::Shape`] is tessellated first.
+    pub fn with_z(mut self, z: f32) -> Self {
+        self.geometry = match self.geometry {
+            Geometry::Shape(mut shape, stroke_width) => {
+                match &mut shape {
+                    Shape::Draw(attrs, _, _, commands) => {
+                        attrs.z = z;
+                        for command in commands {
+                            match command {
+                                DrawCommand::QuadraticBezierTo(attrs, ..) | DrawCommand::CubicBezierTo(attrs, ..) | DrawCommand::LineTo(attrs, ..) => attrs.z = z,
+                            }
+                        }
+                    }
+                    Shape::RoundedRectangle(attrs, ..) | Shape::Rectangle(attrs, ..) | Shape::Ellipse(attrs, ..) => attrs.z = z,
+                }
+                Geometry::Shape(shape, stroke_width)
+            }
+            geometry => {
+                let mut mesh = geometry.into_mesh(self.tolerance.unwrap_or(DEFAULT_TOLERANCE), self.fill_rule);
+                for v in &mut mesh.vertices {v.z = z;}
+                Geometry::Mesh(mesh)
+            }
+        };
+        self
+    }
+
+    /// Whether the shape is filled by [`FillRule::NonZero`] but has a
+    /// [`Geometry::Builder`], which cyat can only fill even-odd.
+    fn fills_builder_non_zero(&self) -> bool {
+        self.fill_rule == FillRule::NonZero && (self.geometry.needs_owned() || self.mask.as_ref().is_some_and(Geometry::needs_owned))
+    }
+
+    /// Tessellate the shape and its mask and rotate their vertices by
+    /// `angle` radians counterclockwise about `pivot`, in NDC, keeping the
+    /// bound and every other setting. On a target that is not square the
+    /// rotation is skewed by its aspect ratio, like any NDC geometry.
+    pub fn rotated(self, angle: f32, pivot: [f32; 2]) -> ShapeArea {
+        let (sin, cos) = angle.sin_cos();
+        self.transformed(|[x, y]| {
+            let (x, y) = (x - pivot[0], y - pivot[1]);
+            [pivot[0] + x * cos - y * sin, pivot[1] + x * sin + y * cos]
+        })
+    }
+
+    /// Tessellate the shape and its mask and scale their vertices by `sx`
+    /// and `sy` about `origin`, in NDC, keeping the bound and every other
+    /// setting.
+    pub fn scaled(self, sx: f32, sy: f32, origin: [f32; 2]) -> ShapeArea {
+        self.transformed(|[x, y]| [origin[0] + (x - origin[0]) * sx, origin[1] + (y - origin[1]) * sy])
+    }
+
+    fn transformed(self, transform: impl Fn([f32; 2]) -> [f32; 2]) -> ShapeArea {
+        let (tolerance, fill_rule) = (self.tolerance.unwrap_or(DEFAULT_TOLERANCE), self.fill_rule);
+        let transform_mesh = |geometry: Geometry| {
+            let mut mesh = geometry.into_mesh(tolerance, fill_rule);
+            for v in &mut mesh.vertices {v.position = transform(v.position);}
+            Geometry::Mesh(mesh)
+        };
+        ShapeArea{
+            geometry: transform_mesh(self.geometry),
+            mask: self.mask.map(transform_mesh),
+            ..self
+        }
+    }
+
+    /// Tessellate the shape and replace its triangles with their edges, drawn
+    /// as a [`Topology::LineList`]. Edges shared by two triangles are kept once.
+    pub fn as_wireframe(self) -> ShapeArea {
+        let mesh = self.geometry.into_mesh(self.tolerance.unwrap_or(DEFAULT_TOLERANCE), self.fill_rule);
+        let mut edges = HashSet::new();
+        let mut indices = Vec::new();
+        for t in mesh.indices.chunks_exact(3) {
+            for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+                if edges.insert((a.min(b), a.max(b))) {indices.extend([a, b]);}
+            }
+        }
+        ShapeArea{
+            geometry: Geometry::Mesh(VertexBuffers{vertices: mesh.vertices, indices}),
+            topology: Topology::LineList,
+            ..self
+        }
+    }
+}
+
+/// Write `contents` to `buffer`, which holds `previous`. When both are the same
+/// size only the range that differs is uploaded, so shapes that did not
+/// change keep their part of the buffer.
+fn write_changed(buffer: &mut DynamicBuffer, device: &Device, queue: &Queue, contents: &[u8], previous: &[u8], strategy: UploadStrategy) {
+    let align = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+    if !contents.len().is_multiple_of(align) {
+        buffer.write_buffer(device, queue, contents);
+        return;
+    }
+    if contents.len() != previous.len() {
+        match contents.len() as BufferAddress <= buffer.as_ref().size() {
+            true => write(buffer.as_ref(), queue, 0, contents, strategy),
+            false => buffer.write_buffer(device, queue, contents),
+        }
+        return;
+    }
+    let Some(range) = changed_range(contents, previous) else {return;};
+    write(buffer.as_ref(), queue, range.start as BufferAddress, &contents[range], strategy);
+}
+
+/// Bytes where `contents` differs from `previous`, the same length and a
+/// multiple of [`wgpu::COPY_BUFFER_ALIGNMENT`], widened to that alignment.
+/// `None` when nothing differs.
+fn changed_range(contents: &[u8], previous: &[u8]) -> Option<Range<usize>> {
+    let align = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+    let first = contents.iter().zip(previous).position(|(a, b)| a != b)?;
+    let last = contents.iter().zip(previous).rposition(|(a, b)| a != b).unwrap();
+    Some(first / align * align..(last / align + 1) * align)
+}
+
+/// Write `contents`, a multiple of [`wgpu::COPY_BUFFER_ALIGNMENT`] long, to
+/// `buffer` at `offset` with `strategy`.
+fn write(buffer: &Buffer, queue: &Queue, offset: BufferAddress, contents: &[u8], strategy: UploadStrategy) {
+    if let (UploadStrategy::Mapped, Some(size)) = (strategy, NonZeroU64::new(contents.len() as u64)) {
+        if let Some(mut view) = queue.write_buffer_with(buffer, offset, size) {
+            view.copy_from_slice(contents);
+            return;
+        }
+    }
+    queue.write_buffer(buffer, offset, contents);
+}
+
+/// Smallest and largest depth of `vertices`, `None` if any is not finite.
+fn z_range(vertices: &[DefaultVertex]) -> Option<(f32, f32)> {
+    vertices.iter().try_fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+        v.z.is_finite().then(|| (min.min(v.z), max.max(v.z)))
+    })
+}
+
+/// Run `build`, adding the time it took to `total`. Clocks panic on the
+/// web, where nothing is added.
+fn timed<T>(total: &mut std::time::Duration, build: impl FnOnce() -> T) -> T {
+    #[cfg(not(target_arch = "wasm32"))]
+    let start = std::time::Instant::now();
+    let result = build();
+    #[cfg(not(target_arch = "wasm32"))]
+    { *total += start.elapsed(); }
+    #[cfg(target_arch = "wasm32")]
+    let _ = total;
+    result
+}
+
+/// Poll `future` on the current thread until it completes.
+#[cfg(any(feature = "test-harness", feature = "bench", all(feature = "hot-reload", debug_assertions)))]
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct Unpark(std::thread::Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+/// Pixel size of a design scaled to fit the viewport with its aspect ratio.
+fn aspect_fit(viewport_width: u32, viewport_height: u32, design_width: u32, design_height: u32) -> (f32, f32) {
+    let scale = (viewport_width as f32 / design_width as f32).min(viewport_height as f32 / design_height as f32);
+    (design_width as f32 * scale, design_height as f32 * scale)
+}
+
+/// Absolute bound of a shape, `None` for an auto bound, and the clip its
+/// ancestors apply to an auto bound.
+type ResolvedBound = (Option<ScissorRect>, Option<ScissorRect>);
+
+fn scale_vertices(vertices: &mut [DefaultVertex], scale: [f32; 2]) {
+    for v in vertices {
+        v.position = [v.position[0] * scale[0], v.position[1] * scale[1]];
+    }
+}
+
+/// `set_scissor_rect` calls needed to draw `shapes` in order.
+fn scissor_changes(shapes: &[PreparedShape]) -> usize {
+    shapes.len().min(1) + shapes.windows(2).filter(|w| w[0].bound != w[1].bound).count()
+}
+
+/// Pixels covered by `aabb`, in NDC with y up, when mapped into `area`,
+/// clamped to it and `None` when they do not overlap.
+fn pixel_rect(aabb: Aabb2D, area: ScissorRect) -> Option<ScissorRect> {
+    let (x, y, width, height) = (area.x as f32, area.y as f32, area.width as f32, area.height as f32);
+    // Float to int casts saturate, so parts left of or above the target clamp to 0.
+    let rect = ScissorRect::from_min_max(
+        [(x + (aabb.min[0] + 1.0) * 0.5 * width).floor() as u32, (y + (1.0 - aabb.max[1]) * 0.5 * height).floor() as u32],
+        [(x + (aabb.max[0] + 1.0) * 0.5 * width).ceil() as u32, (y + (1.0 - aabb.min[1]) * 0.5 * height).ceil() as u32],
+    );
+    rect.intersect(&area)
+}
+
+/// Fill the path of a [`Shape::Draw`] with the non-zero rule, which cyat
+/// cannot. Paths and attributes are passed to lyon like cyat does. A path
+/// lyon cannot fill, e.g. at a tolerance that is not positive or with more
+/// vertices than 16 bit indices reach, appends nothing.
+fn fill_non_zero(shape: &Shape<DefaultAttributes>, tolerance: f32, buffer: &mut VertexBuffers<DefaultVertex, u16>) {
+    struct Constructor;
+    impl FillVertexConstructor<DefaultVertex> for Constructor {
+        fn new_vertex(&mut self, mut vertex: FillVertex) -> DefaultVertex {
+            let position = vertex.position().to_array();
+            let a = vertex.interpolated_attributes();
+            DefaultVertex::construct(position, DefaultAttributes{color: [a[0], a[1], a[2]], z: a[3], palette: a[4].round() as u32})
+        }
+    }
+
+    let Shape::Draw(attrs, x, y, commands) = shape else {unreachable!("only paths are filled non-zero")};
+    let (vertex_count, index_count) = (buffer.vertices.len(), buffer.indices.len());
+    let values = |a: &DefaultAttributes| [a.color[0], a.color[1], a.color[2], a.z, a.palette as f32];
+    let options = FillOptions::default().with_tolerance(tolerance).with_fill_rule(lyon_tessellation::FillRule::NonZero);
+    let mut tessellator = FillTessellator::new();
+    let mut output = BuffersBuilder::new(buffer, Constructor);
+    let mut builder = tessellator.builder_with_attributes(5, &options, &mut output);
+    builder.begin(point(*x, *y), &values(attrs));
+    for command in commands {
+        match command {
+            DrawCommand::LineTo(a, x, y) => {builder.line_to(point(*x, *y), &values(a));}
+            DrawCommand::QuadraticBezierTo(a, x, y, cx, cy) => {builder.quadratic_bezier_to(point(*cx, *cy), point(*x, *y), &values(a));}
+            DrawCommand::CubicBezierTo(a, x, y, cx, cy, cx2, cy2) => {builder.cubic_bezier_to(point(*cx, *cy), point(*cx2, *cy2), point(*x, *y), &values(a));}
+        }
+    }
+    builder.close();
+    if let Err(error) = builder.build() {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(target: "wgpu_cyat", "skipping a path lyon cannot fill: {error:?}");
+        #[cfg(not(feature = "tracing"))]
+        let _ = error;
+        buffer.vertices.truncate(vertex_count);
+        buffer.indices.truncate(index_count);
+    }
+}
+
+/// `Shape` is not `Clone`, but its variants are plain data.
+fn copy_shape(shape: &Shape<DefaultAttributes>) -> Shape<DefaultAttributes> {
+    match shape {
+        Shape::Draw(attrs, x, y, commands) => Shape::Draw(*attrs, *x, *y, commands.clone()),
+        Shape::RoundedRectangle(attrs, x, y, x2, y2, rx, ry) => Shape::RoundedRectangle(*attrs, *x, *y, *x2, *y2, *rx, *ry),
+        Shape::Rectangle(attrs, x, y, x2, y2) => Shape::Rectangle(*attrs, *x, *y, *x2, *y2),
+        Shape::Ellipse(attrs, x, y, w, h) => Shape::Ellipse(*attrs, *x, *y, *w, *h),
+    }
+}
+
+/// A shape being prepared with its resolved bound, and its geometry and mask
+/// when prepare moved them out to build by value.
+struct Pending<'a> {
+    /// Position in the prepared shapes, for errors.
+    index: usize,
+    shape: &'a ShapeArea,
+    bound: ResolvedBound,
+    /// The rounded clip of the shape or its nearest ancestor with one, and
+    /// its rect unless it is the shape's own auto bound.
+    rounded_clip: Option<(Option<ScissorRect>, RoundedClip)>,
+    /// The shape's effect combined with its ancestors'.
+    effect: ColorEffect,
+    geometry: Option<Geometry>,
+    mask: Option<Geometry>,
+    /// The layer of the shape's group, see [`ShapeArea::group_opacity`].
+    group: Option<Group>,
+}
+
+/// The [`ShapeArea::rounded_clip`] applying to each shape, see [`Pending`].
+/// Expects the parents to be resolved already.
+fn resolve_rounded_clips(shapes: &[ShapeArea]) -> Vec<Option<(Option<ScissorRect>, RoundedClip)>> {
+    (0..shapes.len()).map(|i| {
+        let mut clipping = i;
+        while shapes[clipping].rounded_clip.is_none() {
+            clipping = shapes[clipping].parent?;
+        }
+        // Explicit bounds are relative to the parent's origin.
+        let rect = shapes[clipping].bound.map(|mut rect| {
+            let mut parent = shapes[clipping].parent;
+            while let Some(bound) = parent.and_then(|parent| shapes[parent].bound) {
+                rect.x = rect.x.saturating_add(bound.x);
+                rect.y = rect.y.saturating_add(bound.y);
+                parent = shapes[parent.unwrap()].parent;
+            }
+            rect
+        });
+        Some((rect, shapes[clipping].rounded_clip.unwrap()))
+    }).collect()
+}
+
+/// Each shape's [`ShapeArea::effect`] multiplied by its ancestors'. Expects
+/// the parents to be resolved already.
+fn resolve_effects(shapes: &[ShapeArea]) -> Vec<ColorEffect> {
+    (0..shapes.len()).map(|i| {
+        let mut effect = shapes[i].effect;
+        let mut parent = shapes[i].parent;
+        while let Some(p) = parent {
+            effect.saturation *= shapes[p].effect.saturation;
+            effect.brightness *= shapes[p].effect.brightness;
+            parent = shapes[p].parent;
+        }
+        effect
+    }).collect()
+}
+
+/// The [`Group`] of every shape in one, clipped to the target of `size`.
+/// Expects the parents to be resolved already.
+fn resolve_groups(shapes: &[ShapeArea], bounds: &[ResolvedBound], size: (u32, u32)) -> Result<Vec<Option<Group>>, CyatError> {
+    let target = ScissorRect::from_pos_size(0, 0, size.0, size.1);
+    (0..shapes.len()).map(|i| {
+        let mut root = i;
+        while shapes[root].group_opacity >= 1.0 {
+            let Some(parent) = shapes[root].parent else {return Ok(None);};
+            root = parent;
+        }
+        let mut ancestor = shapes[root].parent;
+        while let Some(parent) = ancestor {
+            if shapes[parent].group_opacity < 1.0 {return Err(CyatError::NestedGroup{group: root, outer: parent});}
+            ancestor = shapes[parent].parent;
+        }
+        let bound = bounds[root].0.filter(|_| shapes[root].bound.is_some()).ok_or(CyatError::UnboundGroup(root))?;
+        Ok(Some(Group{root, rect: bound.intersect(&target).unwrap_or_default(), opacity: shapes[root].group_opacity}))
+    }).collect()
+}
+
+/// Origin of an explicit bound and the part of it its ancestors leave visible.
+type Frame = ([u32; 2], Option<ScissorRect>);
+
+/// Resolve every [`ShapeArea::parent`] chain. A shape whose ancestors clip it
+/// away entirely gets an empty bound.
+fn resolve_bounds(shapes: &[ShapeArea]) -> Result<Vec<ResolvedBound>, CyatError> {
+    // Outer `None` while unresolved, inner `None` for auto bound shapes.
+    let mut frames: Vec<Option<Option<Frame>>> = vec![None; shapes.len()];
+    let mut resolved = Vec::with_capacity(shapes.len());
+    for i in 0..shapes.len() {
+        let mut chain = vec![i];
+        while let Some(parent) = shapes[*chain.last().unwrap()].parent {
+            if parent >= shapes.len() {return Err(CyatError::MissingParent{child: *chain.last().unwrap(), parent});}
+            if chain.contains(&parent) {return Err(CyatError::ParentCycle(parent));}
+            if frames[parent].is_some() {break;}
+            chain.push(parent);
+        }
+        for &j in chain.iter().rev() {
+            if frames[j].is_some() {continue;}
+            let frame = match (shapes[j].parent, shapes[j].bound) {
+                (_, None) => None,
+                (None, Some(bound)) => Some(([bound.x, bound.y], Some(bound))),
+                (Some(parent), Some(bound)) => {
+                    let (origin, clip) = frames[parent].unwrap().ok_or(CyatError::UnboundParent{child: j, parent})?;
+                    let origin = [origin[0].saturating_add(bound.x), origin[1].saturating_add(bound.y)];
+                    let rect = ScissorRect::from_pos_size(origin[0], origin[1], bound.width, bound.height);
+                    Some((origin, clip.and_then(|clip| clip.intersect(&rect))))
+                }
+            };
+            frames[j] = Some(frame);
+        }
+        resolved.push(match shapes[i].parent {
+            None => (shapes[i].bound, None),
+            Some(parent) => {
+                let (_, clip) = frames[parent].unwrap().ok_or(CyatError::UnboundParent{child: i, parent})?;
+                let clip = clip.unwrap_or_default();
+                match frames[i].unwrap() {
+                    Some((_, visible)) => (Some(visible.unwrap_or_default()), None),
+                    None => (None, Some(clip)),
+                }
+            }
+        });
+    }
+    Ok(resolved)
+}
+
+impl<B: Into<ScissorRect>> From<(ShapeBuilder<DefaultAttributes>, B)> for ShapeArea {
+    fn from((builder, bound): (ShapeBuilder<DefaultAttributes>, B)) -> Self {
+        ShapeArea::new(builder, bound)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CyatError {
+    /// Shape `child` names a `parent` index past the end of the shapes.
+    MissingParent{child: usize, parent: usize},
+    /// Following parents from this shape leads back to it.
+    ParentCycle(usize),
+    /// Shape `child` is relative to a `parent` with an auto bound, which is
+    /// not known until it is tessellated.
+    UnboundParent{child: usize, parent: usize},
+    /// Not a depth format, see [`CyatRendererDescriptor::depth_format`].
+    InvalidDepthFormat(TextureFormat),
+    /// A [`ShapeArea::pipeline`] that was never registered.
+    UnknownPipeline(String),
+    /// A constant the built-in shaders do not declare, see [`SHADER_CONSTANTS`].
+    UnknownConstant(String),
+    /// The shape at this index has a [`Geometry::Builder`] geometry or mask,
+    /// which [`CyatRenderer::prepare_ref`] cannot build by reference.
+    BorrowedBuilder(usize),
+    /// No shape at this index in the prepared frame.
+    ShapeOutOfRange(usize),
+    /// The render target's format differs from the one the pipelines were
+    /// created for, see [`CyatRenderer::validate_render_target`].
+    FormatMismatch{pipeline_format: TextureFormat, target_format: TextureFormat},
+    /// The render target's sample count differs from the pipelines'.
+    SampleCountMismatch{pipeline_count: u32, target_count: u32},
+    /// Writing the file of [`CyatRenderer::capture_next_frame`] failed.
+    #[cfg(feature = "capture")]
+    Capture(String),
+    /// Reading or compiling the shader of [`CyatRenderer::watch_shader`]
+    /// failed.
+    #[cfg(all(feature = "hot-reload", debug_assertions))]
+    ShaderReload(String),
+    /// The shape at this index has a [`DefaultAttributes::z`] that is not
+    /// finite or lies outside `0.0..=1.0`, which backends clip away.
+    InvalidDepth(usize),
+    /// A [`PathOps`] result or a prepared shape, e.g. a long
+    /// [`ShapeArea::polyline`], needs this many vertices, more than a
+    /// shape's 16 bit indices address.
+    TooManyVertices(usize),
+    /// The vertices or indices of the shape at this index need `size` bytes,
+    /// more than one buffer holds, see [`CyatRenderer::set_max_buffer_size`].
+    ShapeTooLarge{shape: usize, size: u64, limit: u64},
+    /// wgpu or the adapter cannot multisample `format` with `count` samples,
+    /// see [`CyatRendererDescriptor::msaa`].
+    UnsupportedSampleCount{count: u32, format: TextureFormat},
+    /// The shape at this index has a [`ShapeArea::group_opacity`] but an
+    /// auto bound, which the group's layer cannot be sized by.
+    UnboundGroup(usize),
+    /// The [`ShapeArea::group_opacity`] of shape `group` lies within the
+    /// group of shape `outer`, and groups do not nest.
+    NestedGroup{group: usize, outer: usize},
+    /// The shape at this index is in a [`ShapeArea::group_opacity`] group
+    /// and has a mask, gradient, sprite, rounded clip or viewport.
+    UnsupportedGroupShape(usize),
+    /// The shape at this index has a [`ShapeArea::group_opacity`], which
+    /// renderers with quantized vertices, multiview, dual source blending or
+    /// extra bind group layouts cannot draw.
+    UnsupportedGroup(usize),
+    /// The shape at this index has a [`ShapeArea::viewport`] that is empty
+    /// or not within the target set by [`CyatRenderer::resize`].
+    InvalidViewport(usize),
+    /// The shape at this index has a mask, gradient or sprite, which
+    /// renderers with [`CyatRendererDescriptor::with_quantized_vertices`]
+    /// cannot draw.
+    UnsupportedQuantized(usize),
+    /// The shape at this index has a mask, or a gradient or sprite it is
+    /// drawn with, which renderers with dual source blending or extra bind
+    /// group layouts cannot draw.
+    UnsupportedEffect(usize),
+    /// The device lacks these features, which options of the
+    /// [`CyatRendererDescriptor`] need.
+    MissingFeatures(Features),
+    /// Two options of the [`CyatRendererDescriptor`] were set that cannot
+    /// be combined.
+    IncompatibleOptions{option: &'static str, with: &'static str},
+    /// [`CyatRendererDescriptor::multiview`] asked for more than
+    /// [`MAX_VIEWS`] views.
+    TooManyViews(u32),
+    /// The shape at this index is filled by [`FillRule::NonZero`] but its
+    /// geometry or mask is a [`Geometry::Builder`], see
+    /// [`ShapeArea::with_fill_rule`].
+    UnsupportedFillRule(usize),
+    /// [`CyatRendererDescriptor::with_extra_bind_group_layout`] was given
+    /// this index, but groups 0 and 1 hold [`Globals`] and
+    /// [`ShapeUniforms`].
+    ReservedBindGroup(u32),
+    /// A buffer passed to [`CyatRenderer::bind_gpu_shapes`] lacks these
+    /// usages.
+    MissingBufferUsages{buffer: &'static str, usages: BufferUsages},
+}
+
+impl std::fmt::Display for CyatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CyatError::MissingParent{child, parent} => write!(f, "shape {child} has parent {parent}, which does not exist"),
+            CyatError::ParentCycle(shape) => write!(f, "shape {shape} is its own ancestor"),
+            CyatError::UnboundParent{child, parent} => write!(f, "shape {child} is relative to shape {parent}, which has no bound"),
+            CyatError::InvalidDepthFormat(format) => write!(f, "{format:?} is not a depth format"),
+            CyatError::UnknownPipeline(name) => write!(f, "no pipeline variant named {name:?}"),
+            CyatError::UnknownConstant(name) => write!(f, "the built-in shaders have no constant named {name:?}"),
+            CyatError::BorrowedBuilder(shape) => write!(f, "shape {shape} has a builder, which can only be prepared by value"),
+            CyatError::ShapeOutOfRange(shape) => write!(f, "no prepared shape at index {shape}"),
+            CyatError::FormatMismatch{pipeline_format, target_format} => write!(f, "pipelines render to {pipeline_format:?}, not {target_format:?}"),
+            CyatError::SampleCountMismatch{pipeline_count, target_count} => write!(f, "pipelines render with {pipeline_count} samples, not {target_count}"),
+            #[cfg(feature = "capture")]
+            CyatError::Capture(error) => write!(f, "{error}"),
+            #[cfg(all(feature = "hot-reload", debug_assertions))]
+            CyatError::ShaderReload(error) => write!(f, "{error}"),
+            CyatError::InvalidDepth(shape) => write!(f, "shape {shape} has a depth that is not finite or outside 0.0..=1.0"),
+            CyatError::TooManyVertices(count) => write!(f, "{count} vertices do not fit in one shape"),
+            CyatError::ShapeTooLarge{shape, size, limit} => write!(f, "shape {shape} needs a {size} byte buffer, more than the {limit} bytes of one"),
+            CyatError::UnsupportedSampleCount{count, format} => write!(f, "{format:?} cannot be multisampled with {count} samples"),
+            CyatError::UnboundGroup(shape) => write!(f, "shape {shape} has a group opacity, which needs a bound"),
+            CyatError::NestedGroup{group, outer} => write!(f, "group of shape {group} lies within the group of shape {outer}, and groups do not nest"),
+            CyatError::UnsupportedGroupShape(shape) => write!(f, "shape {shape} is in a group and has a mask, gradient, sprite, rounded clip or viewport"),
+            CyatError::UnsupportedGroup(shape) => write!(f, "shape {shape} has a group opacity, which this renderer cannot draw"),
+            CyatError::InvalidViewport(shape) => write!(f, "viewport of shape {shape} is empty or not within the target set by resize"),
+            CyatError::UnsupportedQuantized(shape) => write!(f, "shape {shape} has a mask, gradient or sprite, which quantized vertices do not support"),
+            CyatError::UnsupportedEffect(shape) => write!(f, "shape {shape} has a mask, gradient or sprite, which this renderer cannot draw"),
+            CyatError::MissingFeatures(features) => write!(f, "the device lacks {features:?}"),
+            CyatError::IncompatibleOptions{option, with} => write!(f, "{option} cannot be combined with {with}"),
+            CyatError::TooManyViews(views) => write!(f, "{views} views exceeds MAX_VIEWS of {MAX_VIEWS}"),
+            CyatError::UnsupportedFillRule(shape) => write!(f, "shape {shape} has a builder, which cannot be filled non-zero"),
+            CyatError::ReservedBindGroup(index) => write!(f, "bind group {index} is reserved for Globals and ShapeUniforms"),
+            CyatError::MissingBufferUsages{buffer, usages} => write!(f, "the {buffer} buffer lacks {usages:?} usage"),
+        }
+    }
+}
+
+impl std::error::Error for CyatError {}
+
+/// Counts from the last [`CyatRenderer::prepare`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    pub shapes: usize,
+    pub vertices: usize,
+    pub indices: usize,
+    /// False when the prepared frame is identical to the previous one, in
+    /// which case no buffers were written and rendering can be skipped.
+    pub changed: bool,
+    /// Shapes skipped for lying outside the cull rect or the target, see
+    /// [`CyatRenderer::set_cull_rect`].
+    pub culled: usize,
+    /// Shapes skipped for being covered by opaque shapes drawn after them,
+    /// see [`CyatRenderer::set_occlusion_culling`].
+    pub occluded: usize,
+    /// Vertices of the occluded shapes given as a [`Geometry::Mesh`]. Other
+    /// geometry is skipped before it is tessellated, so its size is unknown.
+    pub occluded_vertices: usize,
+    /// Vertex and index buffer pairs the frame was split across, see
+    /// [`CyatRenderer::set_max_buffer_size`].
+    pub chunks: usize,
+    /// `set_scissor_rect` calls [`CyatRenderer::render`] makes, one for each
+    /// shape whose bound differs from the one drawn before it.
+    pub scissor_changes: usize,
+    /// What `scissor_changes` would be without
+    /// [`CyatRenderer::set_bound_grouping`].
+    pub scissor_changes_before_grouping: usize,
+    /// `multi_draw_indexed_indirect` calls [`CyatRenderer::render_indirect`]
+    /// makes, one per run of consecutive shapes with the same state. 0 unless
+    /// prepared by [`CyatRenderer::prepare_indirect`] on a device with
+    /// [`Features::MULTI_DRAW_INDIRECT`].
+    pub indirect_draw_calls: usize,
+    /// True when the shapes were empty and the previous frame was kept, see
+    /// [`EmptyFrame::KeepPrevious`]. The other counts are the kept frame's.
+    pub stale: bool,
+    /// Microseconds spent tessellating the shapes' geometry and masks, not
+    /// counting the rest of prepare. Always 0 on the web, which has no clock
+    /// to read it from.
+    pub tessellation_time_us: u64,
+}
+
+/// What the GPU did for the draws of one [`CyatRenderer::render`], see
+/// [`CyatRenderer::set_pipeline_statistics`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PipelineStatistics {
+    /// Primitives the vertex shader output, before clipping.
+    pub clipper_invocations: u64,
+    /// Primitives left after clipping, which the rasterizer received.
+    pub clipper_primitives_out: u64,
+    /// Fragment shader runs, after the scissor test and including helper
+    /// invocations of partly covered 2x2 blocks.
+    pub fragment_shader_invocations: u64,
+}
+
+/// Vertices, indices and shapes the renderer has room for without
+/// allocating, see [`CyatRenderer::capacities`] and [`CyatRenderer::reserve`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BufferCapacities {
+    pub vertices: usize,
+    pub indices: usize,
+    pub shapes: usize,
+}
+
+/// A device limit that the renderer needs more of than is available, see
+/// [`CyatRenderer::check_limits`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LimitViolation {
+    pub limit_name: &'static str,
+    pub required: u64,
+    pub available: u64,
+}
+
+impl std::fmt::Display for LimitViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} requires {} but the device allows {}", self.limit_name, self.required, self.available)
+    }
+}
+
+/// Two prepared shapes whose bounds partly overlap, see
+/// [`CyatRenderer::check_scissor_overlaps`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ScissorOverlap {
+    pub shape_a: usize,
+    pub shape_b: usize,
+    pub overlap: ScissorRect,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DefaultAttributes {
+    pub color: [f32; 3],
+    pub z: f32,
+    /// One more than the slot of the renderer's palette the color is taken
+    /// from in place of `color`, or 0 to draw `color`, see
+    /// [`DefaultAttributes::palette`].
+    pub palette: u32,
+}
+
+impl DefaultAttributes {
+    /// Attributes taking their color from `slot` of the renderer's palette
+    /// when drawn, see [`CyatRenderer::set_palette`]. Slots wrap around at
+    /// [`PALETTE_SIZE`].
+    pub fn palette(slot: usize, z: f32) -> Self {
+        DefaultAttributes{color: [0.0; 3], z, palette: (slot % PALETTE_SIZE) as u32 + 1}
+    }
+
+    /// Attributes of the sRGB `color` at a depth of 0.0, e.g. from
+    /// [`Palette`] or [`color!`]. The alpha is dropped, as vertices have none.
+    pub fn colored(color: Color) -> Self {
+        DefaultAttributes{color: [color.r as f32, color.g as f32, color.b as f32], z: 0.0, palette: 0}
+    }
+
+    /// Attributes of an 8 bit sRGB color at a depth of 0.0.
+    pub fn from_rgb_u8(r: u8, g: u8, b: u8) -> Self {
+        DefaultAttributes{color: [r, g, b].map(|c| c as f32 / 255.0), z: 0.0, palette: 0}
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DefaultVertex {
+    position: [f32; 2],
+    color: [f32; 3],
+    z: f32,
+    palette: u32,
+}
+
+impl DefaultVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 4] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x3, 2 => Float32, 3 => Uint32];
+}
+
+impl DefaultVertex {
+    /// Layout of the renderer's vertex buffer, for building pipelines to pass
+    /// to [`CyatRenderer::render_with_pipeline`].
+    pub fn layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+impl Vertex for DefaultVertex {
+    type Attributes = DefaultAttributes;
+
+    fn construct(position: [f32; 2], attrs: Self::Attributes) -> DefaultVertex {
+        let c = srgb_to_linear;
+        DefaultVertex{
+            position,
+            color: [c(attrs.color[0]), c(attrs.color[1]), c(attrs.color[2])],
+            z: attrs.z,
+            palette: attrs.palette,
+        }
+    }
+}
+
+/// Maps vertex positions to 16 bit fixed point for [`QuantizedVertex`]:
+/// `0` is `bias` and `65535` is `bias + scale` on both axes, so a `scale`
+/// of 2.0 and `bias` of `[-1.0, -1.0]` cover the whole NDC range.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct QuantizeConfig {
+    pub scale: f32,
+    pub bias: [f32; 2],
+}
+
+/// An 8 byte vertex in place of the 28 byte [`DefaultVertex`], see
+/// [`CyatRendererDescriptor::with_quantized_vertices`]. The color is the
+/// linear RGB of the vertex with an alpha of 255, as `Unorm8x4`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct QuantizedVertex {
+    pub position: [u16; 2],
+    pub color: u32,
+}
+
+impl QuantizedVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Unorm16x2, 1 => Unorm8x4];
+
+    /// Layout of a quantized renderer's vertex buffer, for building pipelines
+    /// to pass to [`CyatRenderer::render_with_pipeline`].
+    pub fn layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    /// Positions outside the range of `config` are clamped to it, and
+    /// palette colors and depth are dropped.
+    pub fn new(vertex: &DefaultVertex, config: QuantizeConfig) -> Self {
+        let unorm = |value: f32, max: f32| (value.clamp(0.0, 1.0) * max).round() as u32;
+        let [x, y] = [0, 1].map(|i| unorm((vertex.position[i] - config.bias[i]) / config.scale, 65535.0) as u16);
+        let [r, g, b] = vertex.color.map(|c| unorm(c, 255.0));
+        QuantizedVertex{position: [x, y], color: r | g << 8 | b << 16 | 255 << 24}
+    }
+}
+
+/// The bytes of `vertices` as uploaded to a renderer's vertex buffer,
+/// quantized when `quantize` is set.
+fn vertex_bytes(vertices: &[DefaultVertex], quantize: Option<QuantizeConfig>) -> std::borrow::Cow<'_, [u8]> {
+    match quantize {
+        Some(config) => {
+            let mut bytes = Vec::with_capacity(vertices.len() * std::mem::size_of::<QuantizedVertex>());
+            for vertex in vertices {bytes.extend_from_slice(bytemuck::bytes_of(&QuantizedVertex::new(vertex, config)));}
+            bytes.into()
+        }
+        None => bytemuck::cast_slice(vertices).into(),
+    }
+}
+
+/// Decode an sRGB encoded channel in `0.0..=1.0` to linear light.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {c / 12.92} else {((c + 0.055) / 1.055).powf(2.4)}
+}
+
+/// Encode a linear light channel in `0.0..=1.0` as sRGB, the inverse of [`srgb_to_linear`].
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {c * 12.92} else {1.055 * c.powf(1.0 / 2.4) - 0.055}
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct PreparedShape {
+    start: usize,
+    end: usize,
+    vertex_start: usize,
+    vertex_end: usize,
+    bound: ScissorRect,
+    #[cfg(debug_assertions)]
+    label: Option<String>,
+    topology: Topology,
+    uniforms: ShapeUniforms,
+    mask: Option<usize>,
+    gradient: Option<usize>,
+    /// Index of the shape's texture in the frame's sprites.
+    sprite: Option<usize>,
+    /// Index into the renderer's pipeline variants.
+    variant: Option<usize>,
+    /// An overlay shape drawn without the depth test, false without depth.
+    overlay: bool,
+    /// Sorted back to front and drawn without depth writes, see
+    /// [`CyatRenderer::set_translucency_sorting`].
+    translucent: bool,
+    rounded_clip: Option<(ScissorRect, RoundedClip)>,
+    effect: ColorEffect,
+    viewport: Option<ScissorRect>,
+    /// Index of the buffers holding the shape's vertices and indices.
+    chunk: usize,
+    stencil_reference: u32,
+    user_data: u64,
+    /// Index of the group opacity layer the shape is drawn into.
+    layer: Option<usize>,
+}
+
+/// Move each shape back to just after the last one with the same bound,
+/// past shapes it can be drawn before without changing the frame, see
+/// [`CyatRenderer::set_bound_grouping`]. Shapes with the same bound keep
+/// their order. `depth_orders` when the depth buffer alone decides which
+/// opaque shape is on top.
+fn group_bounds(shapes: Vec<PreparedShape>, depth_orders: bool) -> Vec<PreparedShape> {
+    let independent = |a: &PreparedShape, b: &PreparedShape| (a.overlay, a.translucent) == (b.overlay, b.translucent) && (
+        a.bound.intersect(&b.bound).is_none() ||
+        depth_orders && !a.overlay && !a.translucent && a.variant.is_none() && b.variant.is_none()
+    );
+    let mut grouped: Vec<PreparedShape> = Vec::with_capacity(shapes.len());
+    for shape in shapes {
+        let mut position = grouped.len();
+        for (i, other) in grouped.iter().enumerate().rev() {
+            if other.bound == shape.bound {
+                position = i + 1;
+                break;
+            }
+            if !independent(other, &shape) {break;}
+        }
+        grouped.insert(position, shape);
+    }
+    grouped
+}
+
+/// Whether each of the sorted `shapes` may be visible, walking them from the
+/// top and collecting the bounds of opaque shapes. Shapes outside
+/// `cull_rect` neither cover nor get covered.
+fn occlusion(shapes: &[Pending], cull_rect: Option<ScissorRect>) -> Vec<bool> {
+    let mut visible = vec![true; shapes.len()];
+    let in_cull_rect = |bound: &ScissorRect| cull_rect.is_none_or(|rect| rect.intersect(bound).is_some());
+    let mut cover: Vec<ScissorRect> = Vec::new();
+    for (i, Pending{shape, bound: (bound, _), group, ..}) in shapes.iter().enumerate().rev() {
+        let Some(bound) = bound.filter(|bound| !bound.is_empty() && in_cull_rect(bound)) else {continue;};
+        let mut uncovered = vec![bound];
+        for rect in &cover {
+            uncovered = uncovered.iter().flat_map(|part| part.subtract(rect)).collect();
+            if uncovered.is_empty() {break;}
+        }
+        if uncovered.is_empty() {
+            visible[i] = false;
+        } else if shape.opaque && shape.mask.is_none() && shape.sprite.is_none() && group.is_none() && shape.topology == Topology::TriangleList && shape.uniforms.tint[3] >= 1.0 {
+            cover.push(bound);
+        }
+    }
+    visible
+}
+
+/// A vertex and index buffer pair holding a contiguous part of the prepared
+/// frame, see [`CyatRenderer::set_max_buffer_size`].
+struct Chunk {
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+    /// Ranges of the frame's vertices and indices in the buffers.
+    vertices: Range<usize>,
+    indices: Range<usize>,
+}
+
+impl Chunk {
+    fn new(device: &Device) -> Self {
+        Chunk{
+            vertex_buffer: DynamicBuffer::new(device, &DynamicBufferDescriptor {
+                label: None,
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            }),
+            index_buffer: DynamicBuffer::new(device, &DynamicBufferDescriptor {
+                label: None,
+                usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+            }),
+            vertices: 0..0,
+            indices: 0..0,
+        }
+    }
+}
+
+/// Bytes of the larger of the vertex and index buffers holding `vertices`
+/// of `vertex_size` bytes and `indices`.
+fn chunk_size(vertices: usize, vertex_size: usize, indices: usize) -> u64 {
+    let vertex_bytes = (vertices * vertex_size) as u64;
+    let index_bytes = (indices * std::mem::size_of::<u16>()) as u64;
+    vertex_bytes.max(index_bytes.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT))
+}
+
+/// Pipelines [`CyatRenderer::draw`] draws the prepared shapes with.
+#[derive(Copy, Clone)]
+enum DrawWith<'a> {
+    /// The renderer's pipeline for each shape.
+    Own,
+    /// One pipeline for every shape, see [`CyatRenderer::render_with_pipeline`].
+    Pipeline(&'a RenderPipeline),
+    /// The overdraw pipeline for each topology.
+    Overdraw(&'a HashMap<Topology, RenderPipeline>),
+}
+
+/// How [`CyatRenderer::draw`] splits the prepared shapes into draw calls.
+#[derive(Copy, Clone)]
+enum Runs<'a> {
+    /// One draw per shape, scissored to its bound.
+    Shapes,
+    /// One indirect draw from the buffer per run of
+    /// [`CyatRenderer::indirect_runs`].
+    Indirect(&'a Buffer),
+    /// One draw per shape and damage rect its bound intersects, scissored to
+    /// their overlap, see [`CyatRenderer::render_damaged`].
+    Damaged(&'a [ScissorRect]),
+    /// One draw per shape writing depth in the prepass, see
+    /// [`PreparedShape::in_depth_prepass`].
+    DepthPrepass,
+}
+
+/// Which of the renderer's pipelines draws a shape.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct PipelineId {
+    topology: Topology,
+    masked: bool,
+    gradient: bool,
+    sprite: bool,
+    variant: Option<usize>,
+    overlay: bool,
+    translucent: bool,
+}
+
+impl PreparedShape {
+    fn pipeline_id(&self) -> PipelineId {
+        PipelineId{topology: self.topology, masked: self.mask.is_some(), gradient: self.gradient.is_some(), sprite: self.sprite.is_some(), variant: self.variant, overlay: self.overlay, translucent: self.translucent}
+    }
+
+    /// Whether every fragment of the shape is opaque and kept, so its depth
+    /// can be written ahead in [`CyatRenderer::render_depth_prepass`].
+    fn in_depth_prepass(&self) -> bool {
+        self.topology == Topology::TriangleList && !self.overlay && !self.translucent && self.uniforms.tint[3] >= 1.0 &&
+            self.mask.is_none() && self.gradient.is_none() && self.sprite.is_none() && self.variant.is_none() &&
+            self.rounded_clip.is_none() && self.stencil_reference == 0 && self.layer.is_none()
+    }
+
+    /// Whether every piece of state [`CyatRenderer::draw`] sets for `self`
+    /// also draws `next`, so one indirect draw can cover both. Shapes with a
+    /// texture bound per shape, their own viewport or a layer are always drawn
+    /// alone.
+    fn shares_draw_state(&self, next: &PreparedShape) -> bool {
+        let alone = |shape: &PreparedShape| shape.mask.is_some() || shape.gradient.is_some() || shape.sprite.is_some() || shape.viewport.is_some() || shape.layer.is_some();
+        !alone(self) && !alone(next) &&
+            self.chunk == next.chunk &&
+            self.pipeline_id() == next.pipeline_id() &&
+            self.bound == next.bound &&
+            self.stencil_reference == next.stencil_reference &&
+            (self.uniforms, self.rounded_clip, self.effect) == (next.uniforms, next.rounded_clip, next.effect)
+    }
+}
+
+/// Everything needed to create further pipelines after construction.
+#[derive(Clone)]
+struct PipelineState {
+    shader: ShaderModule,
+    layout: PipelineLayout,
+    target: ColorTargetState,
+    multisample: MultisampleState,
+    depth_stencil: Option<DepthStencilState>,
+    multiview: Option<NonZeroU32>,
+    vertex_buffer_slot: u32,
+    vertex_entry_point: Option<String>,
+    fragment_entry_point: Option<String>,
+    cache: Option<PipelineCache>,
+    constants: HashMap<String, f64>,
+    y_axis: YAxis,
+    dual_source_blending: bool,
+    /// Bind groups in `layout`, more than 2 with extra layouts.
+    bind_group_count: u32,
+    quantize: Option<QuantizeConfig>,
+}
+
+impl PipelineState {
+    fn create(&self, device: &Device, topology: PrimitiveTopology) -> RenderPipeline {
+        self.create_with(device, topology, &self.shader.0, &self.layout, [self.vertex_entry_point.as_deref(), self.fragment_entry_point.as_deref()])
+    }
+
+    /// Like [`Self::create`] but always passing and not writing the depth test.
+    fn create_overlay(&self, device: &Device, topology: PrimitiveTopology) -> RenderPipeline {
+        let depth_stencil = self.depth_stencil.clone().map(|depth| DepthStencilState{
+            depth_write_enabled: false,
+            depth_compare: CompareFunction::Always,
+            ..depth
+        });
+        PipelineState{depth_stencil, ..self.clone()}.create(device, topology)
+    }
+
+    /// Like [`Self::create`] but testing without writing depth.
+    fn create_translucent(&self, device: &Device, topology: PrimitiveTopology) -> RenderPipeline {
+        let depth_stencil = self.depth_stencil.clone().map(|depth| DepthStencilState{depth_write_enabled: false, ..depth});
+        PipelineState{depth_stencil, ..self.clone()}.create(device, topology)
+    }
+
+    /// A pipeline drawing every fragment as [`OVERDRAW_STEP`] added to the
+    /// target, with the built-in `shader` and the depth test of
+    /// [`Self::create_overlay`] so hidden fragments count too.
+    fn create_overdraw(&self, device: &Device, topology: PrimitiveTopology, shader: &wgpu::ShaderModule) -> RenderPipeline {
+        let add = BlendComponent{src_factor: BlendFactor::One, dst_factor: BlendFactor::One, operation: BlendOperation::Add};
+        let depth_stencil = self.depth_stencil.clone().map(|depth| DepthStencilState{
+            depth_write_enabled: false,
+            depth_compare: CompareFunction::Always,
+            ..depth
+        });
+        let state = PipelineState{
+            target: ColorTargetState{blend: Some(BlendState{color: add, alpha: add}), ..self.target.clone()},
+            depth_stencil,
+            constants: self.constants.iter().filter(|(name, _)| *name == "Y_DOWN" || name.starts_with("QUANTIZE_")).map(|(name, value)| (name.clone(), *value)).collect(),
+            ..self.clone()
+        };
+        let vertex_entry_point = if self.quantize.is_some() {"vs_quantized"} else {"vs_main"};
+        state.create_with(device, topology, shader, &self.layout, [Some(vertex_entry_point), Some("fs_overdraw")])
+    }
+
+    /// A pipeline writing the stencil reference to every sample of the
+    /// target and nothing else, `None` without a stencil aspect to write.
+    fn create_stencil_clear(&self, device: &Device) -> Option<RenderPipeline> {
+        let format = self.depth_stencil.as_ref().map(|depth| depth.format).filter(TextureFormat::has_stencil_aspect)?;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor{
+            label: Some("stencil_clear.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("stencil_clear.wgsl").into()),
+        });
+        let replace = StencilFaceState{compare: CompareFunction::Always, fail_op: StencilOperation::Replace, depth_fail_op: StencilOperation::Replace, pass_op: StencilOperation::Replace};
+        Some(device.create_render_pipeline(&RenderPipelineDescriptor{
+            label: Some("wgpu_cyat::stencil_clear"),
+            layout: None,
+            vertex: VertexState{module: &shader, entry_point: Some("vs_main"), compilation_options: PipelineCompilationOptions::default(), buffers: &[]},
+            fragment: Some(FragmentState{
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[Some(ColorTargetState{blend: None, write_mask: ColorWrites::empty(), ..self.target.clone()})],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: Some(DepthStencilState{
+                format,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState{front: replace, back: replace, read_mask: !0, write_mask: !0},
+                bias: DepthBiasState::default(),
+            }),
+            multisample: self.multisample,
+            multiview: self.multiview,
+            cache: self.cache.as_ref(),
+        }))
+    }
+
+    /// A triangle pipeline writing only depth, without a fragment stage, for
+    /// a pass without color attachments. `None` without depth to write.
+    fn create_depth_prepass(&self, device: &Device) -> Option<RenderPipeline> {
+        let depth = self.depth_stencil.as_ref()?;
+        // The main pass tests equal depth again, which the prepass writes with its nearest.
+        let depth_compare = match depth.depth_compare {
+            CompareFunction::Equal => CompareFunction::LessEqual,
+            compare => compare,
+        };
+        let compilation_options = PipelineCompilationOptions{constants: &self.constants, ..PipelineCompilationOptions::default()};
+        Some(device.create_render_pipeline(&RenderPipelineDescriptor{
+            label: Some("wgpu_cyat::depth_prepass"),
+            layout: Some(&self.layout),
+            vertex: VertexState{
+                module: &self.shader.0,
+                entry_point: self.vertex_entry_point.as_deref(),
+                compilation_options,
+                buffers: &self.vertex_buffers(&[]),
+            },
+            fragment: None,
+            primitive: PrimitiveState{
+                topology: PrimitiveTopology::TriangleList,
+                front_face: if self.y_axis == YAxis::Down {FrontFace::Cw} else {FrontFace::Ccw},
+                ..PrimitiveState::default()
+            },
+            depth_stencil: Some(DepthStencilState{
+                depth_write_enabled: true,
+                depth_compare,
+                stencil: StencilState::default(),
+                ..depth.clone()
+            }),
+            multisample: self.multisample,
+            multiview: self.multiview,
+            cache: self.cache.as_ref(),
+        }))
+    }
+
+    /// Layouts of the renderer's vertex buffer at its slot, followed by
+    /// `extra_buffers`.
+    fn vertex_buffers<'a>(&self, extra_buffers: &[VertexBufferLayout<'a>]) -> Vec<VertexBufferLayout<'a>> {
+        let mut buffers = vec![VertexBufferLayout{array_stride: 0, step_mode: VertexStepMode::Vertex, attributes: &[]}; self.vertex_buffer_slot as usize];
+        buffers.push(if self.quantize.is_some() {QuantizedVertex::layout()} else {DefaultVertex::layout()});
+        buffers.extend_from_slice(extra_buffers);
+        buffers
+    }
+
+    /// A pipeline for the same target with another shader and layout.
+    fn create_with(
+        &self,
+        device: &Device,
+        topology: PrimitiveTopology,
+        shader: &wgpu::ShaderModule,
+        layout: &PipelineLayout,
+        entry_points: [Option<&str>; 2],
+    ) -> RenderPipeline {
+        self.create_with_buffers(device, topology, shader, layout, entry_points, &[])
+    }
+
+    /// Like [`Self::create_with`], reading `extra_buffers` from the slots
+    /// after the renderer's vertex buffer.
+    fn create_with_buffers(
+        &self,
+        device: &Device,
+        topology: PrimitiveTopology,
+        shader: &wgpu::ShaderModule,
+        layout: &PipelineLayout,
+        [vertex_entry_point, fragment_entry_point]: [Option<&str>; 2],
+        extra_buffers: &[VertexBufferLayout<'_>],
+    ) -> RenderPipeline {
+        let buffers = self.vertex_buffers(extra_buffers);
+        let compilation_options = PipelineCompilationOptions{constants: &self.constants, ..PipelineCompilationOptions::default()};
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: vertex_entry_point,
+                compilation_options: compilation_options.clone(),
+                buffers: &buffers
+            },
+            fragment: Some(FragmentState {
+                module: shader,
+                entry_point: fragment_entry_point,
+                compilation_options,
+                targets: &[Some(self.target.clone())],
+            }),
+            // Negating y mirrors triangles, so front faces wind the other way.
+            primitive: PrimitiveState{
+                topology,
+                front_face: if self.y_axis == YAxis::Down {FrontFace::Cw} else {FrontFace::Ccw},
+                ..PrimitiveState::default()
+            },
+            depth_stencil: self.depth_stencil.clone(),
+            multisample: self.multisample,
+            multiview: self.multiview,
+            cache: self.cache.as_ref()
+        })
+    }
+}
+
+impl PipelineState {
+    /// State for a pipeline variant, sharing the shader and layout unless
+    /// the descriptor replaces them.
+    fn variant(&self, descriptor: &PipelineVariantDescriptor) -> PipelineState {
+        let mut state = self.clone();
+        if let Some(blend) = descriptor.blend {state.target.blend = Some(blend);}
+        if let Some(write_mask) = descriptor.write_mask {state.target.write_mask = write_mask;}
+        if let Some(depth_stencil) = &descriptor.depth_stencil {state.depth_stencil = Some(depth_stencil.clone());}
+        if let Some(shader) = &descriptor.shader_module {state.shader = shader.clone();}
+        if let Some(entry_point) = &descriptor.vertex_entry_point {state.vertex_entry_point = Some(entry_point.clone());}
+        if let Some(entry_point) = &descriptor.fragment_entry_point {state.fragment_entry_point = Some(entry_point.clone());}
+        state
+    }
+}
+
+/// A named pipeline registered with [`CyatRenderer::register_pipeline`],
+/// with one pipeline per topology its shapes used.
+struct PipelineVariant {
+    name: String,
+    /// Kept to rebuild the variant with the renderer's pipelines.
+    descriptor: PipelineVariantDescriptor,
+    state: PipelineState,
+    pipelines: HashMap<Topology, RenderPipeline>,
+}
+
+/// Every pipeline a renderer created so far, built again from a changed
+/// [`PipelineState`] before replacing the current ones.
+struct Pipelines {
+    render: RenderPipeline,
+    line: Option<RenderPipeline>,
+    point: Option<RenderPipeline>,
+    overlay: HashMap<Topology, RenderPipeline>,
+    translucent: HashMap<Topology, RenderPipeline>,
+    variants: Vec<(PipelineState, HashMap<Topology, RenderPipeline>)>,
+    stencil_clear: Option<RenderPipeline>,
+    depth_prepass: Option<RenderPipeline>,
+}
+
+/// Blends by the fragment shader's second output per channel, for
+/// subpixel coverage: `color * coverage + dst * (1 - coverage)`.
+pub const DUAL_SOURCE_BLEND: BlendState = BlendState{
+    color: BlendComponent{src_factor: BlendFactor::Src1, dst_factor: BlendFactor::OneMinusSrc1, operation: BlendOperation::Add},
+    alpha: BlendComponent{src_factor: BlendFactor::Src1Alpha, dst_factor: BlendFactor::OneMinusSrc1Alpha, operation: BlendOperation::Add},
+};
+
+/// A compiled shader that several renderers can share; pipelines keep it
+/// alive for as long as they need it.
+#[derive(Clone, Debug)]
+pub struct ShaderModule(pub Arc<wgpu::ShaderModule>);
+
+/// Options for [`CyatRenderer::from_descriptor`].
+#[derive(Clone, Debug)]
+pub struct CyatRendererDescriptor {
+    pub texture_format: TextureFormat,
+    pub multisample: MultisampleState,
+    pub depth_stencil: Option<DepthStencilState>,
+    /// `None` replaces the target color. The built-in shader always writes an
+    /// alpha of 1.0, so blending needs a custom shader to be visible.
+    pub blend: Option<BlendState>,
+    pub write_mask: ColorWrites,
+    /// Draw every shape to this many array layers at once, each transformed by
+    /// its own matrix from [`CyatRenderer::set_eye_projections`]. Needs
+    /// [`Features::MULTIVIEW`] and at most [`MAX_VIEWS`] views.
+    pub multiview: Option<NonZeroU32>,
+    /// Vertex buffer slot the renderer binds its vertices to. Lower slots are
+    /// left to the caller, who must have bound them before rendering.
+    pub vertex_buffer_slot: u32,
+    /// Write a second, per-channel coverage output from the fragment shader
+    /// and blend with it, as [`DUAL_SOURCE_BLEND`] unless `blend` is set. Needs
+    /// [`Features::DUAL_SOURCE_BLENDING`] and cannot be combined with
+    /// `multiview`.
+    pub dual_source_blending: bool,
+    /// Shader entry points, `vs_main` and `fs_main` by default. `None` lets
+    /// wgpu pick the only entry point of that stage, which fails when the
+    /// shader has several.
+    pub vertex_entry_point: Option<String>,
+    pub fragment_entry_point: Option<String>,
+    /// Cache used to create every pipeline of the renderer, see
+    /// [`CyatRenderer::required_pipeline_cache_data`].
+    pub pipeline_cache: Option<PipelineCache>,
+    /// Shapes to allocate room for up front, see [`CyatRenderer::reserve_shapes`].
+    pub expected_shape_count: usize,
+    /// Use this module instead of compiling the built-in shader, see
+    /// [`CyatRenderer::create_shader_module`].
+    pub shader_module: Option<ShaderModule>,
+    /// Layouts added to the pipeline layout at their bind group index, see
+    /// [`CyatRendererDescriptor::with_extra_bind_group_layout`].
+    pub extra_bind_group_layouts: Vec<(u32, BindGroupLayout)>,
+    /// Values of the shader's `override` declarations by name, see
+    /// [`CyatRendererDescriptor::with_constant`].
+    pub constants: HashMap<String, f64>,
+    /// Convention of the shapes' vertex positions. [`YAxis::Down`] negates y
+    /// in the vertex shader through the `Y_DOWN` constant, which a custom
+    /// shader must then declare. Scissor rects and bounds stay in pixels
+    /// from the top left of the target either way.
+    pub y_axis: YAxis,
+    /// Upload [`QuantizedVertex`] in place of [`DefaultVertex`], see
+    /// [`CyatRendererDescriptor::with_quantized_vertices`].
+    pub quantize: Option<QuantizeConfig>,
+    /// See [`CyatRenderer::set_empty_frame`].
+    pub empty_frame: EmptyFrame,
+    /// Prefer presenting with the least latency, see
+    /// [`CyatRenderer::recommended_present_mode`].
+    pub low_latency: bool,
+}
+
+impl CyatRendererDescriptor {
+    /// No multisampling, depth or blending, writing all channels.
+    pub fn new(texture_format: TextureFormat) -> Self {
+        CyatRendererDescriptor{
+            texture_format,
+            multisample: MultisampleState::default(),
+            depth_stencil: None,
+            blend: None,
+            write_mask: ColorWrites::ALL,
+            multiview: None,
+            vertex_buffer_slot: 0,
+            dual_source_blending: false,
+            vertex_entry_point: Some("vs_main".to_string()),
+            fragment_entry_point: Some("fs_main".to_string()),
+            pipeline_cache: None,
+            expected_shape_count: 0,
+            shader_module: None,
+            extra_bind_group_layouts: Vec::new(),
+            constants: HashMap::new(),
+            y_axis: YAxis::Up,
+            quantize: None,
+            empty_frame: EmptyFrame::Clear,
+            low_latency: false,
+        }
+    }
+
+    /// Upload each vertex as an 8 byte [`QuantizedVertex`] instead of a 28
+    /// byte [`DefaultVertex`], under a third of the bandwidth, mapping
+    /// positions through `scale` and `bias` as in [`QuantizeConfig`]. The
+    /// built-in shader reconstructs them in `vs_quantized` from the
+    /// `QUANTIZE_SCALE`, `QUANTIZE_BIAS_X` and `QUANTIZE_BIAS_Y` constants,
+    /// which a custom shader must declare instead.
+    ///
+    /// Depth is dropped, so every shape is drawn at a depth of 0.0, and
+    /// colors are kept in 8 bits of linear light, which bands in dark
+    /// gradients. Palette colors, masks, gradients, sprites, multiview and
+    /// dual source blending are not supported, and prepare fails with
+    /// [`CyatError::UnsupportedQuantized`] for a shape with a mask, gradient
+    /// or sprite.
+    pub fn with_quantized_vertices(mut self, scale: f32, bias: [f32; 2]) -> Self {
+        self.quantize = Some(QuantizeConfig{scale, bias});
+        self
+    }
+
+    /// Specialize the shader by setting its `override` constant `name` to
+    /// `value` when the pipelines are compiled, cheaper than branching on a
+    /// uniform. Fails for names not in [`SHADER_CONSTANTS`] unless a custom
+    /// [`Self::shader_module`] was set first, whose constants wgpu validates
+    /// when the pipelines are created.
+    ///
+    /// Pipeline variants, gradients and masked shapes are compiled with the
+    /// same constants, so shaders replacing them must declare them too.
+    pub fn with_constant(mut self, name: impl Into<String>, value: f64) -> Result<Self, CyatError> {
+        let name = name.into();
+        if self.shader_module.is_none() && !SHADER_CONSTANTS.contains(&name.as_str()) {
+            return Err(CyatError::UnknownConstant(name));
+        }
+        self.constants.insert(name, value);
+        Ok(self)
+    }
+
+    /// Give a custom shader access to application data through another bind
+    /// group at `index`, set with [`CyatRenderer::set_extra_bind_group`].
+    /// Groups 0 and 1 are reserved for [`Globals`] and [`ShapeUniforms`], so
+    /// [`CyatRenderer::from_descriptor`] fails with
+    /// [`CyatError::ReservedBindGroup`] for them, and indices between them
+    /// and `index` left unused get an empty layout.
+    ///
+    /// Masks, gradients and sprites cannot be combined with extra layouts,
+    /// since they bind to group 2, and prepare fails with
+    /// [`CyatError::UnsupportedEffect`] for shapes drawn with them.
+    pub fn with_extra_bind_group_layout(mut self, layout: BindGroupLayout, index: u32) -> Self {
+        self.extra_bind_group_layouts.push((index, layout));
+        self
+    }
+
+    /// Multisample with `sample_count` samples, validated against what wgpu
+    /// allows, 1, 2, 4 or 8, and what `adapter` supports for the texture
+    /// format and any depth format set before. The render target and depth
+    /// buffer must have the same count, see [`CyatRenderer::sample_count`].
+    pub fn msaa(mut self, adapter: &Adapter, sample_count: u32) -> Result<Self, CyatError> {
+        let formats = std::iter::once(self.texture_format).chain(self.depth_stencil.as_ref().map(|depth| depth.format));
+        for format in formats {
+            if !matches!(sample_count, 1 | 2 | 4 | 8) || !adapter.get_texture_format_features(format).flags.sample_count_supported(sample_count) {
+                return Err(CyatError::UnsupportedSampleCount{count: sample_count, format});
+            }
+        }
+        self.multisample = MultisampleState{count: sample_count, mask: !0, alpha_to_coverage_enabled: false};
+        Ok(self)
+    }
+
+    /// Depth test against a buffer of `format`, keeping fragments with a
+    /// smaller z. Fails for formats without a depth aspect.
+    pub fn depth_format(mut self, format: TextureFormat) -> Result<Self, CyatError> {
+        match format {
+            TextureFormat::Depth16Unorm |
+            TextureFormat::Depth24Plus |
+            TextureFormat::Depth24PlusStencil8 |
+            TextureFormat::Depth32Float |
+            TextureFormat::Depth32FloatStencil8 => {},
+            format => return Err(CyatError::InvalidDepthFormat(format)),
+        }
+        self.depth_stencil = Some(DepthStencilState{
+            format,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::Less,
+            stencil: StencilState::default(),
+            bias: DepthBiasState::default(),
+        });
+        Ok(self)
+    }
+
+    /// Test depth as `LessEqual` without writing it, for drawing after
+    /// [`CyatRenderer::render_depth_prepass`] wrote the depth of the opaque
+    /// shapes. Call it after [`Self::depth_format`], without which it does
+    /// nothing.
+    pub fn with_depth_prepass(mut self) -> Self {
+        if let Some(depth) = &mut self.depth_stencil {
+            depth.depth_compare = CompareFunction::LessEqual;
+            depth.depth_write_enabled = false;
+        }
+        self
+    }
+}
+
+/// Options of a pipeline variant, see [`CyatRenderer::register_pipeline`].
+/// Every `None` keeps the renderer's own setting.
+#[derive(Clone, Debug, Default)]
+pub struct PipelineVariantDescriptor {
+    pub blend: Option<BlendState>,
+    pub write_mask: Option<ColorWrites>,
+    /// Must use the renderer's depth format, if it has one.
+    pub depth_stencil: Option<DepthStencilState>,
+    /// Needs the same bind group layouts as the renderer's shader.
+    pub shader_module: Option<ShaderModule>,
+    pub vertex_entry_point: Option<String>,
+    pub fragment_entry_point: Option<String>,
+}
+
+/// Column major 4x4 matrix, as laid out by WGSL `mat4x4<f32>`.
+pub type Projection = [[f32; 4]; 4];
+
+const IDENTITY: Projection = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+/// Values shared by every draw, readable by shaders as a uniform at
+/// `@group(0) @binding(0)`. The built-in shaders ignore them. The uniform
+/// continues at byte 16 with the `vec2<f32>` range of
+/// [`CyatRenderer::set_depth_range`], which custom shaders can map their
+/// depth into or leave out of their struct.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Globals {
+    /// Elapsed time in seconds, see [`CyatRenderer::set_time`].
+    pub time: f32,
+    /// Free for custom shaders, see [`CyatRenderer::set_user_globals`].
+    pub user: [f32; 3],
+}
+
+/// [`Globals`] followed by the range of [`CyatRenderer::set_depth_range`],
+/// as the built-in shaders read them.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GlobalData {
+    globals: Globals,
+    depth_range: [f32; 2],
+    _padding: [f32; 2],
+}
+
+/// Per-shape values that can change every frame without tessellating again,
+/// readable by shaders as a uniform at `@group(1) @binding(0)`. The uniform
+/// continues with values only the built-in shaders use, which custom shaders
+/// can leave out of their struct.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShapeUniforms {
+    /// Multiplies the vertex color; the built-in shaders write its alpha as
+    /// the opacity, which shows with a [`CyatRendererDescriptor::blend`].
+    pub tint: [f32; 4],
+    /// Free for custom shaders, e.g. a corner radius.
+    pub user: [f32; 4],
+}
+
+impl Default for ShapeUniforms {
+    fn default() -> Self {
+        ShapeUniforms{tint: [1.0; 4], user: [0.0; 4]}
+    }
+}
+
+/// [`ShapeUniforms`] followed by the values the built-in shaders need.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShapeData {
+    uniforms: ShapeUniforms,
+    /// Left, top, right and bottom of the rounded clip in pixels.
+    clip_rect: [f32; 4],
+    clip_radii: [f32; 4],
+    /// Width in pixels of the clip's edge, 0 for a hard edge.
+    clip_fade: f32,
+    saturation: f32,
+    brightness: f32,
+    _padding: f32,
+}
+
+impl ShapeData {
+    /// A rect far outside any target for shapes without a rounded clip.
+    const NO_CLIP: [f32; 4] = [-1.0e6, -1.0e6, 1.0e6, 1.0e6];
+
+    fn new(uniforms: ShapeUniforms, rounded_clip: Option<(ScissorRect, RoundedClip)>, effect: ColorEffect) -> Self {
+        let (clip_rect, clip_radii, clip_fade) = match rounded_clip {
+            Some((rect, clip)) => {
+                let [x, y] = [rect.x as f32, rect.y as f32];
+                ([x, y, x + rect.width as f32, y + rect.height as f32], clip.radii, if clip.antialias {1.0} else {0.0})
+            }
+            None => (Self::NO_CLIP, [0.0; 4], 1.0),
+        };
+        ShapeData{uniforms, clip_rect, clip_radii, clip_fade, saturation: effect.saturation, brightness: effect.brightness, _padding: 0.0}
+    }
+}
+
+/// One [`ShapeData`] per prepared shape, each at a multiple of `stride` and
+/// selected with a dynamic offset before its draw.
+struct ShapeUniformBuffer {
+    layout: BindGroupLayout,
+    buffer: Buffer,
+    bind_group: BindGroup,
+    stride: u64,
+    staging: Vec<u8>,
+}
+
+impl ShapeUniformBuffer {
+    const SIZE: u64 = std::mem::size_of::<ShapeData>() as u64;
+
+    fn new(device: &Device) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[BindGroupLayoutEntry{
+                binding: 0,
+                visibility: ShaderStages::VERTEX_FRAGMENT,
+                ty: BindingType::Buffer{ty: BufferBindingType::Uniform, has_dynamic_offset: true, min_binding_size: NonZeroU64::new(Self::SIZE)},
+                count: None,
+            }],
+        });
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let stride = Self::SIZE.div_ceil(alignment) * alignment;
+        let (buffer, bind_group) = Self::create(device, &layout, stride);
+        ShapeUniformBuffer{layout, buffer, bind_group, stride, staging: Vec::new()}
+    }
+
+    fn create(device: &Device, layout: &BindGroupLayout, size: u64) -> (Buffer, BindGroup) {
+        let buffer = device.create_buffer(&BufferDescriptor{
+            label: None,
+            size,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor{
+            label: None,
+            layout,
+            entries: &[BindGroupEntry{binding: 0, resource: BindingResource::Buffer(BufferBinding{
+                buffer: &buffer,
+                offset: 0,
+                size: NonZeroU64::new(Self::SIZE),
+            })}],
+        });
+        (buffer, bind_group)
+    }
+
+    /// Write the uniforms of `shapes` followed by a default entry for draws
+    /// that are not a shape, like the bounds overlay.
+    fn write(&mut self, device: &Device, queue: &Queue, shapes: &[PreparedShape]) {
+        self.staging.clear();
+        self.staging.resize((shapes.len() + 1) * self.stride as usize, 0);
+        let data = shapes.iter().map(|shape| ShapeData::new(shape.uniforms, shape.rounded_clip, shape.effect))
+            .chain([ShapeData::new(ShapeUniforms::default(), None, ColorEffect::default())]);
+        for (data, slot) in data.zip(self.staging.chunks_exact_mut(self.stride as usize)) {
+            slot[..Self::SIZE as usize].copy_from_slice(bytemuck::bytes_of(&data));
+        }
+        if self.staging.len() as u64 > self.buffer.size() {
+            (self.buffer, self.bind_group) = Self::create(device, &self.layout, (self.staging.len() as u64).next_power_of_two());
+        }
+        queue.write_buffer(&self.buffer, 0, &self.staging);
+    }
+
+    /// Grow the buffer to hold `shapes` and the default entry. Its contents
+    /// are lost, so [`Self::write`] has to follow.
+    fn reserve(&mut self, device: &Device, shapes: usize) {
+        let size = (shapes as u64 + 1) * self.stride;
+        if size > self.buffer.size() {
+            (self.buffer, self.bind_group) = Self::create(device, &self.layout, size.next_power_of_two());
+        }
+    }
+
+    fn offset(&self, shape_index: usize) -> u32 {
+        (shape_index as u64 * self.stride) as u32
+    }
+}
+
+/// Buffers drawn in place of the prepared frame, see
+/// [`CyatRenderer::bind_gpu_shapes`].
+struct GpuShapes {
+    vertex_buffer: Arc<Buffer>,
+    index_buffer: Arc<Buffer>,
+    index_count: u32,
+    /// Default [`ShapeData`] for bind group 1, as the frame's own entries
+    /// are left to the last prepare.
+    bind_group: BindGroup,
+}
+
+/// Bind group 0: [`Globals`] at binding 0, for multiview the per-view
+/// matrices at binding 1, the palette at binding 2 and with the
+/// `color_grading` feature the color LUT at bindings 3 and 4.
+struct Uniforms {
+    layout: BindGroupLayout,
+    globals: Buffer,
+    projections: Option<Buffer>,
+    palette: Buffer,
+    #[cfg(feature = "color_grading")]
+    color_lut: ColorLut,
+    bind_group: BindGroup,
+}
+
+impl Uniforms {
+    fn buffer_entries<'a>(globals: &'a Buffer, projections: Option<&'a Buffer>, palette: &'a Buffer) -> Vec<BindGroupEntry<'a>> {
+        let mut entries = vec![BindGroupEntry{binding: 0, resource: globals.as_entire_binding()}];
+        if let Some(projections) = projections {entries.push(BindGroupEntry{binding: 1, resource: projections.as_entire_binding()});}
+        entries.push(BindGroupEntry{binding: 2, resource: palette.as_entire_binding()});
+        entries
+    }
+}
+
+/// Amount every channel of a pixel grows by per fragment drawn to it in
+/// [`CyatRenderer::render_overdraw`], so 16 layers saturate.
+pub const OVERDRAW_STEP: f32 = 1.0 / 16.0;
+
+/// Pipelines of [`CyatRenderer::render_overdraw`] for the topologies drawn so
+/// far, see [`CyatRenderer::set_overdraw_visualization`].
+struct Overdraw {
+    shader: wgpu::ShaderModule,
+    pipelines: HashMap<Topology, RenderPipeline>,
+}
+
+/// Query of [`CyatRenderer::render`]'s draws and the buffers reading it back,
+/// see [`CyatRenderer::set_pipeline_statistics`].
+struct Statistics {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readback: Buffer,
+    /// Set by render once the query is written, so an unwritten one is
+    /// never resolved.
+    written: AtomicBool,
+    /// Whether `readback` is being mapped, and set once it is.
+    pending: bool,
+    mapped: Arc<AtomicBool>,
+    last: Option<PipelineStatistics>,
+}
+
+impl Statistics {
+    const TYPES: PipelineStatisticsTypes = PipelineStatisticsTypes::CLIPPER_INVOCATIONS
+        .union(PipelineStatisticsTypes::CLIPPER_PRIMITIVES_OUT)
+        .union(PipelineStatisticsTypes::FRAGMENT_SHADER_INVOCATIONS);
+    /// One `u64` per type.
+    const SIZE: u64 = 3 * std::mem::size_of::<u64>() as u64;
+
+    fn new(device: &Device) -> Self {
+        let buffer = |usage| device.create_buffer(&BufferDescriptor{label: None, size: Self::SIZE, usage, mapped_at_creation: false});
+        Statistics{
+            query_set: device.create_query_set(&QuerySetDescriptor{label: Some("wgpu_cyat::statistics"), ty: QueryType::PipelineStatistics(Self::TYPES), count: 1}),
+            resolve_buffer: buffer(BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC),
+            readback: buffer(BufferUsages::COPY_DST | BufferUsages::MAP_READ),
+            written: AtomicBool::new(false),
+            pending: false,
+            mapped: Arc::new(AtomicBool::new(false)),
+            last: None,
+        }
+    }
+}
+
+/// Line outlines of the prepared bounds, see [`CyatRenderer::set_debug_bounds`].
+struct BoundsOverlay {
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+    buffers: VertexBuffers<DefaultVertex, u16>,
+}
+
+impl BoundsOverlay {
+    fn new(device: &Device) -> Self {
+        BoundsOverlay{
+            vertex_buffer: DynamicBuffer::new(device, &DynamicBufferDescriptor {
+                label: None,
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            }),
+            index_buffer: DynamicBuffer::new(device, &DynamicBufferDescriptor {
+                label: None,
+                usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+            }),
+            buffers: VertexBuffers::new(),
+        }
+    }
+
+    fn write(&mut self, device: &Device, queue: &Queue, shapes: &[PreparedShape], size: (u32, u32), y_axis: YAxis, quantize: Option<QuantizeConfig>) {
+        self.buffers.clear();
+        let (width, height) = (size.0 as f32, size.1 as f32);
+        for (i, shape) in shapes.iter().enumerate() {
+            // Spread hues by the golden angle so neighbouring indices differ.
+            let hue = (i as f32 * 0.618_034).fract() * 6.0;
+            let x = 1.0 - (hue % 2.0 - 1.0).abs();
+            let color = match hue as u32 {
+                0 => [1.0, x, 0.0], 1 => [x, 1.0, 0.0], 2 => [0.0, 1.0, x],
+                3 => [0.0, x, 1.0], 4 => [x, 0.0, 1.0], _ => [1.0, 0.0, x],
+            };
+            let attrs = DefaultAttributes{color, z: 0.0, palette: 0};
+
+            // Through the centers of the outermost pixels.
+            let max = shape.bound.max();
+            let (x0, x1) = ((shape.bound.x as f32 + 0.5) / width * 2.0 - 1.0, (max[0] as f32 - 0.5) / width * 2.0 - 1.0);
+            let (mut y0, mut y1) = (1.0 - (shape.bound.y as f32 + 0.5) / height * 2.0, 1.0 - (max[1] as f32 - 0.5) / height * 2.0);
+            if y_axis == YAxis::Down {(y0, y1) = (-y0, -y1);}
+            let base = self.buffers.vertices.len() as u16;
+            self.buffers.vertices.extend([[x0, y0], [x1, y0], [x1, y1], [x0, y1]].map(|p| DefaultVertex::construct(p, attrs)));
+            self.buffers.indices.extend([0, 1, 1, 2, 2, 3, 3, 0].map(|i| base + i));
+        }
+        if self.buffers.vertices.is_empty() {return;}
+        self.vertex_buffer.write_buffer(device, queue, &vertex_bytes(&self.buffers.vertices, quantize));
+        self.index_buffer.write_buffer(device, queue, bytemuck::cast_slice(&self.buffers.indices));
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>, renderer: &CyatRenderer, uniforms_offset: u32) {
+        if self.buffers.indices.is_empty() {return;}
+        let (width, height) = renderer.size;
+        render_pass.set_pipeline(renderer.pipeline(Topology::LineList, false, false));
+        render_pass.set_bind_group(0, &renderer.uniforms.bind_group, &[]);
+        render_pass.set_bind_group(1, &renderer.shape_uniforms.bind_group, &[uniforms_offset]);
+        render_pass.set_vertex_buffer(renderer.pipeline_state.vertex_buffer_slot, self.vertex_buffer.as_ref().slice(..));
+        render_pass.set_index_buffer(self.index_buffer.as_ref().slice(..), IndexFormat::Uint16);
+        render_pass.set_scissor_rect(0, 0, width, height);
+        render_pass.draw_indexed(0..self.buffers.indices.len() as u32, 0, 0..1);
+    }
+}
+
+pub struct CyatRenderer {
+    pipeline_state: PipelineState,
+    render_pipeline: RenderPipeline,
+    uniforms: Uniforms,
+    shape_uniforms: ShapeUniformBuffer,
+    masks: Option<Masks>,
+    gradients: Option<Gradients>,
+    sprites: Option<Sprites>,
+    /// Created by the first shape with a [`ShapeArea::group_opacity`].
+    layers: Option<Layers>,
+    line_pipeline: Option<RenderPipeline>,
+    point_pipeline: Option<RenderPipeline>,
+    /// Pipelines without depth test for overlay shapes, only created when
+    /// there is a depth buffer.
+    overlay_pipelines: HashMap<Topology, RenderPipeline>,
+    /// Pipelines without depth writes for translucent shapes, only created
+    /// when sorting them.
+    translucent_pipelines: HashMap<Topology, RenderPipeline>,
+    /// See [`Self::clear_stencil`], only created with a stencil aspect.
+    stencil_clear_pipeline: Option<RenderPipeline>,
+    /// See [`Self::depth_prepass_pipeline`], only created with depth.
+    depth_prepass_pipeline: Option<RenderPipeline>,
+    variants: Vec<PipelineVariant>,
+    /// Never empty, the first holds the whole frame unless it is split.
+    chunks: Vec<Chunk>,
+    max_buffer_size: Option<u64>,
+    cyat_buffers: VertexBuffers<DefaultVertex, u16>,
+    shape_buffer: Vec<PreparedShape>,
+    previous_buffers: VertexBuffers<DefaultVertex, u16>,
+    previous_shapes: Vec<PreparedShape>,
+    shape_scratch: VertexBuffers<DefaultVertex, u16>,
+    tolerance: f32,
+    size: (u32, u32),
+    cull_rect: Option<ScissorRect>,
+    occlusion_culling: bool,
+    translucency_sorting: bool,
+    bound_grouping: bool,
+    upload_strategy: UploadStrategy,
+    empty_frame: EmptyFrame,
+    low_latency: bool,
+    /// Warn when tessellating a frame takes longer, see
+    /// [`Self::set_frame_time_budget_us`].
+    #[cfg(feature = "tracing")]
+    frame_time_budget_us: Option<u64>,
+    /// Returned again for a frame kept by [`EmptyFrame::KeepPrevious`].
+    last_stats: FrameStats,
+    z_normalization: Option<[f32; 2]>,
+    debug_groups: bool,
+    debug_bounds: bool,
+    overdraw_visualization: bool,
+    overdraw: Option<Overdraw>,
+    statistics: Option<Statistics>,
+    /// Counted by the last draw, see [`Self::redundant_state_changes_avoided`].
+    state_changes_avoided: AtomicUsize,
+    bounds_overlay: Option<BoundsOverlay>,
+    /// Arguments of every shape's draw, created by the first
+    /// [`Self::prepare_indirect`].
+    indirect_buffer: Option<DynamicBuffer>,
+    /// Shapes drawn by each `multi_draw_indexed_indirect`, empty when
+    /// [`Self::render_indirect`] falls back to [`Self::render`].
+    indirect_runs: Vec<Range<usize>>,
+    gpu_shapes: Option<GpuShapes>,
+    /// Device to create pipelines with on their own, see [`Self::set_device`].
+    device: Option<Arc<Device>>,
+    #[cfg(feature = "capture")]
+    capture_path: Option<std::path::PathBuf>,
+    #[cfg(all(feature = "hot-reload", debug_assertions))]
+    watched_shader: Option<hot_reload::WatchedShader>,
+}
+
+impl CyatRenderer {
+    /// Create all unchanging resources here.
+    pub fn new(
+        device: &Device,
+        texture_format: &TextureFormat,
+        multisample: MultisampleState,
+        depth_stencil: Option<DepthStencilState>,
+    ) -> Self {
+        Self::from_descriptor(device, CyatRendererDescriptor{
+            multisample,
+            depth_stencil,
+            ..CyatRendererDescriptor::new(*texture_format)
+        }).expect("the default descriptor is valid")
+    }
+
+    /// Features every renderer needs. Multiview and dual source blending
+    /// need their own features on top, see [`CyatRendererDescriptor`].
+    pub fn required_features() -> Features {
+        Features::empty()
+    }
+
+    /// Limits every renderer fits in, as long as the shapes' buffers do; see
+    /// [`Self::check_limits`] for a prepared frame.
+    pub fn required_limits() -> Limits {
+        Limits::downlevel_webgl2_defaults()
+    }
+
+    /// Pick the adapter of `instance` best suited for a renderer, able to
+    /// present to `surface` if given: discrete over integrated GPUs, then
+    /// Vulkan, Metal or WebGPU over DX12 over OpenGL. Adapters without
+    /// [`Self::required_features`] are skipped.
+    ///
+    /// On the web, where adapters cannot be enumerated, this requests a high
+    /// performance adapter instead.
+    pub fn select_adapter<'a>(instance: &'a Instance, surface: Option<&'a Surface<'_>>) -> impl Future<Output = Option<Adapter>> + 'a {
+        async move {
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                instance.enumerate_adapters(Backends::all()).into_iter()
+                    .filter(|adapter| adapter.features().contains(Self::required_features()))
+                    .filter(|adapter| surface.is_none_or(|surface| adapter.is_surface_supported(surface)))
+                    .max_by_key(|adapter| {
+                        let info = adapter.get_info();
+                        let device_type = match info.device_type {
+                            DeviceType::DiscreteGpu => 4,
+                            DeviceType::IntegratedGpu => 3,
+                            DeviceType::VirtualGpu => 2,
+                            DeviceType::Cpu => 1,
+                            DeviceType::Other => 0,
+                        };
+                        let backend = match info.backend {
+                            Backend::Vulkan | Backend::Metal | Backend::BrowserWebGpu => 2,
+                            Backend::Dx12 => 1,
+                            Backend::Gl | Backend::Empty => 0,
+                        };
+                        (device_type, backend)
+                    })
+            }
+            #[cfg(target_arch = "wasm32")]
+            {
+                instance.request_adapter(&RequestAdapterOptions{
+                    power_preference: PowerPreference::HighPerformance,
+                    compatible_surface: surface,
+                    ..RequestAdapterOptions::default()
+                }).await.filter(|adapter| adapter.features().contains(Self::required_features()))
+            }
+        }
+    }
+
+    /// The present mode to configure `surface` with for this renderer:
+    /// with [`CyatRendererDescriptor::low_latency`], `Mailbox` when the
+    /// adapter supports it, which presents the newest frame without tearing
+    /// but renders frames that are never shown. Otherwise, and as the
+    /// fallback, `Fifo`, which every surface supports and which waits for
+    /// vertical blank without wasting frames.
+    pub fn recommended_present_mode(&self, adapter: &Adapter, surface: &Surface<'_>) -> PresentMode {
+        let modes = surface.get_capabilities(adapter).present_modes;
+        if self.low_latency && modes.contains(&PresentMode::Mailbox) {PresentMode::Mailbox} else {PresentMode::Fifo}
+    }
+
+    /// Request a device from `adapter` with [`Self::required_features`] and
+    /// [`Self::required_limits`], raised to the adapter's texture size limits
+    /// so large surfaces still work.
+    pub fn request_device(adapter: &Adapter) -> impl Future<Output = Result<(Device, Queue), RequestDeviceError>> + '_ {
+        adapter.request_device(&DeviceDescriptor{
+            label: Some("wgpu_cyat"),
+            required_features: Self::required_features(),
+            required_limits: Self::required_limits().using_resolution(adapter.limits()),
+            memory_hints: MemoryHints::default(),
+        }, None)
+    }
+
+    /// Compile the built-in shader once to share it between renderers through
+    /// [`CyatRendererDescriptor::shader_module`]. Renderers using multiview
+    /// or dual source blending need their own shader and cannot share this one.
+    pub fn create_shader_module(device: &Device) -> ShaderModule {
+        ShaderModule(Arc::new(builtin_shader!(device, "shader.wgsl")))
+    }
+
+    /// Like [`Self::new`] with the extra pipeline options of the descriptor.
+    ///
+    /// Fails with [`CyatError::UnknownConstant`] for a constant of
+    /// [`CyatRendererDescriptor::constants`] the built-in shader does not
+    /// declare, when no custom [`CyatRendererDescriptor::shader_module`] is
+    /// set, with [`CyatError::MissingFeatures`] when `device` lacks the
+    /// features of an option, with [`CyatError::IncompatibleOptions`] for
+    /// options that cannot be combined, with [`CyatError::TooManyViews`]
+    /// for more multiview views than [`MAX_VIEWS`], and with
+    /// [`CyatError::ReservedBindGroup`] for an extra bind group layout at
+    /// index 0 or 1.
+    pub fn from_descriptor(device: &Device, descriptor: CyatRendererDescriptor) -> Result<Self, CyatError> {
+        let CyatRendererDescriptor{texture_format, multisample, depth_stencil, blend, write_mask, multiview, vertex_buffer_slot, dual_source_blending, vertex_entry_point, fragment_entry_point, pipeline_cache, expected_shape_count, shader_module, extra_bind_group_layouts, mut constants, y_axis, quantize, empty_frame, low_latency} = descriptor;
+        if dual_source_blending && !device.features().contains(Features::DUAL_SOURCE_BLENDING) {
+            return Err(CyatError::MissingFeatures(Features::DUAL_SOURCE_BLENDING));
+        }
+        if dual_source_blending && multiview.is_some() {
+            return Err(CyatError::IncompatibleOptions{option: "dual source blending", with: "multiview"});
+        }
+        if let Some(&(index, _)) = extra_bind_group_layouts.iter().find(|(index, _)| *index < 2) {
+            return Err(CyatError::ReservedBindGroup(index));
+        }
+        if let Some(name) = constants.keys().find(|name| shader_module.is_none() && !SHADER_CONSTANTS.contains(&name.as_str())) {
+            return Err(CyatError::UnknownConstant(name.clone()));
+        }
+        if y_axis == YAxis::Down {constants.insert("Y_DOWN".to_string(), 1.0);}
+        if let Some(QuantizeConfig{scale, bias: [x, y]}) = quantize {
+            if let Some(with) = [(multiview.is_some(), "multiview"), (dual_source_blending, "dual source blending")].into_iter().find_map(|(set, with)| set.then_some(with)) {
+                return Err(CyatError::IncompatibleOptions{option: "quantized vertices", with});
+            }
+            constants.extend([("QUANTIZE_SCALE", scale), ("QUANTIZE_BIAS_X", x), ("QUANTIZE_BIAS_Y", y)].map(|(name, value)| (name.to_string(), value as f64)));
+        }
+        let vertex_entry_point = match quantize {
+            Some(_) if shader_module.is_none() => Some("vs_quantized".to_string()),
+            _ => vertex_entry_point,
+        };
+        let blend = blend.or(dual_source_blending.then_some(DUAL_SOURCE_BLEND));
+
+        if let Some(views) = multiview {
+            if !device.features().contains(Features::MULTIVIEW) {
+                return Err(CyatError::MissingFeatures(Features::MULTIVIEW));
+            }
+            if views.get() > MAX_VIEWS {
+                return Err(CyatError::TooManyViews(views.get()));
+            }
+        }
+
+        let uniform_entry = |binding, visibility| BindGroupLayoutEntry{
+            binding,
+            visibility,
+            ty: BindingType::Buffer{ty: BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None},
+            count: None,
+        };
+        let mut layout_entries = vec![uniform_entry(0, ShaderStages::VERTEX_FRAGMENT)];
+        if multiview.is_some() {layout_entries.push(uniform_entry(1, ShaderStages::VERTEX));}
+        layout_entries.push(uniform_entry(2, ShaderStages::VERTEX));
+        #[cfg(feature = "color_grading")]
+        layout_entries.extend(ColorLut::layout_entries());
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &layout_entries,
+        });
+
+        let globals = device.create_buffer_init(&BufferInitDescriptor{
+            label: None,
+            contents: bytemuck::bytes_of(&GlobalData{globals: Globals::default(), depth_range: [0.0, 1.0], _padding: [0.0; 2]}),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let projections = multiview.map(|_| device.create_buffer_init(&BufferInitDescriptor{
+            label: None,
+            contents: bytemuck::cast_slice(&[IDENTITY; MAX_VIEWS as usize]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        }));
+        let palette = device.create_buffer_init(&BufferInitDescriptor{
+            label: None,
+            contents: bytemuck::cast_slice(&[[0.0f32; 4]; PALETTE_SIZE]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let entries = Uniforms::buffer_entries(&globals, projections.as_ref(), &palette);
+        #[cfg(feature = "color_grading")]
+        let color_lut = ColorLut::new(device);
+        #[cfg(feature = "color_grading")]
+        let entries: Vec<_> = entries.into_iter().chain(color_lut.entries()).collect();
+        let bind_group = device.create_bind_group(&BindGroupDescriptor{
+            label: None,
+            layout: &bind_group_layout,
+            entries: &entries,
+        });
+        let uniforms = Uniforms{
+            layout: bind_group_layout,
+            globals,
+            projections,
+            palette,
+            #[cfg(feature = "color_grading")]
+            color_lut,
+            bind_group,
+        };
+        let shape_uniforms = ShapeUniformBuffer::new(device);
+
+        let shader = match shader_module {
+            Some(shader) => shader,
+            None if multiview.is_some() => ShaderModule(Arc::new(builtin_shader!(device, "multiview.wgsl"))),
+            None if dual_source_blending => ShaderModule(Arc::new(builtin_shader!(device, "dual_source.wgsl"))),
+            None => Self::create_shader_module(device),
+        };
+        let empty_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{label: None, entries: &[]});
+        let bind_group_count = extra_bind_group_layouts.iter().map(|(index, _)| index + 1).max().unwrap_or(0).max(2);
+        let mut bind_group_layouts = vec![&empty_layout; bind_group_count as usize];
+        bind_group_layouts[0] = &uniforms.layout;
+        bind_group_layouts[1] = &shape_uniforms.layout;
+        for (index, layout) in &extra_bind_group_layouts {
+            bind_group_layouts[*index as usize] = layout;
+        }
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor{
+            bind_group_layouts: &bind_group_layouts,
+            ..PipelineLayoutDescriptor::default()
+        });
+        let pipeline_state = PipelineState{
+            shader,
+            layout,
+            target: ColorTargetState{format: texture_format, blend, write_mask},
+            multisample,
+            depth_stencil,
+            multiview,
+            vertex_buffer_slot,
+            vertex_entry_point,
+            fragment_entry_point,
+            cache: pipeline_cache,
+            constants,
+            y_axis,
+            dual_source_blending,
+            bind_group_count,
+            quantize,
+        };
+        let render_pipeline = pipeline_state.create(device, PrimitiveTopology::TriangleList);
+        let stencil_clear_pipeline = pipeline_state.create_stencil_clear(device);
+        let depth_prepass_pipeline = pipeline_state.create_depth_prepass(device);
+
+        Ok(CyatRenderer{
+            pipeline_state,
+            render_pipeline,
+            stencil_clear_pipeline,
+            depth_prepass_pipeline,
+            uniforms,
+            shape_uniforms,
+            masks: None,
+            gradients: None,
+            sprites: None,
+            layers: None,
+            line_pipeline: None,
+            point_pipeline: None,
+            overlay_pipelines: HashMap::new(),
+            translucent_pipelines: HashMap::new(),
+            variants: Vec::new(),
+            chunks: vec![Chunk::new(device)],
+            max_buffer_size: None,
+            cyat_buffers: VertexBuffers::new(),
+            shape_buffer: Vec::with_capacity(expected_shape_count),
+            previous_buffers: VertexBuffers::new(),
+            previous_shapes: Vec::with_capacity(expected_shape_count),
+            shape_scratch: VertexBuffers::new(),
+            tolerance: DEFAULT_TOLERANCE,
+            size: (0, 0),
+            cull_rect: None,
+            occlusion_culling: false,
+            translucency_sorting: false,
+            bound_grouping: false,
+            upload_strategy: UploadStrategy::WriteBuffer,
+            empty_frame,
+            low_latency,
+            #[cfg(feature = "tracing")]
+            frame_time_budget_us: None,
+            last_stats: FrameStats::default(),
+            z_normalization: None,
+            debug_groups: false,
+            debug_bounds: false,
+            overdraw_visualization: false,
+            overdraw: None,
+            statistics: None,
+            state_changes_avoided: AtomicUsize::new(0),
+            bounds_overlay: None,
+            indirect_buffer: None,
+            indirect_runs: Vec::new(),
+            gpu_shapes: None,
+            device: None,
+            #[cfg(feature = "capture")]
+            capture_path: None,
+            #[cfg(all(feature = "hot-reload", debug_assertions))]
+            watched_shader: None,
+        })
+    }
+
+    /// Upload the matrix applied to vertex positions for each view of a
+    /// multiview renderer, starting at view 0. Views default to identity.
+    ///
+    /// Panics if the renderer was not created with
+    /// [`CyatRendererDescriptor::multiview`] or given more matrices than views.
+    pub fn set_eye_projections(&self, queue: &Queue, projections: &[Projection]) {
+        let buffer = self.uniforms.projections.as_ref().expect("renderer was created without multiview");
+        let views = self.pipeline_state.multiview.map_or(1, NonZeroU32::get) as usize;
+        assert!(projections.len() <= views, "{} projections for {views} views", projections.len());
+        queue.write_buffer(buffer, 0, bytemuck::cast_slice(projections));
+    }
+
+    /// Upload the colors of the palette that shapes built with
+    /// [`DefaultAttributes::palette`] look up when drawn, starting at slot 0,
+    /// so they can be recolored without preparing them again. Colors are sRGB
+    /// encoded like [`DefaultAttributes::color`], and slots default to
+    /// transparent black.
+    ///
+    /// Panics if given more than [`PALETTE_SIZE`] colors.
+    pub fn set_palette(&self, queue: &Queue, colors: &[Color]) {
+        assert!(colors.len() <= PALETTE_SIZE, "{} colors exceeds PALETTE_SIZE", colors.len());
+        let c = |c: f64| srgb_to_linear(c as f32);
+        let colors: Vec<[f32; 4]> = colors.iter().map(|color| [c(color.r), c(color.g), c(color.b), color.a as f32]).collect();
+        queue.write_buffer(&self.uniforms.palette, 0, bytemuck::cast_slice(&colors));
+    }
+
+    /// Check that the pipelines were created for a render target of
+    /// `format` with `sample_count` samples. wgpu panics when rendering to
+    /// any other target, e.g. after the window surface was recreated with a
+    /// new format. wgpu does not expose a render pass's target, so
+    /// [`Self::render`] cannot check this itself.
+    pub fn validate_render_target(&self, format: TextureFormat, sample_count: u32) -> Result<(), CyatError> {
+        let pipeline_format = self.pipeline_state.target.format;
+        if pipeline_format != format {
+            return Err(CyatError::FormatMismatch{pipeline_format, target_format: format});
+        }
+        let pipeline_count = self.pipeline_state.multisample.count;
+        if pipeline_count != sample_count {
+            return Err(CyatError::SampleCountMismatch{pipeline_count, target_count: sample_count});
+        }
+        Ok(())
+    }
+
+    /// Samples per pixel the pipelines were created for, set with
+    /// [`CyatRendererDescriptor::msaa`], [`Self::set_render_target`] or
+    /// [`Self::set_multisample`].
+    pub fn sample_count(&self) -> u32 {
+        self.pipeline_state.multisample.count
+    }
+
+    /// Keep `device` so [`Self::set_render_target`] can create pipelines
+    /// without the caller passing it.
+    pub fn set_device(&mut self, device: Arc<Device>) {
+        self.device = Some(device);
+    }
+
+    /// Render to targets of `format` with `sample_count` samples from now
+    /// on, creating every pipeline again when they differ from the current
+    /// target's. Fails like [`Self::validate_render_target`] when they differ
+    /// and no device was given to [`Self::set_device`].
+    pub fn set_render_target(&mut self, format: TextureFormat, sample_count: u32) -> Result<(), CyatError> {
+        let mismatch = match self.validate_render_target(format, sample_count) {
+            Ok(()) => return Ok(()),
+            Err(mismatch) => mismatch,
+        };
+        let Some(device) = self.device.clone() else {return Err(mismatch);};
+        let mut state = self.pipeline_state.clone();
+        state.target.format = format;
+        state.multisample.count = sample_count;
+        self.recreate_pipelines(&device, state);
+        Ok(())
+    }
+
+    /// Multisample with `multisample` from now on, e.g. when the user turns
+    /// antialiasing on or off, creating every pipeline again from the same
+    /// shader and layout when it differs from the current state. The
+    /// prepared frame and its buffers are kept, so it renders again without
+    /// another [`Self::prepare`]. Render targets and a
+    /// [`Self::create_depth_texture`] must be created again with the new
+    /// [`Self::sample_count`]; wgpu does not expose a pass's sample count,
+    /// so [`Self::render`] cannot assert that they were, but
+    /// [`Self::validate_render_target`] checks a target.
+    pub fn set_multisample(&mut self, device: &Device, multisample: MultisampleState) {
+        if self.pipeline_state.multisample == multisample {return;}
+        let state = PipelineState{multisample, ..self.pipeline_state.clone()};
+        self.recreate_pipelines(device, state);
+    }
+
+    /// Replace every pipeline created so far, including those of masks,
+    /// gradients, sprites, layers and overdraw, by ones created from `state`.
+    fn recreate_pipelines(&mut self, device: &Device, state: PipelineState) {
+        let pipelines = self.create_pipelines(device, &state);
+        self.set_pipelines(state, pipelines);
+
+        let (state, uniforms_layout, shape_layout) = (&self.pipeline_state, &self.uniforms.layout, &self.shape_uniforms.layout);
+        if let Some(masks) = &mut self.masks {masks.recreate_pipeline(device, state, uniforms_layout, shape_layout);}
+        if let Some(gradients) = &mut self.gradients {gradients.recreate_pipeline(device, state, uniforms_layout, shape_layout);}
+        if let Some(sprites) = &mut self.sprites {sprites.recreate_pipeline(device, state, uniforms_layout, shape_layout);}
+        if let Some(layers) = &mut self.layers {layers.recreate_pipeline(device, state);}
+        if let Some(Overdraw{shader, pipelines}) = &mut self.overdraw {
+            for (&topology, pipeline) in pipelines.iter_mut() {*pipeline = state.create_overdraw(device, topology.into(), shader);}
+        }
+    }
+
+    /// Create every pipeline the renderer has created so far from `state`.
+    fn create_pipelines(&self, device: &Device, state: &PipelineState) -> Pipelines {
+        Pipelines{
+            render: state.create(device, PrimitiveTopology::TriangleList),
+            line: self.line_pipeline.as_ref().map(|_| state.create(device, PrimitiveTopology::LineList)),
+            point: self.point_pipeline.as_ref().map(|_| state.create(device, PrimitiveTopology::PointList)),
+            overlay: self.overlay_pipelines.keys().map(|&topology| (topology, state.create_overlay(device, topology.into()))).collect(),
+            translucent: self.translucent_pipelines.keys().map(|&topology| (topology, state.create_translucent(device, topology.into()))).collect(),
+            variants: self.variants.iter().map(|variant| {
+                let variant_state = state.variant(&variant.descriptor);
+                let pipelines = variant.pipelines.keys().map(|&topology| (topology, variant_state.create(device, topology.into()))).collect();
+                (variant_state, pipelines)
+            }).collect(),
+            stencil_clear: state.create_stencil_clear(device),
+            depth_prepass: state.create_depth_prepass(device),
+        }
+    }
+
+    fn set_pipelines(&mut self, state: PipelineState, pipelines: Pipelines) {
+        self.pipeline_state = state;
+        self.render_pipeline = pipelines.render;
+        self.line_pipeline = pipelines.line;
+        self.point_pipeline = pipelines.point;
+        self.overlay_pipelines = pipelines.overlay;
+        self.translucent_pipelines = pipelines.translucent;
+        self.stencil_clear_pipeline = pipelines.stencil_clear;
+        self.depth_prepass_pipeline = pipelines.depth_prepass;
+        for (variant, (state, pipelines)) in self.variants.iter_mut().zip(pipelines.variants) {
+            variant.state = state;
+            variant.pipelines = pipelines;
+        }
+    }
+
+    /// Data of the [`CyatRendererDescriptor::pipeline_cache`] to persist for
+    /// the next run, `None` without a cache or when the backend has none.
+    /// Line and point pipelines are only added once a shape first uses them.
+    pub fn required_pipeline_cache_data(&self) -> Option<Vec<u8>> {
+        self.pipeline_state.cache.as_ref()?.get_data()
+    }
+
+    /// Make room for `shapes` prepared shapes so large frames do not grow the
+    /// shape list while preparing. Around 1.2 times the last
+    /// [`FrameStats::shapes`] adapts to a scene that keeps growing.
+    pub fn reserve_shapes(&mut self, shapes: usize) {
+        self.shape_buffer.reserve(shapes.saturating_sub(self.shape_buffer.len()));
+        self.previous_shapes.reserve(shapes.saturating_sub(self.previous_shapes.len()));
+    }
+
+    /// Make room for frames of up to `vertices`, `indices` and `shapes` up
+    /// front, growing the CPU lists and the GPU buffers once instead of over
+    /// the first frames. Buffers never shrink, so a [`Self::capacities`] from
+    /// a previous run restores its high-water mark. The prepared frame stays
+    /// renderable.
+    ///
+    /// Only the first buffer pair grows, to at most the
+    /// [`Self::set_max_buffer_size`] limit.
+    pub fn reserve(&mut self, device: &Device, queue: &Queue, vertices: usize, indices: usize, shapes: usize) {
+        for buffers in [&mut self.cyat_buffers, &mut self.previous_buffers] {
+            buffers.vertices.reserve(vertices.saturating_sub(buffers.vertices.len()));
+            buffers.indices.reserve(indices.saturating_sub(buffers.indices.len()));
+        }
+        self.reserve_shapes(shapes);
+
+        let limit = self.max_buffer_size.unwrap_or_else(|| device.limits().max_buffer_size).max(1);
+        let chunk_capacity = 1usize << limit.ilog2();
+        let vertex_bytes_reserved = (vertices * self.vertex_size()).min(chunk_capacity);
+        let index_bytes_reserved = (indices * std::mem::size_of::<u16>()).min(chunk_capacity);
+        let quantize = self.pipeline_state.quantize;
+        let chunk = &mut self.chunks[0];
+        // Grow with the chunk's current contents in front, so it can still be drawn.
+        if vertex_bytes_reserved as u64 > chunk.vertex_buffer.as_ref().size() {
+            let mut contents = vertex_bytes(self.cyat_buffers.vertices.get(chunk.vertices.clone()).unwrap_or(&[]), quantize).into_owned();
+            contents.resize(vertex_bytes_reserved, 0);
+            chunk.vertex_buffer.write_buffer(device, queue, &contents);
+        }
+        if index_bytes_reserved as u64 > chunk.index_buffer.as_ref().size() {
+            let mut contents = bytemuck::cast_slice::<_, u8>(self.cyat_buffers.indices.get(chunk.indices.clone()).unwrap_or(&[])).to_vec();
+            contents.resize(index_bytes_reserved, 0);
+            chunk.index_buffer.write_buffer(device, queue, &contents);
+        }
+
+        if (shapes as u64 + 1) * self.shape_uniforms.stride > self.shape_uniforms.buffer.size() {
+            self.shape_uniforms.reserve(device, shapes);
+            self.shape_uniforms.write(device, queue, &self.shape_buffer);
+        }
+    }
+
+    /// What the renderer currently has room for, to pass to
+    /// [`Self::reserve`] on the next run.
+    pub fn capacities(&self) -> BufferCapacities {
+        BufferCapacities{
+            vertices: self.cyat_buffers.vertices.capacity(),
+            indices: self.cyat_buffers.indices.capacity(),
+            shapes: self.shape_buffer.capacity(),
+        }
+    }
+
+    /// Draw `index_count` indices from buffers the application fills on the
+    /// GPU, e.g. with a compute shader, in place of the prepared frame, so
+    /// no shapes pass through the CPU. Until [`Self::unbind_gpu_shapes`],
+    /// [`Self::prepare`] leaves the renderer untouched and [`Self::render`]
+    /// draws the buffers as one shape with default [`ShapeUniforms`],
+    /// scissored to the target from [`Self::resize`].
+    ///
+    /// `vertex_buffer` holds [`DefaultVertex`], or [`QuantizedVertex`] with
+    /// [`CyatRendererDescriptor::with_quantized_vertices`], with
+    /// `VERTEX | STORAGE` usage. `index_buffer` holds `u16` indices with
+    /// `INDEX | STORAGE` usage. Fails with [`CyatError::MissingBufferUsages`]
+    /// when either lacks them, leaving the renderer as it was.
+    pub fn bind_gpu_shapes(&mut self, device: &Device, index_count: u32, vertex_buffer: Arc<Buffer>, index_buffer: Arc<Buffer>) -> Result<(), CyatError> {
+        for (buffer, usage, required) in [("vertex", vertex_buffer.usage(), BufferUsages::VERTEX | BufferUsages::STORAGE), ("index", index_buffer.usage(), BufferUsages::INDEX | BufferUsages::STORAGE)] {
+            if !usage.contains(required) {
+                return Err(CyatError::MissingBufferUsages{buffer, usages: required - usage});
+            }
+        }
+        let uniforms = device.create_buffer_init(&BufferInitDescriptor{
+            label: None,
+            contents: bytemuck::bytes_of(&ShapeData::new(ShapeUniforms::default(), None, ColorEffect::default())),
+            usage: BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor{
+            label: None,
+            layout: &self.shape_uniforms.layout,
+            entries: &[BindGroupEntry{binding: 0, resource: uniforms.as_entire_binding()}],
+        });
+        self.gpu_shapes = Some(GpuShapes{vertex_buffer, index_buffer, index_count, bind_group});
+        Ok(())
+    }
+
+    /// Draw the prepared frame again after [`Self::bind_gpu_shapes`], from
+    /// the next [`Self::prepare`] on.
+    pub fn unbind_gpu_shapes(&mut self) {
+        self.gpu_shapes = None;
+    }
+
+    /// Set [`Globals::time`]. Takes effect on the next render without
+    /// preparing again.
+    pub fn set_time(&self, queue: &Queue, seconds: f32) {
+        queue.write_buffer(&self.uniforms.globals, 0, bytemuck::bytes_of(&seconds));
+    }
+
+    /// Set [`Globals::user`], like [`Self::set_time`].
+    pub fn set_user_globals(&self, queue: &Queue, user: [f32; 3]) {
+        queue.write_buffer(&self.uniforms.globals, std::mem::offset_of!(Globals, user) as BufferAddress, bytemuck::bytes_of(&user));
+    }
+
+    /// Wrap each draw in a debug group named `shape[{i}] {label}`, preceded by
+    /// a `draw_call_{i}` marker, and the whole render in a `wgpu_cyat::render`
+    /// group, so GPU captures and profilers can tell shapes apart. Mask
+    /// rendering during prepare is grouped as `wgpu_cyat::prepare`. Only takes
+    /// effect in builds with debug assertions; release builds do not store
+    /// labels.
+    pub fn set_debug_groups(&mut self, enabled: bool) {
+        self.debug_groups = enabled;
+    }
+
+    /// Outline every shape's bound in a color picked by its index, drawn by
+    /// [`Self::render`] after the shapes without a scissor. The outlines are
+    /// built by [`Self::prepare`] only while this is enabled.
+    pub fn set_debug_bounds(&mut self, enabled: bool) {
+        self.debug_bounds = enabled;
+        if !enabled {self.bounds_overlay = None;}
+    }
+
+    /// Create the pipelines [`Self::render_overdraw`] needs for the shapes of
+    /// every [`Self::prepare`] while enabled.
+    pub fn set_overdraw_visualization(&mut self, enabled: bool) {
+        self.overdraw_visualization = enabled;
+        if !enabled {self.overdraw = None;}
+    }
+
+    /// Count the primitives and fragments of every [`Self::render`] with a
+    /// pipeline statistics query, read with [`Self::resolve_statistics`].
+    /// Needs [`Features::PIPELINE_STATISTICS_QUERY`] on `device`, without
+    /// which it stays off. Returns whether it is on. Off by default, when
+    /// render does no more than before.
+    pub fn set_pipeline_statistics(&mut self, device: &Device, enabled: bool) -> bool {
+        let enabled = enabled && device.features().contains(Features::PIPELINE_STATISTICS_QUERY);
+        match (enabled, &self.statistics) {
+            (true, None) => self.statistics = Some(Statistics::new(device)),
+            (false, _) => self.statistics = None,
+            (true, Some(_)) => {}
+        }
+        enabled
+    }
+
+    /// The [`PipelineStatistics`] of the last render read back so far, or
+    /// `None` before the first arrives or while statistics are off. Call it
+    /// once per frame after submitting the render: it starts copying the
+    /// query of that frame, whose counts a later call returns once the
+    /// GPU is done, usually the next frame's.
+    pub fn resolve_statistics(&mut self, device: &Device, queue: &Queue) -> Option<PipelineStatistics> {
+        let statistics = self.statistics.as_mut()?;
+        if statistics.pending {
+            device.poll(Maintain::Poll);
+            if !statistics.mapped.load(Ordering::Acquire) {return statistics.last;}
+            let data = statistics.readback.slice(..).get_mapped_range();
+            let [clipper_invocations, clipper_primitives_out, fragment_shader_invocations]: [u64; 3] = bytemuck::pod_read_unaligned(&data);
+            drop(data);
+            statistics.readback.unmap();
+            statistics.last = Some(PipelineStatistics{clipper_invocations, clipper_primitives_out, fragment_shader_invocations});
+            statistics.pending = false;
+            statistics.mapped.store(false, Ordering::Release);
+        }
+        if statistics.written.swap(false, Ordering::AcqRel) {
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor{label: Some("wgpu_cyat::resolve_statistics")});
+            encoder.resolve_query_set(&statistics.query_set, 0..1, &statistics.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(&statistics.resolve_buffer, 0, &statistics.readback, 0, Statistics::SIZE);
+            queue.submit([encoder.finish()]);
+            let mapped = statistics.mapped.clone();
+            statistics.readback.slice(..).map_async(MapMode::Read, move |result| mapped.store(result.is_ok(), Ordering::Release));
+            statistics.pending = true;
+        }
+        statistics.last
+    }
+
+    /// `set_pipeline`, `set_vertex_buffer` and `set_index_buffer` calls the
+    /// last [`Self::render`], or other `render_*` method, skipped because a
+    /// shape shares them with the one drawn before it. Counted as the draws
+    /// are recorded, so it reflects layers, damage and indirect runs, and is
+    /// 0 after drawing [`Self::bind_gpu_shapes`].
+    pub fn redundant_state_changes_avoided(&self) -> usize {
+        self.state_changes_avoided.load(Ordering::Relaxed)
+    }
+
+    /// View of array layer `layer` of `texture` to use as the color attachment
+    /// when rendering into a `D2Array` texture, for example one screen per
+    /// layer sampled later in a 3D pass.
+    ///
+    /// Panics if the texture format differs from the one this renderer was
+    /// created with or `layer` is past the last array layer.
+    pub fn layer_view(&self, texture: &Texture, layer: u32) -> TextureView {
+        assert_eq!(texture.format(), self.pipeline_state.target.format, "texture format does not match the render pipeline");
+        assert!(layer < texture.depth_or_array_layers(), "layer {layer} out of range for {} array layers", texture.depth_or_array_layers());
+        texture.create_view(&TextureViewDescriptor{
+            dimension: Some(TextureViewDimension::D2),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..TextureViewDescriptor::default()
+        })
+    }
+
+    /// Format of the depth buffer the pipelines were created for, if any.
+    pub fn depth_texture_format(&self) -> Option<TextureFormat> {
+        self.pipeline_state.depth_stencil.as_ref().map(|depth| depth.format)
+    }
+
+    /// A depth texture matching the pipelines' depth format and sample count.
+    ///
+    /// Panics if the renderer was created without a depth stencil state.
+    pub fn create_depth_texture(&self, device: &Device, width: u32, height: u32) -> Texture {
+        device.create_texture(&TextureDescriptor{
+            label: None,
+            size: Extent3d{width, height, depth_or_array_layers: 1},
+            mip_level_count: 1,
+            sample_count: self.pipeline_state.multisample.count,
+            dimension: TextureDimension::D2,
+            format: self.depth_texture_format().expect("renderer was created without depth"),
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+    }
+
+    /// Size in pixels of the render target, used to resolve auto bounds.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.size = (width, height);
+    }
+
+    /// Skip shapes whose bound does not intersect `rect`. Shapes with an
+    /// explicit bound are skipped before tessellation, auto bound ones once
+    /// their bound is known. `None`, the default, keeps every shape.
+    pub fn set_cull_rect(&mut self, rect: Option<ScissorRect>) {
+        self.cull_rect = rect;
+    }
 
-pub use cyat;
-use cyat::{VertexBuffers, ShapeBuilder, Vertex};
+    /// Skip shapes whose explicit bound is entirely covered by the bounds of
+    /// [`ShapeArea::opaque`] shapes drawn after them, before tessellating.
+    /// Only unmasked triangle shapes with an opaque tint and an explicit
+    /// bound count as covering; partly covered shapes are always kept. Does
+    /// nothing with a depth buffer, where draw order does not decide what is
+    /// on top. Off by default since it costs time in every prepare.
+    pub fn set_occlusion_culling(&mut self, enabled: bool) {
+        self.occlusion_culling = enabled;
+    }
 
-type Bound = (u32, u32, u32, u32);
-pub struct ShapeArea(pub ShapeBuilder<DefaultAttributes>, pub Bound);
+    /// With a depth buffer, draw translucent shapes after the others sorted
+    /// back to front by their farthest vertex [`DefaultAttributes::z`], and
+    /// without writing depth, so overlapping translucent shapes blend the
+    /// same whatever order they were passed in. A shape counts as
+    /// translucent unless it is [`ShapeArea::opaque`] with an opaque tint.
+    /// Overlay shapes keep their own order. Off by default, and does nothing
+    /// without a depth buffer, where the passed order is the draw order.
+    ///
+    /// Sorting whole shapes cannot order translucent shapes that intersect
+    /// in depth, or one whose vertices lie at varying depths in front of and
+    /// behind another; such scenes still depend on the order of the shapes.
+    /// Masked, gradient and pipeline variant shapes are sorted, but their
+    /// pipelines keep writing depth.
+    pub fn set_translucency_sorting(&mut self, enabled: bool) {
+        self.translucency_sorting = enabled;
+    }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct DefaultAttributes {
-    pub color: [f32; 3],
-    pub z: f32
-}
+    /// Draw shapes with the same bound one after another, so
+    /// [`Self::render`] sets each scissor rect once instead of on every
+    /// switch between interleaved panels. A shape only moves back past
+    /// shapes whose bounds it does not overlap, or with a depth buffer that
+    /// writes depth and no blending, past opaque shapes the depth test
+    /// orders anyway, so the frame looks the same. Shapes at equal depths
+    /// are the exception, where the first drawn wins. Overlay and
+    /// translucent shapes never move past other kinds of shapes. Off by
+    /// default; see [`FrameStats::scissor_changes`] for the effect.
+    pub fn set_bound_grouping(&mut self, enabled: bool) {
+        self.bound_grouping = enabled;
+    }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct DefaultVertex {
-    position: [f32; 2],
-    color: [f32; 3],
-    z: f32
-}
+    /// Split the prepared frame across several vertex and index buffer pairs
+    /// when one would exceed `size` bytes, rebinding them between shapes in
+    /// [`Self::render`]; `None`, the default, uses the device's
+    /// `max_buffer_size`. Buffers grow to powers of two, so each holds at
+    /// most the largest one within `size`. Shapes never straddle two
+    /// buffers, and [`Self::prepare`] fails with
+    /// [`CyatError::ShapeTooLarge`] for one larger than that on its own.
+    pub fn set_max_buffer_size(&mut self, size: Option<u64>) {
+        self.max_buffer_size = size;
+    }
 
-impl DefaultVertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 3] =
-        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x3, 2 => Float32];
-}
+    /// What [`Self::prepare`] does when given no shapes, from the
+    /// [`CyatRendererDescriptor::empty_frame`] until changed. It can be set
+    /// before any call, e.g. to keep the previous frame only while the
+    /// application knows it may miss one.
+    pub fn set_empty_frame(&mut self, policy: EmptyFrame) {
+        self.empty_frame = policy;
+    }
 
-impl DefaultVertex {
-    fn layout() -> VertexBufferLayout<'static> {
-        VertexBufferLayout {
-            array_stride: std::mem::size_of::<Self>() as BufferAddress,
-            step_mode: VertexStepMode::Vertex,
-            attributes: &Self::ATTRIBS,
-        }
+    /// How [`Self::prepare`] uploads the frame's vertices and indices,
+    /// [`UploadStrategy::WriteBuffer`] by default. Use
+    /// [`UploadStrategy::for_device`] to map them where the GPU shares its
+    /// memory with the CPU.
+    pub fn set_upload_strategy(&mut self, strategy: UploadStrategy) {
+        self.upload_strategy = strategy;
     }
-}
 
-impl Vertex for DefaultVertex {
-    type Attributes = DefaultAttributes;
+    /// Log a warning after every [`Self::prepare`] whose
+    /// [`FrameStats::tessellation_time_us`] exceeds `budget` microseconds,
+    /// a sign that the scene needs to become simpler to keep the frame rate.
+    #[cfg(feature = "tracing")]
+    pub fn set_frame_time_budget_us(&mut self, budget: u64) {
+        self.frame_time_budget_us = Some(budget);
+    }
 
-    fn construct(position: [f32; 2], attrs: Self::Attributes) -> DefaultVertex {
-        let c = |f: f32| OrderedFloat((f + 0.055) / 1.055).powf(2.4);
-        DefaultVertex{
-            position,
-            color: [c(attrs.color[0]), c(attrs.color[1]), c(attrs.color[2])],
-            z: attrs.z
+    /// Remap the [`DefaultAttributes::z`] of every prepared vertex from the
+    /// smallest and largest of the frame to `[near, far]` within
+    /// `0.0..=1.0`, so shapes may use any finite depths. A frame with a
+    /// single depth is drawn at `near`. `None`, the default, keeps the depths
+    /// as given and [`Self::prepare`] fails for any outside `0.0..=1.0`.
+    pub fn set_z_normalization(&mut self, range: Option<[f32; 2]>) {
+        if let Some([near, far]) = range {
+            assert!((0.0..=1.0).contains(&near) && (0.0..=1.0).contains(&far), "depth range {near}..{far} is outside 0.0..=1.0");
         }
+        self.z_normalization = range;
     }
-}
-
-pub struct CyatRenderer {
-    render_pipeline: RenderPipeline,
-    vertex_buffer: DynamicBuffer,
-    index_buffer: DynamicBuffer,
-    cyat_buffers: VertexBuffers<DefaultVertex, u16>,
-    shape_buffer: Vec<(usize, usize, Bound)>
-}
-
-impl CyatRenderer {
-    /// Create all unchanging resources here.
-    pub fn new(
-        device: &Device,
-        texture_format: &TextureFormat,
-        multisample: MultisampleState,
-        depth_stencil: Option<DepthStencilState>,
-    ) -> Self {
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor::default());
-        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                compilation_options: PipelineCompilationOptions::default(),
-                buffers: &[DefaultVertex::layout()]
-            },
-            fragment: Some(FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                compilation_options: PipelineCompilationOptions::default(),
-                targets: &[Some((*texture_format).into())],
-            }),
-            primitive: PrimitiveState::default(),
-            depth_stencil,
-            multisample,
-            multiview: None,
-            cache: None
-        });
 
-        let vertex_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
-            label: None,
-            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-        });
+    /// Confine the depth the renderer writes and tests to `[min, max]` of
+    /// the depth buffer through the viewport, e.g. `[0.1, 0.2]` to draw
+    /// between the layers of other renderers sharing it without z-fighting
+    /// them. The prepared depths, after any [`Self::set_z_normalization`],
+    /// are mapped linearly from `0.0..=1.0` into the range by the built-in
+    /// vertex shaders, and the default `[0.0, 1.0]` leaves them as they are.
+    /// Like [`Self::set_time`] it takes effect on the next render without
+    /// preparing again. Pipelines passed to [`Self::render_with_pipeline`]
+    /// and custom shaders map their depth themselves, see [`Globals`].
+    pub fn set_depth_range(&self, queue: &Queue, [min, max]: [f32; 2]) {
+        assert!((0.0..=1.0).contains(&min) && (0.0..=1.0).contains(&max) && min <= max, "depth range {min}..{max} is not ordered within 0.0..=1.0");
+        queue.write_buffer(&self.uniforms.globals, std::mem::offset_of!(GlobalData, depth_range) as BufferAddress, bytemuck::bytes_of(&[min, max]));
+    }
 
-        let index_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
-            label: None,
-            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
-        });
+    /// Bind `group` at `index` for a layout added with
+    /// [`CyatRendererDescriptor::with_extra_bind_group_layout`], before
+    /// calling [`Self::render`]. The caller creates the group to match it.
+    pub fn set_extra_bind_group(&self, render_pass: &mut RenderPass<'_>, group: &BindGroup, index: u32) {
+        assert!(index >= 2 && index < self.pipeline_state.bind_group_count, "no extra bind group layout at {index}");
+        render_pass.set_bind_group(index, group, &[]);
+    }
 
-        CyatRenderer{
-            render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            cyat_buffers: VertexBuffers::new(),
-            shape_buffer: Vec::new()
+    /// Add a pipeline variant that shapes select by `name` with
+    /// [`ShapeArea::with_pipeline`], replacing any variant of that name. Its
+    /// triangle pipeline is created here, others when a shape first uses them.
+    pub fn register_pipeline(&mut self, device: &Device, name: impl Into<String>, descriptor: PipelineVariantDescriptor) {
+        let name = name.into();
+        let state = self.pipeline_state.variant(&descriptor);
+        let pipelines = HashMap::from([(Topology::TriangleList, state.create(device, PrimitiveTopology::TriangleList))]);
+        let variant = PipelineVariant{name, descriptor, state, pipelines};
+        match self.variants.iter_mut().find(|v| v.name == variant.name) {
+            Some(existing) => *existing = variant,
+            None => self.variants.push(variant),
         }
     }
 
+    /// Default tessellation tolerance for shapes that do not set their own.
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        self.tolerance = tolerance;
+    }
+
     /// Prepare for rendering this frame; create all resources that will be
     /// used during the next render that do not already exist.
+    ///
+    /// The built buffers are compared against the previous frame and only
+    /// uploaded when they differ, see [`FrameStats::changed`]. When their size
+    /// is unchanged only the range that differs is written.
+    ///
+    /// Fails without changing the prepared frame when a
+    /// [`ShapeArea::parent`] is missing or part of a cycle, or a
+    /// [`ShapeArea::pipeline`] was not registered. A shape with a depth that
+    /// is not finite or, without [`Self::set_z_normalization`], outside
+    /// `0.0..=1.0` fails with [`CyatError::InvalidDepth`] and leaves nothing
+    /// to render until the next prepare.
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "wgpu_cyat::prepare", level = "info", skip_all,
+        fields(vertex_count = tracing::field::Empty, index_count = tracing::field::Empty)
+    ))]
     pub fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        mut shapes: Vec<ShapeArea>
+    ) -> Result<FrameStats, CyatError> {
+        if let Some(index) = shapes.iter().position(ShapeArea::fills_builder_non_zero) {
+            return Err(CyatError::UnsupportedFillRule(index));
+        }
+        #[cfg(feature = "capture")]
+        self.capture_owned(&mut shapes)?;
+
+        // Builders can only be built by value, so move them out of the
+        // shapes and build everything else by reference.
+        let mut taken = Vec::with_capacity(shapes.len());
+        for shape in &mut shapes {
+            let geometry = shape.geometry.needs_owned().then(|| std::mem::replace(&mut shape.geometry, Geometry::Mesh(VertexBuffers::new())));
+            let mask = shape.mask.as_mut().filter(|mask| mask.needs_owned()).map(|mask| std::mem::replace(mask, Geometry::Mesh(VertexBuffers::new())));
+            taken.push((geometry, mask));
+        }
+        self.prepare_shapes(device, queue, &shapes, taken)
+    }
+
+    /// Like [`Self::prepare`], but leaves `shapes` with the caller so they can
+    /// be prepared again next frame without being rebuilt or cloned. Meshes
+    /// are copied straight into the frame's buffers.
+    ///
+    /// cyat can only build a [`ShapeBuilder`] by value, so this fails with
+    /// [`CyatError::BorrowedBuilder`] for shapes made from one, like
+    /// [`ShapeArea::new`]; use [`ShapeArea::shape`] or a mesh instead.
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "wgpu_cyat::prepare", level = "info", skip_all,
+        fields(vertex_count = tracing::field::Empty, index_count = tracing::field::Empty)
+    ))]
+    pub fn prepare_ref(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        shapes: &[ShapeArea]
+    ) -> Result<FrameStats, CyatError> {
+        if let Some(i) = shapes.iter().position(|shape| shape.geometry.needs_owned() || shape.mask.as_ref().is_some_and(Geometry::needs_owned)) {
+            return Err(CyatError::BorrowedBuilder(i));
+        }
+        #[cfg(feature = "capture")]
+        self.capture(shapes)?;
+        self.prepare_shapes(device, queue, shapes, (0..shapes.len()).map(|_| (None, None)).collect())
+    }
+
+    /// Like [`Self::prepare`], also writing the arguments of every shape's
+    /// draw to an indirect buffer so [`Self::render_indirect`] can draw runs
+    /// of consecutive shapes with one `multi_draw_indexed_indirect` each.
+    ///
+    /// Scissor rects, per-shape uniforms and bind groups cannot change
+    /// within an indirect draw, so a run ends wherever any of them does:
+    /// shapes with the same bound, [`ShapeUniforms`] and effects and no
+    /// texture of their own share one, and a frame of plain shapes in one
+    /// bound is a single call. [`Self::set_bound_grouping`] lengthens the
+    /// runs of interleaved bounds. Without [`Features::MULTI_DRAW_INDIRECT`]
+    /// on `device` this is [`Self::prepare`], and
+    /// [`FrameStats::indirect_draw_calls`] is 0.
+    pub fn prepare_indirect(
         &mut self,
         device: &Device,
         queue: &Queue,
         shapes: Vec<ShapeArea>
-    ) {
+    ) -> Result<FrameStats, CyatError> {
+        let mut stats = self.prepare(device, queue, shapes)?;
+        if !device.features().contains(Features::MULTI_DRAW_INDIRECT) || self.shape_buffer.is_empty() {return Ok(stats);}
+
+        for (i, shape) in self.shape_buffer.iter().enumerate() {
+            match self.indirect_runs.last_mut() {
+                Some(run) if self.shape_buffer[run.start].shares_draw_state(shape) => run.end = i + 1,
+                _ => self.indirect_runs.push(i..i + 1),
+            }
+        }
+        let args: Vec<u8> = self.shape_buffer.iter().flat_map(|shape| {
+            let chunk = &self.chunks[shape.chunk];
+            DrawIndexedIndirectArgs{
+                index_count: (shape.end - shape.start) as u32,
+                instance_count: 1,
+                first_index: (shape.start - chunk.indices.start) as u32,
+                base_vertex: (shape.vertex_start - chunk.vertices.start) as i32,
+                first_instance: 0,
+            }.as_bytes().to_vec()
+        }).collect();
+        self.indirect_buffer.get_or_insert_with(|| DynamicBuffer::new(device, &DynamicBufferDescriptor{
+            label: None,
+            usage: BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+        })).write_buffer(device, queue, &args);
+
+        stats.indirect_draw_calls = self.indirect_runs.len();
+        Ok(stats)
+    }
+
+    /// Prepare `shapes`, building the geometries in `taken` by value in
+    /// place of the ones left in the shapes.
+    fn prepare_shapes(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        shapes: &[ShapeArea],
+        taken: Vec<(Option<Geometry>, Option<Geometry>)>
+    ) -> Result<FrameStats, CyatError> {
+        #[cfg(all(feature = "hot-reload", debug_assertions))]
+        self.reload_shader_if_modified(device);
+        self.indirect_runs.clear();
+        if let Some(gpu_shapes) = &self.gpu_shapes {
+            return Ok(FrameStats{shapes: 1, indices: gpu_shapes.index_count as usize, changed: true, ..FrameStats::default()});
+        }
+        if shapes.is_empty() && self.empty_frame == EmptyFrame::KeepPrevious {
+            return Ok(FrameStats{changed: false, stale: true, ..self.last_stats});
+        }
+
+        let bounds = resolve_bounds(shapes)?;
+        if let Some(name) = shapes.iter().filter_map(|shape| shape.pipeline.as_ref()).find(|name| !self.variants.iter().any(|v| &v.name == *name)) {
+            return Err(CyatError::UnknownPipeline(name.clone()));
+        }
+        if let Some(index) = shapes.iter().position(|shape| shape.mask.is_some() || shape.gradient.is_some() || shape.sprite.is_some()).filter(|_| self.pipeline_state.quantize.is_some()) {
+            return Err(CyatError::UnsupportedQuantized(index));
+        }
+        let effect = |shape: &ShapeArea| shape.mask.is_some() || ((shape.gradient.is_some() || shape.sprite.is_some()) && shape.topology == Topology::TriangleList);
+        if let Some(index) = shapes.iter().position(effect).filter(|_| self.pipeline_state.dual_source_blending || self.pipeline_state.bind_group_count > 2) {
+            return Err(CyatError::UnsupportedEffect(index));
+        }
+        let groups = resolve_groups(shapes, &bounds, self.size)?;
+        let rounded_clips = resolve_rounded_clips(shapes);
+        let effects = resolve_effects(shapes);
+        let mut shapes: Vec<_> = shapes.iter().enumerate().zip(bounds).zip(rounded_clips).zip(effects).zip(taken).zip(groups)
+            .map(|((((((index, shape), bound), rounded_clip), effect), (geometry, mask)), group)| Pending{index, shape, bound, rounded_clip, effect, geometry, mask, group})
+            .collect();
+        if let Some(group) = shapes.iter().find_map(|pending| pending.group).filter(|_| !Layers::supported(&self.pipeline_state)) {
+            return Err(CyatError::UnsupportedGroup(group.root));
+        }
+        let unsupported = |pending: &Pending| pending.group.is_some() && (
+            pending.shape.mask.is_some() || pending.shape.gradient.is_some() || pending.shape.sprite.is_some() || pending.rounded_clip.is_some() || pending.shape.viewport.is_some()
+        );
+        if let Some(pending) = shapes.iter().find(|pending| unsupported(pending)) {
+            return Err(CyatError::UnsupportedGroupShape(pending.index));
+        }
+        shapes.sort_by_key(|pending| (pending.shape.overlay, pending.shape.z_index));
+
+        std::mem::swap(&mut self.cyat_buffers, &mut self.previous_buffers);
+        std::mem::swap(&mut self.shape_buffer, &mut self.previous_shapes);
         self.cyat_buffers.clear();
         self.shape_buffer.clear();
+        if let Some(masks) = &mut self.masks {masks.clear();}
+        if let Some(gradients) = &mut self.gradients {gradients.clear();}
+        if let Some(sprites) = &mut self.sprites {sprites.clear();}
+        if let Some(layers) = &mut self.layers {layers.clear();}
 
         let mut index = 0;
+        let mut culled = 0;
+        // First vertex and index of each chunk. Buffers grow to powers of
+        // two, so a chunk holds at most the largest one within the limit.
+        let mut chunk_starts = vec![(0, 0)];
+        let limit = self.max_buffer_size.unwrap_or_else(|| device.limits().max_buffer_size).max(1);
+        let chunk_capacity = 1 << limit.ilog2();
+        // Farthest depth of each prepared shape, while sorting translucent ones.
+        let mut depths = Vec::new();
+        let (mut occluded, mut occluded_vertices) = (0, 0);
+        let visible = match self.occlusion_culling && self.pipeline_state.depth_stencil.is_none() {
+            true => occlusion(&shapes, self.cull_rect),
+            false => vec![true; shapes.len()],
+        };
+
+        // Smallest and largest depth of the frame, while normalizing it.
+        let mut frame_z = (f32::INFINITY, f32::NEG_INFINITY);
+        // Groups with a prepared shape, indexed by PreparedShape::layer.
+        let mut groups: Vec<Group> = Vec::new();
+
+        let mut tessellation_time = std::time::Duration::ZERO;
+        for (Pending{index: shape_index, shape, bound: (bound, clip), rounded_clip, effect, geometry: taken_geometry, mask: taken_mask, group}, visible) in shapes.into_iter().zip(visible) {
+            let ShapeArea{geometry, tolerance, label, topology, uniforms, mask, overlay, gradient, pipeline, sprite, clip_mode, viewport, fill_rule, ..} = shape;
+            let (tolerance, topology, uniforms, overlay) = (tolerance.unwrap_or(self.tolerance), *topology, *uniforms, *overlay);
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("wgpu_cyat::build_shape", index = self.shape_buffer.len()).entered();
+
+            if !visible {
+                occluded += 1;
+                if let Geometry::Mesh(mesh) = geometry {occluded_vertices += mesh.vertices.len();}
+                continue;
+            }
+
+            if bound.is_some_and(|bound| bound.is_empty() || !self.in_cull_rect(bound)) || group.is_some_and(|group| group.rect.is_empty()) {
+                culled += 1;
+                continue;
+            }
 
-        for ShapeArea(shape, bound) in shapes {
-            shape.build(&mut self.cyat_buffers);
+            // Each shape is built on its own so its indices start at 0 and are
+            // offset by base_vertex when drawn, which also lets the frame hold
+            // more vertices than a u16 index can address.
+            let vertex_start = self.cyat_buffers.vertices.len();
+            let constant_z = taken_geometry.as_ref().unwrap_or(geometry).constant_z();
+            self.shape_scratch.clear();
+            timed(&mut tessellation_time, || geometry.build_or(taken_geometry, tolerance, *fill_rule, &mut self.shape_scratch));
+            if self.shape_scratch.vertices.len() > u16::MAX as usize + 1 {
+                self.cyat_buffers.clear();
+                self.shape_buffer.clear();
+                return Err(CyatError::TooManyVertices(self.shape_scratch.vertices.len()));
+            }
+
+            // Constant depth is checked once, anything else per vertex.
+            let z_range = match constant_z {
+                Some(z) => z.is_finite().then_some((z, z)),
+                None => z_range(&self.shape_scratch.vertices),
+            };
+            let valid = z_range.is_some_and(|(min, max)| self.z_normalization.is_some() || (min >= 0.0 && max <= 1.0));
+            let Some(z_range) = z_range.filter(|_| valid) else {
+                // Leave nothing to draw rather than a partly built frame.
+                self.cyat_buffers.clear();
+                self.shape_buffer.clear();
+                return Err(CyatError::InvalidDepth(shape_index));
+            };
+            self.cyat_buffers.vertices.extend_from_slice(&self.shape_scratch.vertices);
+            self.cyat_buffers.indices.extend_from_slice(&self.shape_scratch.indices);
+
+            let viewport = viewport.filter(|_| mask.is_none());
+            let target = ScissorRect::from_pos_size(0, 0, self.size.0, self.size.1);
+            if viewport.is_some_and(|viewport| viewport.is_empty() || viewport.intersect(&target) != Some(viewport)) {
+                self.cyat_buffers.clear();
+                self.shape_buffer.clear();
+                return Err(CyatError::InvalidViewport(shape_index));
+            }
+            let bound = bound.or_else(|| self.auto_bound(vertex_start, viewport).and_then(|bound| clip.map_or(Some(bound), |clip| clip.intersect(&bound))));
+            let Some(bound) = bound.filter(|bound| self.in_cull_rect(*bound)) else {
+                self.cyat_buffers.vertices.truncate(vertex_start);
+                self.cyat_buffers.indices.truncate(index);
+                culled += 1;
+                continue;
+            };
+
+            let (vertex_end, index_end) = (self.cyat_buffers.vertices.len(), self.cyat_buffers.indices.len());
+            let size = chunk_size(vertex_end - vertex_start, self.vertex_size(), index_end - index);
+            if size > chunk_capacity {
+                self.cyat_buffers.clear();
+                self.shape_buffer.clear();
+                return Err(CyatError::ShapeTooLarge{shape: shape_index, size, limit: chunk_capacity});
+            }
+            let (chunk_vertex, chunk_index) = *chunk_starts.last().unwrap();
+            if chunk_size(vertex_end - chunk_vertex, self.vertex_size(), index_end - chunk_index) > chunk_capacity {
+                chunk_starts.push((vertex_start, index));
+            }
+
+            let variant = pipeline.as_ref().and_then(|name| self.variants.iter().position(|v| &v.name == name));
+            if let Some(variant) = variant.map(|i| &mut self.variants[i]) {
+                variant.pipelines.entry(topology).or_insert_with(|| variant.state.create(device, topology.into()));
+            }
+
+            let gradient = gradient.as_ref().filter(|_| mask.is_none() && topology == Topology::TriangleList).map(|gradient| {
+                let (state, uniforms_layout, shape_layout) = (&self.pipeline_state, &self.uniforms.layout, &self.shape_uniforms.layout);
+                let gradients = self.gradients.get_or_insert_with(|| Gradients::new(device, state, uniforms_layout, shape_layout));
+                gradients.push(gradient, self.size, &self.shape_scratch.vertices)
+            });
+
+            let sprite = sprite.as_ref().filter(|_| mask.is_none() && gradient.is_none() && topology == Topology::TriangleList).map(|sprite| {
+                let (state, uniforms_layout, shape_layout) = (&self.pipeline_state, &self.uniforms.layout, &self.shape_uniforms.layout);
+                let sprites = self.sprites.get_or_insert_with(|| Sprites::new(device, state, uniforms_layout, shape_layout));
+                sprites.push(device, sprite, vertex_start, self.cyat_buffers.vertices.len())
+            });
+
+            let mask = mask.as_ref().map(|mask| {
+                let (state, uniforms_layout, shape_layout) = (&self.pipeline_state, &self.uniforms.layout, &self.shape_uniforms.layout);
+                let masks = self.masks.get_or_insert_with(|| Masks::new(device, state, uniforms_layout, shape_layout));
+                masks.push(|buffer| timed(&mut tessellation_time, || mask.build_or(taken_mask, tolerance, *fill_rule, buffer)), bound, *clip_mode)
+            });
+
+            let layer = group.map(|group| groups.iter().position(|g| g.root == group.root).unwrap_or_else(|| {
+                groups.push(group);
+                groups.len() - 1
+            }));
 
             let buffer_len = self.cyat_buffers.indices.len();
-            self.shape_buffer.push((index, buffer_len, bound));
+            #[cfg(not(debug_assertions))]
+            let _ = label;
+            self.shape_buffer.push(PreparedShape{
+                start: index,
+                end: buffer_len,
+                vertex_start,
+                vertex_end: self.cyat_buffers.vertices.len(),
+                bound,
+                #[cfg(debug_assertions)]
+                label: label.clone(),
+                topology,
+                uniforms,
+                mask,
+                gradient,
+                sprite,
+                variant,
+                overlay: overlay && self.pipeline_state.depth_stencil.is_some(),
+                translucent: self.translucency_sorting && self.pipeline_state.depth_stencil.is_some() &&
+                    !overlay && !(shape.opaque && uniforms.tint[3] >= 1.0),
+                rounded_clip: rounded_clip.map(|(rect, rounded_clip)| (rect.unwrap_or(bound), rounded_clip)),
+                effect,
+                viewport,
+                chunk: chunk_starts.len() - 1,
+                stencil_reference: shape.stencil_reference,
+                user_data: shape.user_data,
+                layer,
+            });
+
             index = buffer_len;
+            frame_z = (frame_z.0.min(z_range.0), frame_z.1.max(z_range.1));
+
+            if self.translucency_sorting {
+                depths.push(self.cyat_buffers.vertices[vertex_start..].iter().map(|v| v.z).fold(f32::MIN, f32::max));
+            }
+            if self.shape_buffer.last().unwrap().overlay {
+                self.overlay_pipelines.entry(topology).or_insert_with(|| self.pipeline_state.create_overlay(device, topology.into()));
+            }
+            if self.shape_buffer.last().unwrap().translucent {
+                self.translucent_pipelines.entry(topology).or_insert_with(|| self.pipeline_state.create_translucent(device, topology.into()));
+            }
+            let pipeline = match topology {
+                Topology::TriangleList => continue,
+                Topology::LineList => &mut self.line_pipeline,
+                Topology::PointList => &mut self.point_pipeline,
+            };
+            pipeline.get_or_insert_with(|| self.pipeline_state.create(device, topology.into()));
         }
+        let tessellation_time_us = tessellation_time.as_micros() as u64;
 
-        if self.cyat_buffers.vertices.is_empty() || self.cyat_buffers.indices.is_empty() {return;}
+        let frame_end = (self.cyat_buffers.vertices.len(), self.cyat_buffers.indices.len());
+        let chunk_ranges: Vec<_> = chunk_starts.iter().zip(chunk_starts.iter().skip(1).chain([&frame_end]))
+            .map(|(&(vertex_start, index_start), &(vertex_end, index_end))| (vertex_start..vertex_end, index_start..index_end))
+            .collect();
+        let chunks_changed = chunk_ranges.len() > self.chunks.len() || self.chunks.iter().enumerate().any(|(i, chunk)| {
+            let (vertices, indices) = chunk_ranges.get(i).cloned().unwrap_or_default();
+            chunk.vertices != vertices || chunk.indices != indices
+        });
+
+        if let Some([near, far]) = self.z_normalization.filter(|_| frame_z.0 < frame_z.1) {
+            let scale = (far - near) / (frame_z.1 - frame_z.0);
+            for vertex in &mut self.cyat_buffers.vertices {
+                vertex.z = near + (vertex.z - frame_z.0) * scale;
+            }
+        } else if let Some([near, _]) = self.z_normalization {
+            for vertex in &mut self.cyat_buffers.vertices {vertex.z = near;}
+        }
+
+        // Shapes index their own ranges of the buffers, so they can be
+        // reordered once built. The sort is stable, keeping other shapes and
+        // translucent ones at the same depth in order.
+        if self.shape_buffer.iter().any(|shape| shape.translucent) {
+            let mut shapes: Vec<_> = std::mem::take(&mut self.shape_buffer).into_iter().zip(depths).collect();
+            shapes.sort_by(|(a, a_depth), (b, b_depth)| {
+                a.overlay.cmp(&b.overlay).then(a.translucent.cmp(&b.translucent))
+                    .then_with(|| if a.translucent {b_depth.total_cmp(a_depth)} else {std::cmp::Ordering::Equal})
+            });
+            self.shape_buffer = shapes.into_iter().map(|(shape, _)| shape).collect();
+        }
 
-        self.vertex_buffer.write_buffer(device, queue, bytemuck::cast_slice(&self.cyat_buffers.vertices));
-        self.index_buffer.write_buffer(device, queue, bytemuck::cast_slice(&self.cyat_buffers.indices));
+        let scissor_changes_before_grouping = scissor_changes(&self.shape_buffer);
+        if self.bound_grouping {
+            let shapes = std::mem::take(&mut self.shape_buffer);
+            let depth_orders = self.pipeline_state.target.blend.is_none() &&
+                self.pipeline_state.depth_stencil.as_ref().is_some_and(|depth| depth.depth_write_enabled && !depth.stencil.is_enabled());
+            self.shape_buffer = group_bounds(shapes, depth_orders);
+        }
+
+        // Masks are rendered again on every prepare, so a frame using any of
+        // them always counts as changed. So are layers, once the shape
+        // uniforms they read are written below.
+        let masked = self.masks.as_mut().is_some_and(|masks| masks.render(device, queue, self.size, cfg!(debug_assertions) && self.debug_groups));
+        if !groups.is_empty() {
+            let state = &self.pipeline_state;
+            let layers = self.layers.get_or_insert_with(|| Layers::new(device, state));
+            layers.push(&groups, &self.shape_buffer, &self.cyat_buffers, self.size, state.y_axis);
+        }
+        let layered = self.layers.as_mut().is_some_and(|layers| layers.write(device, queue, &self.pipeline_state));
+
+        let gradients_changed = self.gradients.as_mut().is_some_and(|gradients| gradients.write(device, queue));
+        let sprites_changed = self.sprites.as_mut().is_some_and(|sprites| sprites.write(device, queue, self.cyat_buffers.vertices.len(), self.upload_strategy));
+
+        let buffers_changed =
+            bytemuck::cast_slice::<_, u8>(&self.cyat_buffers.vertices) != bytemuck::cast_slice::<_, u8>(&self.previous_buffers.vertices) ||
+            self.cyat_buffers.indices != self.previous_buffers.indices;
+
+        let stats = FrameStats{
+            shapes: self.shape_buffer.len(),
+            vertices: self.cyat_buffers.vertices.len(),
+            indices: self.cyat_buffers.indices.len(),
+            changed: buffers_changed || masked || layered || gradients_changed || sprites_changed || self.shape_buffer != self.previous_shapes,
+            culled,
+            occluded,
+            occluded_vertices,
+            chunks: chunk_ranges.len(),
+            scissor_changes: scissor_changes(&self.shape_buffer),
+            scissor_changes_before_grouping,
+            indirect_draw_calls: 0,
+            stale: false,
+            tessellation_time_us,
+        };
+        self.last_stats = stats;
+
+        #[cfg(feature = "tracing")]
+        if let Some(budget) = self.frame_time_budget_us.filter(|&budget| tessellation_time_us > budget) {
+            tracing::warn!(target: "wgpu_cyat", tessellation_time_us, budget, "tessellating the frame took longer than its budget");
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::Span::current()
+            .record("vertex_count", stats.vertices)
+            .record("index_count", stats.indices);
+
+        if stats.changed {
+            self.shape_uniforms.write(device, queue, &self.shape_buffer);
+        }
+        if let Some(layers) = self.layers.as_ref().filter(|_| layered) {
+            layers.render(device, queue, &self.pipeline_state, cfg!(debug_assertions) && self.debug_groups, |render_pass, i| {
+                let shape = &self.shape_buffer[i];
+                match shape.variant {
+                    Some(variant) => render_pass.set_pipeline(&self.variants[variant].pipelines[&shape.topology]),
+                    None => render_pass.set_pipeline(self.pipeline(shape.topology, shape.overlay, shape.translucent)),
+                }
+                render_pass.set_bind_group(0, &self.uniforms.bind_group, &[]);
+                render_pass.set_bind_group(1, &self.shape_uniforms.bind_group, &[self.shape_uniforms.offset(i)]);
+                render_pass.set_stencil_reference(shape.stencil_reference);
+            });
+        }
+
+        if self.overdraw_visualization {
+            let overdraw = self.overdraw.get_or_insert_with(|| Overdraw{
+                shader: builtin_shader!(device, "shader.wgsl"),
+                pipelines: HashMap::new(),
+            });
+            for shape in &self.shape_buffer {
+                overdraw.pipelines.entry(shape.topology).or_insert_with(|| self.pipeline_state.create_overdraw(device, shape.topology.into(), &overdraw.shader));
+            }
+        }
+
+        if self.debug_bounds {
+            self.line_pipeline.get_or_insert_with(|| self.pipeline_state.create(device, PrimitiveTopology::LineList));
+            let overlay = self.bounds_overlay.get_or_insert_with(|| BoundsOverlay::new(device));
+            overlay.write(device, queue, &self.shape_buffer, self.size, self.pipeline_state.y_axis, self.pipeline_state.quantize);
+        }
+
+        #[cfg(all(feature = "debug_checks", debug_assertions))]
+        for ScissorOverlap{shape_a, shape_b, overlap} in self.check_scissor_overlaps() {
+            tracing::warn!(target: "wgpu_cyat", shape_a, shape_b, ?overlap, "shape bounds partly overlap");
+        }
+
+        if !(buffers_changed || chunks_changed) || self.cyat_buffers.vertices.is_empty() || self.cyat_buffers.indices.is_empty() {return Ok(stats);}
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("wgpu_cyat::upload").entered();
+
+        // The previous frame is only on the GPU if it was uploaded.
+        let uploaded = !self.previous_buffers.vertices.is_empty() && !self.previous_buffers.indices.is_empty();
+        // Chunks the frame does not need keep their buffers for later frames.
+        for chunk in self.chunks.iter_mut().skip(chunk_ranges.len()) {
+            (chunk.vertices, chunk.indices) = (0..0, 0..0);
+        }
+        for (i, (vertices, indices)) in chunk_ranges.into_iter().enumerate() {
+            if i == self.chunks.len() {self.chunks.push(Chunk::new(device));}
+            let chunk = &mut self.chunks[i];
+            // A chunk holding the same range as before only needs what changed.
+            let same = uploaded && chunk.vertices == vertices && chunk.indices == indices;
+            let quantize = self.pipeline_state.quantize;
+            let previous_vertices = if same {vertex_bytes(&self.previous_buffers.vertices[vertices.clone()], quantize)} else {(&[][..]).into()};
+            let previous_indices = if same {bytemuck::cast_slice(&self.previous_buffers.indices[indices.clone()])} else {&[][..]};
+            write_changed(&mut chunk.vertex_buffer, device, queue, &vertex_bytes(&self.cyat_buffers.vertices[vertices.clone()], quantize), &previous_vertices, self.upload_strategy);
+            write_changed(&mut chunk.index_buffer, device, queue, bytemuck::cast_slice(&self.cyat_buffers.indices[indices.clone()]), previous_indices, self.upload_strategy);
+            (chunk.vertices, chunk.indices) = (vertices, indices);
+        }
+        Ok(stats)
+    }
+
+    /// Bytes of each vertex in the vertex buffer.
+    fn vertex_size(&self) -> usize {
+        match self.pipeline_state.quantize {
+            Some(_) => std::mem::size_of::<QuantizedVertex>(),
+            None => std::mem::size_of::<DefaultVertex>(),
+        }
+    }
+
+    /// Check the vertex layout, bind groups and buffers of the last prepare
+    /// against the limits of `device`, returning every limit that is exceeded.
+    pub fn check_limits(&self, device: &Device) -> Result<(), Vec<LimitViolation>> {
+        let limits = device.limits();
+        let checks = [
+            ("max_vertex_buffers", self.pipeline_state.vertex_buffer_slot as u64 + if self.sprites.is_some() {2} else {1}, limits.max_vertex_buffers as u64),
+            ("max_vertex_attributes", DefaultVertex::ATTRIBS.len() as u64, limits.max_vertex_attributes as u64),
+            ("max_vertex_buffer_array_stride", self.vertex_size() as u64, limits.max_vertex_buffer_array_stride as u64),
+            ("max_bind_groups", self.pipeline_state.bind_group_count.max(if self.masks.is_some() || self.gradients.is_some() || self.sprites.is_some() {3} else {2}) as u64, limits.max_bind_groups as u64),
+            ("max_buffer_size", self.chunks.iter().map(|chunk| chunk.vertices.len() * self.vertex_size()).max().unwrap_or(0) as u64, limits.max_buffer_size),
+            ("max_buffer_size", self.chunks.iter().map(|chunk| chunk.indices.len() * std::mem::size_of::<u16>()).max().unwrap_or(0) as u64, limits.max_buffer_size),
+        ];
+        let violations: Vec<_> = checks.into_iter()
+            .filter(|(_, required, available)| required > available)
+            .map(|(limit_name, required, available)| LimitViolation{limit_name, required, available})
+            .collect();
+        if violations.is_empty() {Ok(())} else {Err(violations)}
+    }
+
+    /// Pairs of shapes from the last prepare whose bounds overlap without one
+    /// containing the other, which usually means a layout bug. Nested bounds
+    /// are not reported. Compares every pair, so it is slow for large frames;
+    /// with the `debug_checks` feature debug builds log each overlap as a
+    /// warning after every prepare.
+    pub fn check_scissor_overlaps(&self) -> Vec<ScissorOverlap> {
+        let mut overlaps = Vec::new();
+        for (shape_a, a) in self.shape_buffer.iter().enumerate() {
+            for (shape_b, b) in self.shape_buffer.iter().enumerate().skip(shape_a + 1) {
+                let Some(overlap) = a.bound.intersect(&b.bound) else {continue;};
+                if overlap != a.bound && overlap != b.bound {
+                    overlaps.push(ScissorOverlap{shape_a, shape_b, overlap});
+                }
+            }
+        }
+        overlaps
+    }
+
+    /// Move the scissor rect of shape `shape_index` in the prepared frame,
+    /// e.g. to scroll a panel, without preparing again or any GPU work. The
+    /// index counts shapes as drawn, after sorting and culling, and `bound`
+    /// is in pixels of the target rather than relative to a parent, clamped
+    /// to the size set by [`Self::resize`].
+    ///
+    /// Only the scissor moves: rounded clips and the debug bounds overlay
+    /// stay where the last prepare put them, and the shape is not culled.
+    pub fn set_bound(&mut self, shape_index: usize, bound: impl Into<ScissorRect>) -> Result<(), CyatError> {
+        let target = ScissorRect::from_pos_size(0, 0, self.size.0, self.size.1);
+        let shape = self.shape_buffer.get_mut(shape_index).ok_or(CyatError::ShapeOutOfRange(shape_index))?;
+        shape.bound = bound.into().intersect(&target).unwrap_or_default();
+        Ok(())
+    }
+
+    /// [`Self::set_bound`] for several shapes, changing none of them if any
+    /// index is out of range.
+    pub fn set_bounds(&mut self, bounds: impl IntoIterator<Item = (usize, ScissorRect)>) -> Result<(), CyatError> {
+        let bounds: Vec<_> = bounds.into_iter().collect();
+        if let Some(&(shape_index, _)) = bounds.iter().find(|(i, _)| *i >= self.shape_buffer.len()) {
+            return Err(CyatError::ShapeOutOfRange(shape_index));
+        }
+        let target = ScissorRect::from_pos_size(0, 0, self.size.0, self.size.1);
+        for (shape_index, bound) in bounds {
+            self.shape_buffer[shape_index].bound = bound.intersect(&target).unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    /// Bounds of the vertices of shape `shape_index` from the last prepare.
+    pub fn shape_bounds(&self, shape_index: usize) -> Option<Aabb2D> {
+        let shape = self.shape_buffer.get(shape_index)?;
+        Aabb2D::from_points(self.cyat_buffers.vertices[shape.vertex_start..shape.vertex_end].iter().map(|v| v.position))
+    }
+
+    /// [`ShapeArea::user_data`] of shape `shape_index` from the last
+    /// prepare, counting shapes as drawn like [`Self::set_bound`].
+    pub fn user_data(&self, shape_index: usize) -> Option<u64> {
+        self.shape_buffer.get(shape_index).map(|shape| shape.user_data)
+    }
+
+    /// Union of the bounds of every shape from the last prepare.
+    pub fn scene_bounds(&self) -> Option<Aabb2D> {
+        (0..self.shape_buffer.len()).filter_map(|i| self.shape_bounds(i)).reduce(|a, b| a.union(&b))
+    }
+
+    /// Pixel bounding box of the vertices built since `vertex_start`, clamped
+    /// to the target. `None` when nothing of the shape lands on the target.
+    fn auto_bound(&self, vertex_start: usize, viewport: Option<ScissorRect>) -> Option<ScissorRect> {
+        let mut aabb = Aabb2D::from_points(self.cyat_buffers.vertices[vertex_start..].iter().map(|v| v.position))?;
+        if self.pipeline_state.y_axis == YAxis::Down {
+            (aabb.min[1], aabb.max[1]) = (-aabb.max[1], -aabb.min[1]);
+        }
+        let target = ScissorRect::from_pos_size(0, 0, self.size.0, self.size.1);
+        pixel_rect(aabb, viewport.unwrap_or(target))?.intersect(&target)
+    }
+
+    fn in_cull_rect(&self, bound: ScissorRect) -> bool {
+        self.cull_rect.is_none_or(|rect| rect.intersect(&bound).is_some())
+    }
+
+    fn pipeline(&self, topology: Topology, overlay: bool, translucent: bool) -> &RenderPipeline {
+        if let Some(pipeline) = self.overlay_pipelines.get(&topology).filter(|_| overlay) {return pipeline;}
+        if let Some(pipeline) = self.translucent_pipelines.get(&topology).filter(|_| translucent) {return pipeline;}
+        match topology {
+            Topology::TriangleList => &self.render_pipeline,
+            Topology::LineList => self.line_pipeline.as_ref().expect("created by prepare"),
+            Topology::PointList => self.point_pipeline.as_ref().expect("created by prepare"),
+        }
     }
 
     /// Render using caller provided render pass.
+    ///
+    /// Render leaves the scissor at the last shape's bound, so reset it before
+    /// drawing more into the same pass, or when another renderer draws into
+    /// it next.
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "wgpu_cyat::render", level = "info", skip_all,
+        fields(draw_calls = self.shape_buffer.len())
+    ))]
     pub fn render(&self, render_pass: &mut RenderPass<'_>) {
+        self.draw_counted(render_pass, Runs::Shapes);
+        self.draw_bounds_overlay(render_pass);
+    }
+
+    /// Like [`Self::render`], but draw only the shapes whose bound
+    /// intersects a rect of `damage`, scissored to that overlap, so a pass
+    /// loading the previous frame repaints just the damaged regions and no
+    /// pixel outside them. A shape is drawn once per damage rect it
+    /// intersects, which for overlapping damage rects blends translucent
+    /// shapes twice where they overlap. Opaque shapes come out as from
+    /// [`Self::render`] either way, so merge overlapping damage first when
+    /// the frame blends. The [`Self::set_debug_bounds`] overlay is not
+    /// drawn, as it covers the whole target.
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "wgpu_cyat::render", level = "info", skip_all,
+        fields(damage = damage.len())
+    ))]
+    pub fn render_damaged(&self, render_pass: &mut RenderPass<'_>, damage: &[ScissorRect]) {
+        self.draw_counted(render_pass, Runs::Damaged(damage));
+    }
+
+    /// Draw the frame prepared by [`Self::prepare_indirect`] with one
+    /// `multi_draw_indexed_indirect` per run of shapes sharing their state,
+    /// falling back to [`Self::render`] when the frame was prepared
+    /// otherwise or the device lacks [`Features::MULTI_DRAW_INDIRECT`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "wgpu_cyat::render", level = "info", skip_all,
+        fields(draw_calls = self.indirect_runs.len())
+    ))]
+    pub fn render_indirect(&self, render_pass: &mut RenderPass<'_>) {
+        match self.indirect_buffer.as_ref().filter(|_| !self.indirect_runs.is_empty()) {
+            Some(buffer) => {
+                self.draw_counted(render_pass, Runs::Indirect(buffer.as_ref()));
+                self.draw_bounds_overlay(render_pass);
+            }
+            None => self.render(render_pass),
+        }
+    }
+
+    /// Begin a render pass on `encoder` that loads `view` with `load`, and
+    /// [`Self::render`] the prepared frame into it. `depth` is a view of a
+    /// [`Self::create_depth_texture`], given exactly when the renderer has a
+    /// depth format. It is cleared to 1.0, along with any stencil to 0, and
+    /// discarded after the pass. Compose passes with [`Self::render`] to
+    /// draw more into the same pass or to resolve a multisampled target.
+    ///
+    /// The pass has no resolve target, so debug builds assert that the
+    /// renderer is not multisampled, along with `depth` matching it. wgpu
+    /// does not expose a view's format, so check that with
+    /// [`Self::validate_render_target`].
+    pub fn render_to_view(&self, encoder: &mut CommandEncoder, view: &TextureView, load: LoadOp<Color>, depth: Option<&TextureView>) {
+        debug_assert_eq!(depth.is_some(), self.depth_texture_format().is_some(), "render_to_view needs a depth view exactly when the renderer has a depth format");
+        debug_assert_eq!(self.sample_count(), 1, "render_to_view cannot resolve a multisampled target");
+        let stencil = self.depth_texture_format().is_some_and(|format| format.has_stencil_aspect());
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor{
+            label: None,
+            color_attachments: &[Some(RenderPassColorAttachment{
+                view,
+                resolve_target: None,
+                ops: Operations{load, store: StoreOp::Store},
+            })],
+            depth_stencil_attachment: depth.map(|view| RenderPassDepthStencilAttachment{
+                view,
+                depth_ops: Some(Operations{load: LoadOp::Clear(1.0), store: StoreOp::Discard}),
+                stencil_ops: stencil.then_some(Operations{load: LoadOp::Clear(0), store: StoreOp::Discard}),
+            }),
+            ..RenderPassDescriptor::default()
+        });
+        self.render(&mut render_pass);
+    }
+
+    /// Set the whole stencil aspect of the pass's target to `value` by
+    /// drawing a full screen triangle that writes nothing else, e.g. between
+    /// groups of shapes clipping with [`ShapeArea::with_stencil_reference`]
+    /// so one group's stencil values do not leak into the next. The scissor
+    /// rect is reset to the target from [`Self::resize`], and the stencil
+    /// reference back to 0 as [`Self::render`] expects it.
+    ///
+    /// Needs a [`CyatRendererDescriptor::depth_stencil`] whose format has a
+    /// stencil aspect, like `Depth24PlusStencil8` or `Stencil8`, and panics
+    /// otherwise. Its own stencil ops do not matter, but the pass must not
+    /// mark the stencil read-only. See [`Self::needs_stencil_clear`].
+    pub fn clear_stencil(&self, render_pass: &mut RenderPass<'_>, value: u8) {
+        let pipeline = self.stencil_clear_pipeline.as_ref().expect("clearing the stencil needs a depth format with a stencil aspect");
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_stencil_reference(value as u32);
+        if self.size.0 > 0 && self.size.1 > 0 {
+            render_pass.set_scissor_rect(0, 0, self.size.0, self.size.1);
+        }
+        render_pass.draw(0..3, 0..1);
+        render_pass.set_stencil_reference(0);
+    }
+
+    /// Whether the prepared frame draws shapes with a stencil reference
+    /// through a stencil test, whose values a following frame or group of
+    /// shapes sharing the pass may need cleared with [`Self::clear_stencil`].
+    pub fn needs_stencil_clear(&self) -> bool {
+        self.stencil_clear_pipeline.is_some() &&
+            self.pipeline_state.depth_stencil.as_ref().is_some_and(|depth| depth.stencil.is_enabled()) &&
+            self.shape_buffer.iter().any(|shape| shape.stencil_reference != 0)
+    }
+
+    /// A pipeline drawing the prepared shapes' depth and nothing else, with
+    /// no fragment stage so the GPU can skip shading, as
+    /// [`Self::render_depth_prepass`] uses it. Panics when the renderer has
+    /// no depth format.
+    pub fn depth_prepass_pipeline(&self) -> &RenderPipeline {
+        self.depth_prepass_pipeline.as_ref().expect("a depth prepass needs a depth format")
+    }
+
+    /// Write the depth of the prepared opaque shapes with the
+    /// [`Self::depth_prepass_pipeline`], so the following [`Self::render`]
+    /// shades each pixel only for the nearest of them instead of every shape
+    /// covering it. Without a fragment stage the pipeline has no color
+    /// targets, so call it in a pass of its own before the main one, with
+    /// only the depth attachment that the main pass then loads. Set up the
+    /// main pass with [`CyatRendererDescriptor::with_depth_prepass`], as a
+    /// strictly `Less` test would reject the depth written here.
+    ///
+    /// Shapes with translucent or discarded fragments are left out: tints
+    /// below full opacity, translucent shapes when sorted, overlays, masks,
+    /// gradients, sprites, rounded clips, stencil references, pipeline
+    /// variants, lines and points. The main pass draws them over what the
+    /// prepass wrote without hiding the opaque shapes behind them, so give
+    /// them a z in front of those. Shapes bound with
+    /// [`Self::bind_gpu_shapes`] are left out too.
+    pub fn render_depth_prepass(&self, render_pass: &mut RenderPass<'_>) {
+        debug_assert!(
+            self.pipeline_state.depth_stencil.as_ref().is_some_and(|depth| !matches!(depth.depth_compare, CompareFunction::Less | CompareFunction::Greater)),
+            "the main pass must accept the prepass depth, see CyatRendererDescriptor::with_depth_prepass"
+        );
+        self.draw(render_pass, DrawWith::Pipeline(self.depth_prepass_pipeline()), Runs::DepthPrepass);
+    }
+
+    /// [`Self::draw`] with the renderer's pipelines inside the statistics
+    /// query, when there is one.
+    fn draw_counted(&self, render_pass: &mut RenderPass<'_>, runs: Runs<'_>) {
+        let Some(statistics) = &self.statistics else {return self.draw(render_pass, DrawWith::Own, runs);};
+        render_pass.begin_pipeline_statistics_query(&statistics.query_set, 0);
+        self.draw(render_pass, DrawWith::Own, runs);
+        render_pass.end_pipeline_statistics_query();
+        statistics.written.store(true, Ordering::Release);
+    }
+
+    fn draw_bounds_overlay(&self, render_pass: &mut RenderPass<'_>) {
+        if let Some(overlay) = self.bounds_overlay.as_ref().filter(|_| self.debug_bounds) {
+            let offset = self.shape_uniforms.offset(self.shape_buffer.len());
+            overlay.draw(render_pass, self, offset);
+        }
+    }
+
+    /// Draw the prepared frame again with `pipeline` in place of the
+    /// renderer's own, e.g. for a shadow or glow pass before [`Self::render`].
+    ///
+    /// The vertex and index buffers and per-shape scissor rects are reused, so
+    /// `pipeline` must read [`DefaultVertex::layout`] from the
+    /// [`CyatRendererDescriptor::vertex_buffer_slot`] and its target must
+    /// match the pass. Groups 0 and 1 are bound to the renderer's [`Globals`]
+    /// and [`ShapeUniforms`], so a pipeline using them must declare the same
+    /// layouts. The buffers stay valid until the next [`Self::prepare`]. The
+    /// one pipeline draws every shape, so debug builds assert that all of
+    /// them use [`Topology::TriangleList`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "wgpu_cyat::render", level = "info", skip_all,
+        fields(draw_calls = self.shape_buffer.len())
+    ))]
+    pub fn render_with_pipeline(&self, render_pass: &mut RenderPass<'_>, pipeline: &RenderPipeline) {
+        debug_assert!(
+            self.shape_buffer.iter().all(|s| s.topology == Topology::TriangleList),
+            "render_with_pipeline draws line and point shapes with the caller's pipeline"
+        );
+        self.draw(render_pass, DrawWith::Pipeline(pipeline), Runs::Shapes);
+    }
+
+    /// Draw the prepared frame as a heat map of overdraw in place of
+    /// [`Self::render`]: every fragment adds [`OVERDRAW_STEP`] to its pixel
+    /// with the same scissor rects, regardless of depth, masks or clips, so
+    /// pixels shaded by many shapes are bright. Render into a target cleared
+    /// to black, which a final shape or pass may map to false colors.
+    ///
+    /// Panics unless [`Self::set_overdraw_visualization`] was enabled before
+    /// the last [`Self::prepare`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "wgpu_cyat::render", level = "info", skip_all,
+        fields(draw_calls = self.shape_buffer.len())
+    ))]
+    pub fn render_overdraw(&self, render_pass: &mut RenderPass<'_>) {
+        let overdraw = self.overdraw.as_ref().expect("set_overdraw_visualization was enabled before prepare");
+        self.draw(render_pass, DrawWith::Overdraw(&overdraw.pipelines), Runs::Shapes);
+    }
+
+    fn draw_gpu_shapes(&self, render_pass: &mut RenderPass<'_>, with: DrawWith<'_>, runs: Runs<'_>, gpu_shapes: &GpuShapes) {
+        match with {
+            DrawWith::Own => render_pass.set_pipeline(&self.render_pipeline),
+            DrawWith::Pipeline(pipeline) => render_pass.set_pipeline(pipeline),
+            DrawWith::Overdraw(pipelines) => match pipelines.get(&Topology::TriangleList) {
+                Some(pipeline) => render_pass.set_pipeline(pipeline),
+                None => return,
+            },
+        }
+        render_pass.set_bind_group(0, &self.uniforms.bind_group, &[]);
+        render_pass.set_bind_group(1, &gpu_shapes.bind_group, &[0]);
+        render_pass.set_vertex_buffer(self.pipeline_state.vertex_buffer_slot, gpu_shapes.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(gpu_shapes.index_buffer.slice(..), IndexFormat::Uint16);
+        let target = ScissorRect::from_pos_size(0, 0, self.size.0, self.size.1);
+        if let Runs::DepthPrepass = runs {return;}
+        if let Runs::Damaged(damage) = runs {
+            for scissor in damage.iter().filter_map(|rect| rect.intersect(&target)) {
+                render_pass.set_scissor_rect(scissor.x, scissor.y, scissor.width, scissor.height);
+                render_pass.draw_indexed(0..gpu_shapes.index_count, 0, 0..1);
+            }
+        } else {
+            // The pass's scissor may be left by what drew into it before.
+            if !target.is_empty() {render_pass.set_scissor_rect(target.x, target.y, target.width, target.height);}
+            render_pass.draw_indexed(0..gpu_shapes.index_count, 0, 0..1);
+        }
+    }
+
+    /// Draw the prepared shapes in the draw calls `runs` splits them into.
+    fn draw(&self, render_pass: &mut RenderPass<'_>, with: DrawWith<'_>, runs: Runs<'_>) {
+        self.state_changes_avoided.store(0, Ordering::Relaxed);
+        if let Some(gpu_shapes) = &self.gpu_shapes {
+            self.draw_gpu_shapes(render_pass, with, runs, gpu_shapes);
+            return;
+        }
         if self.cyat_buffers.vertices.is_empty() || self.cyat_buffers.indices.is_empty() {return;}
 
-        render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.as_ref().slice(..));
-        render_pass.set_index_buffer(self.index_buffer.as_ref().slice(..), IndexFormat::Uint16);
-        for (start, end, bound) in &self.shape_buffer {
-            render_pass.set_scissor_rect(bound.0, bound.1, bound.2, bound.3);
-            render_pass.draw_indexed(*start as u32..*end as u32, 0, 0..1);
+        #[cfg(debug_assertions)]
+        if self.debug_groups {render_pass.push_debug_group("wgpu_cyat::render");}
+
+        if let DrawWith::Pipeline(pipeline) = with {render_pass.set_pipeline(pipeline);}
+        render_pass.set_bind_group(0, &self.uniforms.bind_group, &[]);
+        let own = matches!(with, DrawWith::Own);
+        let masks = self.masks.as_ref().filter(|_| own);
+        let gradients = self.gradients.as_ref().filter(|_| own);
+        let sprites = self.sprites.as_ref().filter(|_| own);
+        let uv_buffer = sprites.and_then(Sprites::uv_buffer);
+        // The first shape of each layer draws the layer over its rect, and the others nothing.
+        let layers = self.layers.as_ref().filter(|layers| own && !layers.is_empty());
+        let drawn = move |i: usize, shape: &PreparedShape| match (layers, shape.layer) {
+            (Some(layers), Some(layer)) => layers.composites_at(layer, i).then(|| layers.rect(layer)),
+            _ => Some(shape.bound),
+        };
+        // Consecutive shapes often share a pipeline and buffers, so they are only set when they change.
+        let mut last_pipeline_id = None;
+        let mut last_chunk = None;
+        let mut avoided = 0;
+        // The pass's scissor may be left by what drew into it before, so only
+        // rects set by this draw are skipped.
+        let mut last_bound = None;
+        // Render passes start with a stencil reference of 0.
+        let mut stencil_reference = 0;
+        let draws: Box<dyn Iterator<Item = (Range<usize>, ScissorRect)>> = match runs {
+            Runs::Shapes => Box::new(self.shape_buffer.iter().enumerate().filter_map(move |(i, shape)| Some((i..i + 1, drawn(i, shape)?)))),
+            Runs::Indirect(_) => Box::new(self.indirect_runs.iter().filter_map(move |run| Some((run.clone(), drawn(run.start, &self.shape_buffer[run.start])?)))),
+            Runs::Damaged(damage) => Box::new(self.shape_buffer.iter().enumerate().filter_map(move |(i, shape)| Some((i, drawn(i, shape)?))).flat_map(move |(i, bound)| {
+                damage.iter().filter_map(move |rect| Some((i..i + 1, bound.intersect(rect)?)))
+            })),
+            Runs::DepthPrepass => Box::new(self.shape_buffer.iter().enumerate().filter(|(_, shape)| shape.in_depth_prepass()).map(|(i, shape)| (i..i + 1, shape.bound))),
+        };
+        for (run, scissor) in draws {
+            // Every shape of a run shares the state set for its first.
+            let (i, shape) = (run.start, &self.shape_buffer[run.start]);
+            if let (Some(layers), Some(layer)) = (layers, shape.layer) {
+                #[cfg(debug_assertions)]
+                if self.debug_groups {render_pass.push_debug_group(&format!("layer[{layer}]"));}
+                layers.draw(render_pass, layer, scissor);
+                #[cfg(debug_assertions)]
+                if self.debug_groups {render_pass.pop_debug_group();}
+                render_pass.set_bind_group(0, &self.uniforms.bind_group, &[]);
+                (last_pipeline_id, last_bound) = (None, Some(scissor));
+                continue;
+            }
+            let chunk = &self.chunks[shape.chunk];
+            if last_chunk != Some(shape.chunk) {
+                render_pass.set_vertex_buffer(self.pipeline_state.vertex_buffer_slot, chunk.vertex_buffer.as_ref().slice(..));
+                render_pass.set_index_buffer(chunk.index_buffer.as_ref().slice(..), IndexFormat::Uint16);
+                if let Some(uv_buffer) = uv_buffer {
+                    let offset = (chunk.vertices.start * std::mem::size_of::<[f32; 2]>()) as BufferAddress;
+                    render_pass.set_vertex_buffer(self.pipeline_state.vertex_buffer_slot + 1, uv_buffer.slice(offset..));
+                }
+                last_chunk = Some(shape.chunk);
+            } else {
+                avoided += 2;
+            }
+
+            let pipeline_id = shape.pipeline_id();
+            match with {
+                DrawWith::Own if last_pipeline_id != Some(pipeline_id) => match (
+                    masks.filter(|_| shape.mask.is_some()), gradients.filter(|_| shape.gradient.is_some()), sprites.filter(|_| shape.sprite.is_some())
+                ) {
+                    (Some(masks), _, _) => render_pass.set_pipeline(masks.pipeline()),
+                    (None, Some(gradients), _) => render_pass.set_pipeline(gradients.pipeline()),
+                    (None, None, Some(sprites)) => render_pass.set_pipeline(sprites.pipeline()),
+                    (None, None, None) => match shape.variant {
+                        Some(variant) => render_pass.set_pipeline(&self.variants[variant].pipelines[&shape.topology]),
+                        None => render_pass.set_pipeline(self.pipeline(shape.topology, shape.overlay, shape.translucent)),
+                    },
+                },
+                DrawWith::Overdraw(pipelines) if last_pipeline_id.is_none_or(|last: PipelineId| last.topology != shape.topology) => {
+                    render_pass.set_pipeline(&pipelines[&shape.topology]);
+                }
+                DrawWith::Own | DrawWith::Overdraw(_) => avoided += 1,
+                DrawWith::Pipeline(_) => {}
+            }
+            last_pipeline_id = Some(pipeline_id);
+            if let (Some(masks), Some(mask)) = (masks, shape.mask) {
+                render_pass.set_bind_group(2, masks.bind_group(mask), &[]);
+            }
+            if let (Some(gradients), Some(gradient)) = (gradients, shape.gradient) {
+                render_pass.set_bind_group(2, gradients.bind_group(), &[gradients.offset(gradient)]);
+            }
+            if let (Some(sprites), Some(sprite)) = (sprites, shape.sprite) {
+                render_pass.set_bind_group(2, sprites.bind_group(sprite), &[]);
+            }
+
+            #[cfg(debug_assertions)]
+            if self.debug_groups {
+                match &shape.label {
+                    _ if run.len() > 1 => render_pass.push_debug_group(&format!("shapes[{i}..{}]", run.end)),
+                    Some(label) => render_pass.push_debug_group(&format!("shape[{i}] {label}")),
+                    None => render_pass.push_debug_group(&format!("shape[{i}]")),
+                }
+                render_pass.insert_debug_marker(&format!("draw_call_{i}"));
+            }
+
+            render_pass.set_bind_group(1, &self.shape_uniforms.bind_group, &[self.shape_uniforms.offset(i)]);
+            if shape.stencil_reference != stencil_reference {
+                stencil_reference = shape.stencil_reference;
+                render_pass.set_stencil_reference(stencil_reference);
+            }
+            if last_bound != Some(scissor) {
+                render_pass.set_scissor_rect(scissor.x, scissor.y, scissor.width, scissor.height);
+                last_bound = Some(scissor);
+            }
+            // The full viewport is restored after each shape drawn into its
+            // own, so shapes drawn after the frame are not affected either.
+            if let Some(viewport) = shape.viewport {
+                render_pass.set_viewport(viewport.x as f32, viewport.y as f32, viewport.width as f32, viewport.height as f32, 0.0, 1.0);
+            }
+            match runs {
+                Runs::Indirect(buffer) => {
+                    let offset = (run.start * std::mem::size_of::<DrawIndexedIndirectArgs>()) as BufferAddress;
+                    render_pass.multi_draw_indexed_indirect(buffer, offset, run.len() as u32);
+                }
+                Runs::Shapes | Runs::Damaged(_) | Runs::DepthPrepass => {
+                    let (start, end) = (shape.start - chunk.indices.start, shape.end - chunk.indices.start);
+                    render_pass.draw_indexed(start as u32..end as u32, (shape.vertex_start - chunk.vertices.start) as i32, 0..1);
+                }
+            }
+            if shape.viewport.is_some() {
+                render_pass.set_viewport(0.0, 0.0, self.size.0 as f32, self.size.1 as f32, 0.0, 1.0);
+            }
+
+            #[cfg(debug_assertions)]
+            if self.debug_groups {render_pass.pop_debug_group();}
         }
+
+        // Leave the reference as the pass started for what the caller draws next.
+        if stencil_reference != 0 {render_pass.set_stencil_reference(0);}
+        self.state_changes_avoided.store(avoided, Ordering::Relaxed);
+
+        #[cfg(debug_assertions)]
+        if self.debug_groups {render_pass.pop_debug_group();}
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unfillable_non_zero_path() {
+        let attributes = DefaultAttributes{color: [1.0; 3], z: 0.0, palette: 0};
+        let path = Geometry::Shape(Shape::Draw(attributes, 0.0, 0.0, vec![DrawCommand::LineTo(attributes, 1.0, 0.0), DrawCommand::LineTo(attributes, 0.0, 1.0)]), None);
+        let mut buffer = VertexBuffers::new();
+        path.build_ref(DEFAULT_TOLERANCE, FillRule::NonZero, &mut buffer);
+        let filled = (buffer.vertices.len(), buffer.indices.len());
+        assert_eq!(filled, (3, 3));
+        path.build_ref(f32::NAN, FillRule::NonZero, &mut buffer);
+        assert_eq!((buffer.vertices.len(), buffer.indices.len()), filled);
+    }
+
+    #[test]
+    fn unknown_constant() {
+        let descriptor = CyatRendererDescriptor::new(TextureFormat::Rgba8Unorm).with_constant("SRGB", 1.0);
+        assert_eq!(descriptor.unwrap_err(), CyatError::UnknownConstant("SRGB".to_string()));
+    }
+
+    #[test]
+    fn colored_attributes() {
+        assert_eq!(DefaultAttributes::from_rgb_u8(255, 0, 51).color, [1.0, 0.0, 0.2]);
+        assert_eq!(DefaultAttributes::colored(Palette::BLUE).color, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn tuple_constructor() {
+        let builder = ShapeBuilder::new(Shape::Rectangle(DefaultAttributes{color: [1.0; 3], z: 0.0, palette: 0}, -1.0, -1.0, 1.0, 1.0), None, 0.01);
+        let shape = ShapeArea(builder, (1, 2, 3, 4)).with_tolerance(0.5);
+        assert_eq!((shape.bound, shape.tolerance()), (Some(ScissorRect::from_pos_size(1, 2, 3, 4)), Some(0.5)));
+        assert!(matches!(shape.geometry, Geometry::Builder(_)));
+    }
+
+    #[test]
+    fn rotated_and_scaled() {
+        let aabb = |shape: ShapeArea| match shape.geometry {
+            Geometry::Mesh(mesh) => Aabb2D::from_points(mesh.vertices.iter().map(|v| v.position)).unwrap(),
+            _ => panic!("transformed shapes are meshes"),
+        };
+        let square = || ShapeArea::shape(Shape::Rectangle(DefaultAttributes{color: [1.0; 3], z: 0.0, palette: 0}, -0.5, -0.5, 0.5, 0.5), None, (0, 0, 1, 1));
+        let near = |a: [f32; 2], b: [f32; 2]| (a[0] - b[0]).abs() < 1e-5 && (a[1] - b[1]).abs() < 1e-5;
+
+        let rotated = aabb(square().rotated(std::f32::consts::FRAC_PI_4, [0.0, 0.0]));
+        let half_diagonal = 0.5 * std::f32::consts::SQRT_2;
+        assert!(near(rotated.min, [-half_diagonal; 2]) && near(rotated.max, [half_diagonal; 2]), "{rotated:?}");
+
+        let rotated = aabb(square().rotated(std::f32::consts::PI, [0.5, 0.5]));
+        assert!(near(rotated.min, [0.5, 0.5]) && near(rotated.max, [1.5, 1.5]), "{rotated:?}");
+
+        let scaled = aabb(square().scaled(2.0, 0.5, [-0.5, -0.5]));
+        assert!(near(scaled.min, [-0.5, -0.5]) && near(scaled.max, [1.5, 0.0]), "{scaled:?}");
+    }
+
+    fn area(bound: Option<(u32, u32, u32, u32)>) -> ShapeArea {
+        ShapeArea::from_geometry(Geometry::Mesh(VertexBuffers::new()), bound.map(ScissorRect::from))
+    }
+
+    #[test]
+    fn scissor_intersect() {
+        let rect = ScissorRect::from_pos_size(2, 2, 4, 4);
+        assert_eq!(rect.intersect(&ScissorRect::from_pos_size(4, 0, 8, 3)), Some(ScissorRect::from_pos_size(4, 2, 2, 1)));
+        assert_eq!(rect.intersect(&ScissorRect::from_pos_size(0, 0, 10, 10)), Some(rect));
+        assert_eq!(rect.intersect(&ScissorRect::from_pos_size(6, 2, 4, 4)), None, "touching edges");
+        assert_eq!(rect.intersect(&ScissorRect::from_pos_size(8, 8, 1, 1)), None);
+    }
+
+    #[test]
+    fn scissor_subtract() {
+        let rect = ScissorRect::from_pos_size(0, 0, 10, 10);
+        assert_eq!(rect.subtract(&ScissorRect::from_pos_size(2, 3, 4, 5)), vec![
+            ScissorRect::from_pos_size(0, 0, 10, 3),
+            ScissorRect::from_pos_size(0, 8, 10, 2),
+            ScissorRect::from_pos_size(0, 3, 2, 5),
+            ScissorRect::from_pos_size(6, 3, 4, 5),
+        ]);
+        assert_eq!(rect.subtract(&ScissorRect::from_pos_size(5, 0, 10, 10)), vec![ScissorRect::from_pos_size(0, 0, 5, 10)]);
+        assert_eq!(rect.subtract(&ScissorRect::from_pos_size(20, 20, 5, 5)), vec![rect]);
+        assert!(rect.subtract(&ScissorRect::from_pos_size(0, 0, 10, 10)).is_empty());
+    }
+
+    #[test]
+    fn resolved_bounds() {
+        let shapes = vec![
+            area(Some((5, 5, 30, 30))).with_parent(1),
+            area(Some((10, 10, 20, 20))),
+            area(None).with_parent(1),
+            area(Some((30, 30, 5, 5))).with_parent(1),
+            area(Some((1, 1, 2, 2))).with_parent(0),
+        ];
+        assert_eq!(resolve_bounds(&shapes).unwrap(), vec![
+            (Some(ScissorRect::from_min_max([15, 15], [30, 30])), None),
+            (Some(ScissorRect::from_pos_size(10, 10, 20, 20)), None),
+            (None, Some(ScissorRect::from_pos_size(10, 10, 20, 20))),
+            (Some(ScissorRect::default()), None),
+            (Some(ScissorRect::from_pos_size(16, 16, 2, 2)), None),
+        ]);
+    }
+
+    #[test]
+    fn unresolvable_bounds() {
+        let cycle = vec![area(Some((0, 0, 1, 1))).with_parent(1), area(Some((0, 0, 1, 1))).with_parent(0)];
+        assert_eq!(resolve_bounds(&cycle), Err(CyatError::ParentCycle(0)));
+        assert_eq!(resolve_bounds(&[area(None).with_parent(0)]), Err(CyatError::ParentCycle(0)));
+        let missing = vec![area(None), area(None).with_parent(5)];
+        assert_eq!(resolve_bounds(&missing), Err(CyatError::MissingParent{child: 1, parent: 5}));
+        let unbound = vec![area(None), area(Some((0, 0, 1, 1))).with_parent(0)];
+        assert_eq!(resolve_bounds(&unbound), Err(CyatError::UnboundParent{child: 1, parent: 0}));
+    }
+
+    #[test]
+    fn resolved_groups() {
+        let shapes = vec![area(Some((2, 2, 8, 8))).with_group_opacity(0.5), area(None).with_parent(0), area(Some((0, 0, 4, 4)))];
+        let group = Group{root: 0, rect: ScissorRect::from_pos_size(2, 2, 4, 4), opacity: 0.5};
+        assert_eq!(resolve_groups(&shapes, &resolve_bounds(&shapes).unwrap(), (6, 6)), Ok(vec![Some(group), Some(group), None]));
+
+        let nested = vec![area(Some((0, 0, 4, 4))).with_group_opacity(0.5), area(Some((0, 0, 2, 2))).with_parent(0).with_group_opacity(0.5)];
+        assert_eq!(resolve_groups(&nested, &resolve_bounds(&nested).unwrap(), (4, 4)), Err(CyatError::NestedGroup{group: 1, outer: 0}));
+        let unbound = vec![area(None).with_group_opacity(0.5)];
+        assert_eq!(resolve_groups(&unbound, &resolve_bounds(&unbound).unwrap(), (4, 4)), Err(CyatError::UnboundGroup(0)));
+    }
+
+    #[test]
+    fn occluded_shapes() {
+        let visible = |shapes: &[ShapeArea], cull_rect| {
+            let pending: Vec<_> = shapes.iter().enumerate().map(|(index, shape)| Pending{
+                index, shape, bound: (shape.bound, None), rounded_clip: None, effect: ColorEffect::default(), geometry: None, mask: None, group: None,
+            }).collect();
+            occlusion(&pending, cull_rect)
+        };
+        let below = || area(Some((0, 0, 4, 4)));
+        let left = || area(Some((0, 0, 2, 4))).as_opaque();
+        let right = || area(Some((2, 0, 2, 4))).as_opaque();
+        assert_eq!(visible(&[below(), left(), right()], None), [false, true, true]);
+        assert_eq!(visible(&[below(), left()], None), [true, true], "partly covered");
+        assert_eq!(visible(&[left(), below()], None), [true, true], "not opaque");
+        assert_eq!(visible(&[left(), below().as_opaque()], None), [false, true]);
+        assert_eq!(visible(&[left(), below().as_opaque()], Some(ScissorRect::from_pos_size(8, 8, 1, 1))), [true, true], "outside the cull rect");
+    }
+
+    #[test]
+    fn grouped_bounds() {
+        let prepared = |start, bound: (u32, u32, u32, u32)| PreparedShape{start, bound: bound.into(), ..PreparedShape::default()};
+        let order = |shapes: Vec<PreparedShape>, depth_orders| group_bounds(shapes, depth_orders).iter().map(|shape| shape.start).collect::<Vec<_>>();
+        let (left, right, overlapping) = ((0, 0, 4, 4), (4, 0, 4, 4), (2, 0, 4, 4));
+        assert_eq!(order(vec![prepared(0, left), prepared(1, right), prepared(2, left), prepared(3, left)], false), [0, 2, 3, 1]);
+        assert_eq!(order(vec![prepared(0, left), prepared(1, overlapping), prepared(2, left)], false), [0, 1, 2]);
+        assert_eq!(order(vec![prepared(0, left), prepared(1, overlapping), prepared(2, left)], true), [0, 2, 1]);
+    }
+
+    #[test]
+    fn changed_ranges() {
+        let previous = [0u8; 16];
+        assert_eq!(changed_range(&previous, &previous), None);
+        let mut contents = previous;
+        contents[5] = 1;
+        assert_eq!(changed_range(&contents, &previous), Some(4..8));
+        contents[9] = 1;
+        assert_eq!(changed_range(&contents, &previous), Some(4..12));
+        contents[15] = 1;
+        assert_eq!(changed_range(&contents, &previous), Some(4..16));
+    }
+}
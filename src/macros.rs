@@ -0,0 +1,100 @@
+//! The [`shapes!`](crate::shapes) and [`color!`](crate::color) macros.
+
+use crate::{ShapeArea, ScissorRect};
+
+/// Build a `Vec<ShapeArea>` from a list of primitives, each with its
+/// arguments followed by `;` separated options:
+///
+/// ```
+/// let shapes = wgpu_cyat::shapes![
+///     rect(-1.0, -1.0, 1.0, 2.0; color = [1.0, 0.0, 0.0]; z = 0.5; bound = (0, 0, 800, 600)),
+///     circle(0.5, 0.0, 0.25; color = [0.0, 0.0, 1.0]),
+/// ];
+/// ```
+///
+/// The primitives and the [`ShapeArea`](struct@crate::ShapeArea) constructor they use:
+///
+/// - `rect(x, y, w, h)`: [`ShapeArea::rect`] from the corner `x`, `y`
+/// - `rounded_rect(x, y, w, h, radius)`: [`ShapeArea::rounded_rect`]
+/// - `circle(cx, cy, radius)`: [`ShapeArea::circle`]
+/// - `ellipse(cx, cy, rx, ry)`: [`ShapeArea::ellipse`]
+/// - `polyline(points, width)`: [`ShapeArea::polyline`]
+///
+/// The options are `color`, black by default, `z`, see
+/// [`ShapeArea::with_z`], and `bound`, anything that converts into a
+/// [`ScissorRect`](crate::ScissorRect). Without a bound the shape is scissored
+/// to its vertices like [`ShapeArea::auto_bound`]. An unknown primitive or
+/// option fails to compile.
+#[macro_export]
+macro_rules! shapes {
+    ($($name:ident($($arg:expr),+ $(; $option:ident = $value:expr)*)),* $(,)?) => {
+        vec![$($crate::shapes!(@shape $name($($arg),+), $crate::ShapeOptions::default()$(.$option($value))*)),*]
+    };
+    (@shape rect($x:expr, $y:expr, $w:expr, $h:expr), $options:expr) => {{
+        let (options, x, y): ($crate::ShapeOptions, f32, f32) = ($options, $x, $y);
+        options.apply($crate::ShapeArea::rect([x, y, x + $w, y + $h], options.color, $crate::ScissorRect::default()))
+    }};
+    (@shape rounded_rect($x:expr, $y:expr, $w:expr, $h:expr, $radius:expr), $options:expr) => {{
+        let (options, x, y): ($crate::ShapeOptions, f32, f32) = ($options, $x, $y);
+        options.apply($crate::ShapeArea::rounded_rect([x, y, x + $w, y + $h], $radius, options.color, $crate::ScissorRect::default()))
+    }};
+    (@shape circle($cx:expr, $cy:expr, $radius:expr), $options:expr) => {{
+        let options: $crate::ShapeOptions = $options;
+        options.apply($crate::ShapeArea::circle([$cx, $cy], $radius, options.color, $crate::ScissorRect::default()))
+    }};
+    (@shape ellipse($cx:expr, $cy:expr, $rx:expr, $ry:expr), $options:expr) => {{
+        let options: $crate::ShapeOptions = $options;
+        options.apply($crate::ShapeArea::ellipse([$cx, $cy], [$rx, $ry], options.color, $crate::ScissorRect::default()))
+    }};
+    (@shape polyline($points:expr, $width:expr), $options:expr) => {{
+        let options: $crate::ShapeOptions = $options;
+        options.apply($crate::ShapeArea::polyline($points, $width, options.color, $crate::ScissorRect::default()))
+    }};
+    (@shape $name:ident($($arg:expr),+), $options:expr) => {
+        compile_error!(concat!("unknown shape `", stringify!($name), "` in shapes!, expected rect, rounded_rect, circle, ellipse or polyline"))
+    };
+}
+
+/// A [`wgpu::Color`] from CSS style hex digits, `RRGGBB` like
+/// `color!(#FF5733)` or with alpha as `RRGGBBAA`, parsed at compile time by
+/// [`Palette::hex`](crate::Palette::hex) so invalid digits fail to compile.
+#[macro_export]
+macro_rules! color {
+    (# $hex:tt) => {
+        const {$crate::Palette::hex(stringify!($hex))}
+    };
+}
+
+/// Options of a [`shapes!`](crate::shapes) primitive, one method per option.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ShapeOptions {
+    pub color: [f32; 3],
+    z: Option<f32>,
+    bound: Option<ScissorRect>,
+}
+
+impl ShapeOptions {
+    pub fn color(mut self, color: [f32; 3]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn z(mut self, z: f32) -> Self {
+        self.z = Some(z);
+        self
+    }
+
+    pub fn bound(mut self, bound: impl Into<ScissorRect>) -> Self {
+        self.bound = Some(bound.into());
+        self
+    }
+
+    /// Apply the depth and bound to `shape`, built with a placeholder bound.
+    pub fn apply(self, shape: ShapeArea) -> ShapeArea {
+        let shape = ShapeArea{bound: self.bound, ..shape};
+        match self.z {
+            Some(z) => shape.with_z(z),
+            None => shape,
+        }
+    }
+}
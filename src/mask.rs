@@ -0,0 +1,266 @@
+use wgpu::{PipelineCompilationOptions, RenderPipelineDescriptor, PipelineLayoutDescriptor, BindGroupLayoutDescriptor, BindGroupLayoutEntry, RenderPassColorAttachment, CommandEncoderDescriptor, RenderPassDescriptor, TextureSampleType, TextureViewDimension, PrimitiveTopology, TextureDescriptor, BindGroupDescriptor, BufferBindingType, BindGroupLayout, MultisampleState, TextureDimension, RenderPipeline, PrimitiveState, TextureUsages, TextureFormat, BindGroupEntry, BindingResource, FragmentState, BufferUsages, ShaderStages, BindingType, IndexFormat, TextureView, VertexState, BindGroup, Extent3d, StoreOp, Operations, Buffer, Color, LoadOp, Device, Queue};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use wgpu_dyn_buffer::{DynamicBufferDescriptor, DynamicBuffer};
+
+use cyat::VertexBuffers;
+
+use crate::{DefaultVertex, PipelineState, ScissorRect, ClipMode};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaskUniforms {
+    target_size: [f32; 2],
+    origin: [f32; 2],
+    size: [f32; 2],
+    /// Nonzero to draw outside the mask.
+    outside: u32,
+    _padding: f32,
+}
+
+struct MaskDraw {
+    start: usize,
+    end: usize,
+    vertex_start: usize,
+    bound: ScissorRect,
+    clip_mode: ClipMode,
+}
+
+/// A single channel texture covering one masked shape's bound.
+struct MaskTarget {
+    size: (u32, u32),
+    view: TextureView,
+    uniforms: Buffer,
+    pass_bind_group: BindGroup,
+    sample_bind_group: BindGroup,
+}
+
+/// Mask geometry of the prepared frame and the textures it is rendered to,
+/// created the first time a shape uses [`ShapeArea::masked_by`](crate::ShapeArea::masked_by).
+pub(crate) struct Masks {
+    pass_pipeline: RenderPipeline,
+    masked_pipeline: RenderPipeline,
+    pass_layout: BindGroupLayout,
+    sample_layout: BindGroupLayout,
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+    buffers: VertexBuffers<DefaultVertex, u16>,
+    scratch: VertexBuffers<DefaultVertex, u16>,
+    draws: Vec<MaskDraw>,
+    /// One target per draw, reused across frames by size.
+    pool: Vec<MaskTarget>,
+}
+
+impl Masks {
+    pub(crate) fn new(device: &Device, state: &PipelineState, uniforms_layout: &BindGroupLayout, shape_layout: &BindGroupLayout) -> Self {
+        debug_assert!(!state.dual_source_blending && state.bind_group_count <= 2, "prepare checks the renderer supports masked shapes");
+
+        let uniform_entry = BindGroupLayoutEntry{
+            binding: 0,
+            visibility: ShaderStages::VERTEX_FRAGMENT,
+            ty: BindingType::Buffer{ty: BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None},
+            count: None,
+        };
+        let pass_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[uniform_entry],
+        });
+        let sample_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[uniform_entry, BindGroupLayoutEntry{
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture{
+                    sample_type: TextureSampleType::Float{filterable: false},
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        let pass_shader = device.create_shader_module(wgpu::include_wgsl!("mask.wgsl"));
+        // The pass only declares the constant flipping positions.
+        let pass_constants = state.constants.iter().filter(|(name, _)| *name == "Y_DOWN").map(|(name, value)| (name.clone(), *value)).collect();
+        let pass_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor{
+            label: None,
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor{
+                bind_group_layouts: &[&pass_layout],
+                ..PipelineLayoutDescriptor::default()
+            })),
+            vertex: VertexState{
+                module: &pass_shader,
+                entry_point: Some("vs_main"),
+                compilation_options: PipelineCompilationOptions{constants: &pass_constants, ..PipelineCompilationOptions::default()},
+                buffers: &[DefaultVertex::layout()]
+            },
+            fragment: Some(FragmentState{
+                module: &pass_shader,
+                entry_point: Some("fs_main"),
+                compilation_options: PipelineCompilationOptions{constants: &pass_constants, ..PipelineCompilationOptions::default()},
+                targets: &[Some(TextureFormat::R8Unorm.into())],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache: state.cache.as_ref()
+        });
+
+        let masked_pipeline = Self::create_masked_pipeline(device, state, uniforms_layout, shape_layout, &sample_layout);
+
+        let vertex_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
+            label: None,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+
+        let index_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
+            label: None,
+            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+        });
+
+        Masks{
+            pass_pipeline,
+            masked_pipeline,
+            pass_layout,
+            sample_layout,
+            vertex_buffer,
+            index_buffer,
+            buffers: VertexBuffers::new(),
+            scratch: VertexBuffers::new(),
+            draws: Vec::new(),
+            pool: Vec::new(),
+        }
+    }
+
+    fn create_masked_pipeline(device: &Device, state: &PipelineState, uniforms_layout: &BindGroupLayout, shape_layout: &BindGroupLayout, sample_layout: &BindGroupLayout) -> RenderPipeline {
+        let masked_shader = builtin_shader!(device, "masked.wgsl");
+        let masked_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor{
+            bind_group_layouts: &[uniforms_layout, shape_layout, sample_layout],
+            ..PipelineLayoutDescriptor::default()
+        });
+        state.create_with(device, PrimitiveTopology::TriangleList, &masked_shader, &masked_layout, [Some("vs_main"), Some("fs_main")])
+    }
+
+    /// Create the pipeline drawing masked shapes again for a changed
+    /// `state`. The mask pass renders to its own textures and is kept.
+    pub(crate) fn recreate_pipeline(&mut self, device: &Device, state: &PipelineState, uniforms_layout: &BindGroupLayout, shape_layout: &BindGroupLayout) {
+        self.masked_pipeline = Self::create_masked_pipeline(device, state, uniforms_layout, shape_layout, &self.sample_layout);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.buffers.clear();
+        self.draws.clear();
+    }
+
+    /// Tessellate a mask with `build` for a shape drawn within `bound` on
+    /// the `clip_mode` side of it, returning its index.
+    pub(crate) fn push(&mut self, build: impl FnOnce(&mut VertexBuffers<DefaultVertex, u16>), bound: ScissorRect, clip_mode: ClipMode) -> usize {
+        self.scratch.clear();
+        build(&mut self.scratch);
+        self.draws.push(MaskDraw{
+            start: self.buffers.indices.len(),
+            end: self.buffers.indices.len() + self.scratch.indices.len(),
+            vertex_start: self.buffers.vertices.len(),
+            bound,
+            clip_mode,
+        });
+        self.buffers.vertices.extend_from_slice(&self.scratch.vertices);
+        self.buffers.indices.extend_from_slice(&self.scratch.indices);
+        self.draws.len() - 1
+    }
+
+    /// Render every mask pushed since the last clear into its target and
+    /// submit the work, inside a `wgpu_cyat::prepare` debug group if
+    /// `debug_group` is set. Returns false when there were none.
+    pub(crate) fn render(&mut self, device: &Device, queue: &Queue, target_size: (u32, u32), debug_group: bool) -> bool {
+        if self.draws.is_empty() {return false;}
+
+        let mut previous = std::mem::take(&mut self.pool);
+        for draw in &self.draws {
+            let size = (draw.bound.width, draw.bound.height);
+            let target = match previous.iter().position(|t| t.size == size) {
+                Some(i) => previous.swap_remove(i),
+                None => self.create_target(device, size),
+            };
+            let uniforms = MaskUniforms{
+                target_size: [target_size.0 as f32, target_size.1 as f32],
+                origin: [draw.bound.x as f32, draw.bound.y as f32],
+                size: [size.0 as f32, size.1 as f32],
+                outside: (draw.clip_mode == ClipMode::Outside) as u32,
+                _padding: 0.0,
+            };
+            queue.write_buffer(&target.uniforms, 0, bytemuck::bytes_of(&uniforms));
+            self.pool.push(target);
+        }
+
+        if !self.buffers.vertices.is_empty() && !self.buffers.indices.is_empty() {
+            self.vertex_buffer.write_buffer(device, queue, bytemuck::cast_slice(&self.buffers.vertices));
+            self.index_buffer.write_buffer(device, queue, bytemuck::cast_slice(&self.buffers.indices));
+        }
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        if debug_group {encoder.push_debug_group("wgpu_cyat::prepare");}
+        for (draw, target) in self.draws.iter().zip(&self.pool) {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor{
+                label: None,
+                color_attachments: &[Some(RenderPassColorAttachment{
+                    view: &target.view,
+                    resolve_target: None,
+                    ops: Operations{load: LoadOp::Clear(Color::TRANSPARENT), store: StoreOp::Store},
+                })],
+                ..RenderPassDescriptor::default()
+            });
+            if draw.start == draw.end {continue;}
+            render_pass.set_pipeline(&self.pass_pipeline);
+            render_pass.set_bind_group(0, &target.pass_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.as_ref().slice(..));
+            render_pass.set_index_buffer(self.index_buffer.as_ref().slice(..), IndexFormat::Uint16);
+            render_pass.draw_indexed(draw.start as u32..draw.end as u32, draw.vertex_start as i32, 0..1);
+        }
+        if debug_group {encoder.pop_debug_group();}
+        queue.submit([encoder.finish()]);
+        true
+    }
+
+    fn create_target(&self, device: &Device, size: (u32, u32)) -> MaskTarget {
+        let texture = device.create_texture(&TextureDescriptor{
+            label: None,
+            size: Extent3d{width: size.0, height: size.1, depth_or_array_layers: 1},
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        let uniforms = device.create_buffer_init(&BufferInitDescriptor{
+            label: None,
+            contents: bytemuck::bytes_of(&MaskUniforms{target_size: [0.0; 2], origin: [0.0; 2], size: [0.0; 2], outside: 0, _padding: 0.0}),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let pass_bind_group = device.create_bind_group(&BindGroupDescriptor{
+            label: None,
+            layout: &self.pass_layout,
+            entries: &[BindGroupEntry{binding: 0, resource: uniforms.as_entire_binding()}],
+        });
+        let sample_bind_group = device.create_bind_group(&BindGroupDescriptor{
+            label: None,
+            layout: &self.sample_layout,
+            entries: &[
+                BindGroupEntry{binding: 0, resource: uniforms.as_entire_binding()},
+                BindGroupEntry{binding: 1, resource: BindingResource::TextureView(&view)},
+            ],
+        });
+        MaskTarget{size, view, uniforms, pass_bind_group, sample_bind_group}
+    }
+
+    pub(crate) fn pipeline(&self) -> &RenderPipeline {
+        &self.masked_pipeline
+    }
+
+    pub(crate) fn bind_group(&self, mask: usize) -> &BindGroup {
+        &self.pool[mask].sample_bind_group
+    }
+}
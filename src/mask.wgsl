@@ -0,0 +1,29 @@
+struct MaskUniforms {
+    target_size: vec2<f32>,
+    origin: vec2<f32>,
+    size: vec2<f32>,
+    // Nonzero to draw outside the mask.
+    outside: u32,
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> mask: MaskUniforms;
+
+// Negate y of vertex positions, like the shaders drawing the masked shapes.
+override Y_DOWN: bool = false;
+
+// Maps the NDC position on the render target into the mask texture, which
+// covers only the masked shape's bound.
+@vertex
+fn vs_main(@location(0) position: vec2<f32>) -> @builtin(position) vec4<f32> {
+    let y = select(position.y, -position.y, Y_DOWN);
+    let pixel = vec2<f32>(position.x + 1.0, 1.0 - y) * 0.5 * mask.target_size;
+    let local = (pixel - mask.origin) / mask.size;
+    return vec4<f32>(local.x * 2.0 - 1.0, 1.0 - local.y * 2.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
@@ -0,0 +1,190 @@
+use cyat::{ShapeBuilder, VertexBuffers};
+
+use crate::{DefaultVertex, DefaultAttributes, Geometry, CyatError};
+
+/// Pieces smaller than this area in NDC are dropped as slivers.
+const MIN_AREA: f32 = 1e-10;
+
+/// Boolean operations on filled shapes, e.g. a rounded rectangle with a
+/// cutout, giving a [`Geometry::Mesh`] to use as a [`ShapeArea`](struct@crate::ShapeArea)'s
+/// geometry.
+///
+/// Both shapes are tessellated first, so self intersecting paths count
+/// with the even-odd rule and curves are flattened to their builder's
+/// tolerance. The result takes its colors and depths from the shape it was
+/// cut from, and fails with [`CyatError::TooManyVertices`] when it needs
+/// more vertices than a shape can index. An empty result is an empty mesh.
+pub struct PathOps;
+
+impl PathOps {
+    /// Everything inside either shape, with `a` over `b` where they overlap.
+    pub fn union(a: ShapeBuilder<DefaultAttributes>, b: ShapeBuilder<DefaultAttributes>) -> Result<Geometry, CyatError> {
+        let (a, b) = (triangles(a), triangles(b));
+        let mut pieces: Vec<Piece> = a.iter().map(|&triangle| Piece::whole(triangle)).collect();
+        pieces.extend(b.iter().flat_map(|&triangle| subtract_all(Piece::whole(triangle), &a)));
+        mesh(pieces)
+    }
+
+    /// Everything inside both shapes, colored like `a`.
+    pub fn intersection(a: ShapeBuilder<DefaultAttributes>, b: ShapeBuilder<DefaultAttributes>) -> Result<Geometry, CyatError> {
+        let (a, b) = (triangles(a), triangles(b));
+        let pieces = a.iter().flat_map(|&triangle| {
+            let piece = Piece::whole(triangle);
+            b.iter().filter_map(move |other| piece.intersect(other))
+        }).collect();
+        mesh(pieces)
+    }
+
+    /// Everything inside `a` but not `b`.
+    pub fn difference(a: ShapeBuilder<DefaultAttributes>, b: ShapeBuilder<DefaultAttributes>) -> Result<Geometry, CyatError> {
+        let (a, b) = (triangles(a), triangles(b));
+        mesh(a.iter().flat_map(|&triangle| subtract_all(Piece::whole(triangle), &b)).collect())
+    }
+}
+
+/// The triangles `builder` tessellates to, dropping degenerate ones.
+fn triangles(builder: ShapeBuilder<DefaultAttributes>) -> Vec<[DefaultVertex; 3]> {
+    let mut buffers: VertexBuffers<DefaultVertex, u16> = VertexBuffers::new();
+    builder.build(&mut buffers);
+    buffers.indices.chunks_exact(3)
+        .map(|t| [buffers.vertices[t[0] as usize], buffers.vertices[t[1] as usize], buffers.vertices[t[2] as usize]])
+        .filter(|t| area(&t.map(|v| v.position)).abs() > MIN_AREA)
+        .collect()
+}
+
+/// A convex polygon cut from a source triangle, whose vertices its
+/// attributes are interpolated from.
+struct Piece {
+    points: Vec<[f32; 2]>,
+    source: [DefaultVertex; 3],
+}
+
+impl Piece {
+    fn whole(source: [DefaultVertex; 3]) -> Self {
+        Piece{points: source.map(|v| v.position).to_vec(), source}
+    }
+
+    fn with_points(&self, points: Vec<[f32; 2]>) -> Option<Self> {
+        (points.len() >= 3 && area(&points).abs() > MIN_AREA).then_some(Piece{points, source: self.source})
+    }
+
+    /// The part of the piece inside `triangle`, if any.
+    fn intersect(&self, triangle: &[DefaultVertex; 3]) -> Option<Self> {
+        if !bounds_overlap(&self.points, triangle) {return None;}
+        let mut points = self.points.clone();
+        for (start, end) in edges(triangle) {
+            points = clip(&points, start, end, true);
+            if points.is_empty() {return None;}
+        }
+        self.with_points(points)
+    }
+
+    /// The parts of the piece outside `triangle`, as up to three convex
+    /// pieces split off along its edges.
+    fn subtract(self, triangle: &[DefaultVertex; 3]) -> Vec<Self> {
+        if !bounds_overlap(&self.points, triangle) {return vec![self];}
+        let mut outside = Vec::new();
+        let mut rest = self.points.clone();
+        for (start, end) in edges(triangle) {
+            outside.extend(self.with_points(clip(&rest, start, end, false)));
+            rest = clip(&rest, start, end, true);
+            if rest.is_empty() {break;}
+        }
+        outside
+    }
+
+    /// Fan triangulate into `mesh`, interpolating the source's attributes.
+    fn triangulate(&self, mesh: &mut VertexBuffers<DefaultVertex, u16>) {
+        let [a, b, c] = self.source.map(|v| v.position);
+        let total = area(&[a, b, c]);
+        let base = mesh.vertices.len() as u16;
+        mesh.vertices.extend(self.points.iter().map(|&p| {
+            let weights = [area(&[p, b, c]) / total, area(&[a, p, c]) / total, area(&[a, b, p]) / total];
+            let mix = |f: fn(&DefaultVertex) -> f32| self.source.iter().zip(weights).map(|(v, w)| f(v) * w).sum::<f32>();
+            DefaultVertex{
+                position: p,
+                color: [mix(|v| v.color[0]), mix(|v| v.color[1]), mix(|v| v.color[2])],
+                z: mix(|v| v.z),
+                // Slots do not blend, so the first vertex's is kept.
+                palette: self.source[0].palette,
+            }
+        }));
+        for i in 1..self.points.len() as u16 - 1 {
+            mesh.indices.extend([base, base + i, base + i + 1]);
+        }
+    }
+}
+
+/// Cut `piece` by every triangle of `triangles` in turn.
+fn subtract_all(piece: Piece, triangles: &[[DefaultVertex; 3]]) -> Vec<Piece> {
+    triangles.iter().fold(vec![piece], |pieces, triangle| {
+        pieces.into_iter().flat_map(|piece| piece.subtract(triangle)).collect()
+    })
+}
+
+fn mesh(pieces: Vec<Piece>) -> Result<Geometry, CyatError> {
+    let vertices: usize = pieces.iter().map(|piece| piece.points.len()).sum();
+    if vertices > u16::MAX as usize + 1 {return Err(CyatError::TooManyVertices(vertices));}
+    let mut mesh = VertexBuffers::new();
+    for piece in &pieces {piece.triangulate(&mut mesh);}
+    Ok(Geometry::Mesh(mesh))
+}
+
+/// Edges of `triangle` in counterclockwise order, so its inside is on
+/// their left.
+fn edges(triangle: &[DefaultVertex; 3]) -> [([f32; 2], [f32; 2]); 3] {
+    let [a, b, c] = triangle.map(|v| v.position);
+    let [a, b, c] = if area(&[a, b, c]) < 0.0 {[a, c, b]} else {[a, b, c]};
+    [(a, b), (b, c), (c, a)]
+}
+
+/// One Sutherland-Hodgman step: the part of convex `polygon` on the left
+/// of the line from `start` to `end` when `inside`, else on its right.
+fn clip(polygon: &[[f32; 2]], start: [f32; 2], end: [f32; 2], inside: bool) -> Vec<[f32; 2]> {
+    let sign = if inside {1.0} else {-1.0};
+    let side = |p: [f32; 2]| sign * ((end[0] - start[0]) * (p[1] - start[1]) - (end[1] - start[1]) * (p[0] - start[0]));
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, &current) in polygon.iter().enumerate() {
+        let next = polygon[(i + 1) % polygon.len()];
+        let (d_current, d_next) = (side(current), side(next));
+        if d_current >= 0.0 {clipped.push(current);}
+        if (d_current >= 0.0) != (d_next >= 0.0) {
+            let t = d_current / (d_current - d_next);
+            clipped.push([current[0] + (next[0] - current[0]) * t, current[1] + (next[1] - current[1]) * t]);
+        }
+    }
+    clipped
+}
+
+/// Signed area, positive when counterclockwise.
+fn area(points: &[[f32; 2]]) -> f32 {
+    let mut sum = 0.0;
+    for (i, p) in points.iter().enumerate() {
+        let q = points[(i + 1) % points.len()];
+        sum += p[0] * q[1] - q[0] * p[1];
+    }
+    sum / 2.0
+}
+
+fn bounds_overlap(points: &[[f32; 2]], triangle: &[DefaultVertex; 3]) -> bool {
+    let bounds = |points: &mut dyn Iterator<Item = [f32; 2]>| points.fold([f32::MAX, f32::MAX, f32::MIN, f32::MIN], |[l, b, r, t], p| {
+        [l.min(p[0]), b.min(p[1]), r.max(p[0]), t.max(p[1])]
+    });
+    let [l, b, r, t] = bounds(&mut points.iter().copied());
+    let [l2, b2, r2, t2] = bounds(&mut triangle.iter().map(|v| v.position));
+    l < r2 && l2 < r && b < t2 && b2 < t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cyat::Shape;
+
+    #[test]
+    fn disjoint_intersection() {
+        let rect = |min: f32, max: f32| ShapeBuilder::new(Shape::Rectangle(DefaultAttributes{color: [1.0, 0.0, 0.0], z: 0.0, palette: 0}, min, min, max, max), None, 0.001);
+        let Geometry::Mesh(disjoint) = PathOps::intersection(rect(-1.0, -0.5), rect(0.5, 1.0)).unwrap() else {panic!("not a mesh")};
+        assert!(disjoint.vertices.is_empty() && disjoint.indices.is_empty());
+    }
+}
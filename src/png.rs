@@ -0,0 +1,238 @@
+//! Just enough PNG for golden images: 8 bit RGB and RGBA, not interlaced.
+//! Encoding stores the pixels uncompressed, decoding inflates any stream.
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| if crc & 1 == 1 {(crc >> 1) ^ 0xedb8_8320} else {crc >> 1})
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (a, b) = bytes.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// PNG of `width` x `height` RGBA pixels.
+pub(crate) fn encode(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(rgba.len() + height as usize);
+    for row in rgba.chunks_exact(width.max(1) as usize * 4) {
+        raw.push(0);
+        raw.extend(row);
+    }
+
+    // Zlib header without a preset dictionary, then stored deflate blocks.
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = if raw.is_empty() {vec![&[]]} else {raw.chunks(0xffff).collect()};
+    for (i, &block) in blocks.iter().enumerate() {
+        zlib.push((i + 1 == blocks.len()) as u8);
+        zlib.extend((block.len() as u16).to_le_bytes());
+        zlib.extend((!(block.len() as u16)).to_le_bytes());
+        zlib.extend(block);
+    }
+    zlib.extend(adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    header.extend([8, 6, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib);
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// Size and RGBA pixels of a PNG.
+pub(crate) fn decode(png: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+    let mut rest = png.strip_prefix(&SIGNATURE[..]).ok_or("not a PNG")?;
+    let mut header = None;
+    let mut zlib = Vec::new();
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        if rest.len() < len + 12 {return Err("truncated chunk".into());}
+        let (kind, data) = (&rest[4..8], &rest[8..8 + len]);
+        match kind {
+            b"IHDR" if len == 13 => header = Some(data.to_vec()),
+            b"IDAT" => zlib.extend(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = &rest[len + 12..];
+    }
+    let header = header.ok_or("missing IHDR")?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let channels = match (header[8], header[9], header[12]) {
+        (8, 6, 0) => 4,
+        (8, 2, 0) => 3,
+        (depth, color, interlace) => return Err(format!("unsupported bit depth {depth}, color type {color} or interlace {interlace}")),
+    };
+
+    if zlib.len() < 2 || zlib[0] & 0x0f != 8 || zlib[1] & 0x20 != 0 {return Err("unsupported zlib stream".into());}
+    let raw = inflate(&zlib[2..])?;
+
+    let stride = width as usize * channels;
+    if raw.len() < (stride + 1) * height as usize {return Err("not enough image data".into());}
+    let mut pixels = vec![0u8; stride * height as usize];
+    for y in 0..height as usize {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        let (done, current) = pixels.split_at_mut(y * stride);
+        let above = done.get(done.len().saturating_sub(stride)..).filter(|_| y > 0);
+        let current = &mut current[..stride];
+        for x in 0..stride {
+            let a = if x >= channels {current[x - channels]} else {0};
+            let b = above.map_or(0, |above| above[x]);
+            let c = if x >= channels {above.map_or(0, |above| above[x - channels])} else {0};
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => {
+                    let p = a as i16 + b as i16 - c as i16;
+                    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+                    if pa <= pb && pa <= pc {a} else if pb <= pc {b} else {c}
+                }
+                filter => return Err(format!("unknown filter {filter}")),
+            };
+            current[x] = line[x].wrapping_add(predicted);
+        }
+    }
+
+    if channels == 3 {
+        pixels = pixels.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect();
+    }
+    Ok((width, height, pixels))
+}
+
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self.data.get(self.position / 8).ok_or("unexpected end of deflate stream")?;
+            value |= ((byte >> (self.position % 8)) as u32 & 1) << i;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+}
+
+/// Canonical Huffman code as the number of codes of each length and the
+/// symbols ordered by code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for &len in lengths {counts[len as usize] += 1;}
+        counts[0] = 0;
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16).filter(|&s| lengths[s as usize] != 0).collect();
+        symbols.sort_by_key(|&s| lengths[s as usize]);
+        Huffman{counts, symbols}
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {return Ok(self.symbols[(index + code - first) as usize]);}
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".into())
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut bits = Bits{data, position: 0};
+    let mut out = Vec::new();
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                let start = bits.position.div_ceil(8);
+                let header = data.get(start..start + 4).ok_or("truncated stored block")?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                out.extend(data.get(start + 4..start + 4 + len).ok_or("truncated stored block")?);
+                bits.position = (start + 4 + len) * 8;
+            }
+            kind @ (1 | 2) => {
+                let (literals, distances) = if kind == 1 {
+                    let mut lengths = [8u8; 288];
+                    lengths[144..256].fill(9);
+                    lengths[256..280].fill(7);
+                    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+                } else {
+                    let literal_count = bits.bits(5)? as usize + 257;
+                    let distance_count = bits.bits(5)? as usize + 1;
+                    let code_count = bits.bits(4)? as usize + 4;
+                    let mut code_lengths = [0u8; 19];
+                    for &i in &CODE_LENGTH_ORDER[..code_count] {code_lengths[i] = bits.bits(3)? as u8;}
+                    let codes = Huffman::new(&code_lengths);
+                    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                    while lengths.len() < literal_count + distance_count {
+                        let (value, repeat) = match codes.decode(&mut bits)? {
+                            symbol @ 0..=15 => (symbol as u8, 1),
+                            16 => (*lengths.last().ok_or("repeat without a length")?, 3 + bits.bits(2)?),
+                            17 => (0, 3 + bits.bits(3)?),
+                            _ => (0, 11 + bits.bits(7)?),
+                        };
+                        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+                    }
+                    if lengths.len() > literal_count + distance_count {return Err("code lengths overflow".into());}
+                    (Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..]))
+                };
+                loop {
+                    match literals.decode(&mut bits)? {
+                        symbol @ 0..=255 => out.push(symbol as u8),
+                        256 => break,
+                        symbol => {
+                            let i = symbol as usize - 257;
+                            if i >= LENGTH_BASE.len() {return Err("invalid length code".into());}
+                            let length = LENGTH_BASE[i] as usize + bits.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                            let j = distances.decode(&mut bits)? as usize;
+                            if j >= DISTANCE_BASE.len() {return Err("invalid distance code".into());}
+                            let distance = DISTANCE_BASE[j] as usize + bits.bits(DISTANCE_EXTRA[j] as u32)? as usize;
+                            if distance > out.len() {return Err("distance past the start of the stream".into());}
+                            for _ in 0..length {out.push(out[out.len() - distance]);}
+                        }
+                    }
+                }
+            }
+            _ => return Err("invalid deflate block type".into()),
+        }
+        if last {return Ok(out);}
+    }
+}
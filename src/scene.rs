@@ -0,0 +1,137 @@
+use wgpu::{RenderPass, Device, Queue};
+
+use std::collections::HashSet;
+
+use cyat::VertexBuffers;
+
+use crate::{CyatRenderer, FrameStats, ShapeArea, CyatError, Geometry};
+
+/// Key of a shape in a [`CyatSceneGraph`], chosen by the caller.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShapeId(pub usize);
+
+/// Shapes that persist between frames, so only the ones inserted or updated
+/// since the last prepare are tessellated again.
+///
+/// Shapes are drawn by ascending [`ShapeId`] within each
+/// [`ShapeArea::z_index`]. A [`ShapeArea::parent`] names the parent's
+/// [`ShapeId`] rather than an index, and [`CyatError`]s refer to ids the same
+/// way. Shapes are tessellated with the renderer's tolerance at the time of
+/// the prepare that first sees them; update them to pick up a new one.
+pub struct CyatSceneGraph {
+    renderer: CyatRenderer,
+    /// Ids in ascending order, each at the index of its shape.
+    ids: Vec<ShapeId>,
+    /// The inserted shapes, their geometry and mask replaced by meshes once
+    /// tessellated and their parent by the parent's index at each prepare.
+    shapes: Vec<ShapeArea>,
+    /// The parent id each shape was inserted with.
+    parents: Vec<Option<usize>>,
+    dirty: HashSet<ShapeId>,
+}
+
+impl CyatSceneGraph {
+    pub fn new(renderer: CyatRenderer) -> Self {
+        CyatSceneGraph{renderer, ids: Vec::new(), shapes: Vec::new(), parents: Vec::new(), dirty: HashSet::new()}
+    }
+
+    pub fn renderer(&self) -> &CyatRenderer {
+        &self.renderer
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut CyatRenderer {
+        &mut self.renderer
+    }
+
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    pub fn contains(&self, id: ShapeId) -> bool {
+        self.ids.binary_search(&id).is_ok()
+    }
+
+    /// Add `shape` under `id`, replacing any shape already there.
+    pub fn insert(&mut self, id: ShapeId, shape: ShapeArea) -> ShapeId {
+        let parent = shape.parent;
+        match self.ids.binary_search(&id) {
+            Ok(i) => (self.shapes[i], self.parents[i]) = (shape, parent),
+            Err(i) => {
+                self.ids.insert(i, id);
+                self.shapes.insert(i, shape);
+                self.parents.insert(i, parent);
+            }
+        }
+        self.dirty.insert(id);
+        id
+    }
+
+    /// Replace the shape under `id`.
+    ///
+    /// Panics if there is none.
+    pub fn update(&mut self, id: ShapeId, new_shape: ShapeArea) {
+        assert!(self.contains(id), "no shape with {id:?}");
+        self.insert(id, new_shape);
+    }
+
+    /// Remove the shape under `id`, returning whether there was one.
+    pub fn remove(&mut self, id: ShapeId) -> bool {
+        self.dirty.remove(&id);
+        let Ok(i) = self.ids.binary_search(&id) else {return false;};
+        self.ids.remove(i);
+        self.shapes.remove(i);
+        self.parents.remove(i);
+        true
+    }
+
+    /// Tessellate the shapes inserted or updated since the last prepare,
+    /// then prepare the renderer with every shape through
+    /// [`CyatRenderer::prepare_ref`], which copies the cached meshes into the
+    /// frame's buffers without cloning the shapes. Uploads are as for any
+    /// prepare, see [`CyatRenderer::prepare`].
+    pub fn prepare(&mut self, device: &Device, queue: &Queue) -> Result<FrameStats, CyatError> {
+        if let Some(&ShapeId(id)) = self.dirty.iter().find(|&&id| self.shapes[self.ids.binary_search(&id).unwrap()].fills_builder_non_zero()) {
+            return Err(CyatError::UnsupportedFillRule(id));
+        }
+        for id in self.dirty.drain() {
+            let shape = &mut self.shapes[self.ids.binary_search(&id).unwrap()];
+            let (tolerance, fill_rule) = (shape.tolerance.unwrap_or(self.renderer.tolerance), shape.fill_rule);
+            let geometry = std::mem::replace(&mut shape.geometry, Geometry::Mesh(VertexBuffers::new()));
+            shape.geometry = Geometry::Mesh(geometry.into_mesh(tolerance, fill_rule));
+            shape.mask = shape.mask.take().map(|mask| Geometry::Mesh(mask.into_mesh(tolerance, fill_rule)));
+        }
+
+        let ids = &self.ids;
+        for ((shape, parent), &ShapeId(child)) in self.shapes.iter_mut().zip(&self.parents).zip(ids) {
+            shape.parent = match *parent {
+                Some(parent) => Some(ids.binary_search(&ShapeId(parent)).map_err(|_| CyatError::MissingParent{child, parent})?),
+                None => None,
+            };
+        }
+
+        self.renderer.prepare_ref(device, queue, &self.shapes).map_err(|error| match error {
+            CyatError::MissingParent{child, parent} => CyatError::MissingParent{child: ids[child].0, parent},
+            CyatError::ParentCycle(shape) => CyatError::ParentCycle(ids[shape].0),
+            CyatError::InvalidDepth(shape) => CyatError::InvalidDepth(ids[shape].0),
+            CyatError::ShapeTooLarge{shape, size, limit} => CyatError::ShapeTooLarge{shape: ids[shape].0, size, limit},
+            CyatError::UnboundParent{child, parent} => CyatError::UnboundParent{child: ids[child].0, parent: ids[parent].0},
+            CyatError::UnboundGroup(shape) => CyatError::UnboundGroup(ids[shape].0),
+            CyatError::InvalidViewport(shape) => CyatError::InvalidViewport(ids[shape].0),
+            CyatError::UnsupportedQuantized(shape) => CyatError::UnsupportedQuantized(ids[shape].0),
+            CyatError::UnsupportedEffect(shape) => CyatError::UnsupportedEffect(ids[shape].0),
+            CyatError::NestedGroup{group, outer} => CyatError::NestedGroup{group: ids[group].0, outer: ids[outer].0},
+            CyatError::UnsupportedGroupShape(shape) => CyatError::UnsupportedGroupShape(ids[shape].0),
+            CyatError::UnsupportedGroup(shape) => CyatError::UnsupportedGroup(ids[shape].0),
+            error => error,
+        })
+    }
+
+    /// See [`CyatRenderer::render`].
+    pub fn render(&self, render_pass: &mut RenderPass<'_>) {
+        self.renderer.render(render_pass);
+    }
+}
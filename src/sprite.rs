@@ -0,0 +1,263 @@
+use wgpu::{PipelineLayoutDescriptor, BindGroupLayoutDescriptor, BindGroupLayoutEntry, TextureViewDescriptor, TextureViewDimension, TextureSampleType, SamplerBindingType, BindGroupDescriptor, PrimitiveTopology, TextureDescriptor, SamplerDescriptor, VertexBufferLayout, BindGroupLayout, TextureDimension, RenderPipeline, VertexStepMode, BufferAddress, TextureUsages, TextureFormat, BindGroupEntry, BindingResource, BufferUsages, ShaderStages, BindingType, AddressMode, TextureView, FilterMode, BindGroup, Extent3d, Texture, Sampler, Buffer, Device, Queue};
+use wgpu::util::{TextureDataOrder, DeviceExt};
+
+use wgpu_dyn_buffer::{DynamicBufferDescriptor, DynamicBuffer};
+
+use cyat::{VertexBuffers, Vertex};
+
+use std::sync::Arc;
+
+use crate::{PipelineState, DefaultVertex, DefaultAttributes, ShapeArea, ScissorRect, Geometry, UploadStrategy, write_changed};
+
+/// An image that shapes can sample through [`ShapeArea::sprite`], such as a
+/// glyph atlas.
+pub struct SpriteTexture {
+    view: TextureView,
+    size: (u32, u32),
+}
+
+impl SpriteTexture {
+    /// Upload `rgba`, tightly packed sRGB encoded rows of `width` x `height`
+    /// pixels with straight alpha.
+    pub fn new(device: &Device, queue: &Queue, width: u32, height: u32, rgba: &[u8]) -> Self {
+        assert_eq!(rgba.len(), (width * height * 4) as usize, "rgba does not match a {width}x{height} image");
+        let texture = device.create_texture_with_data(queue, &TextureDescriptor{
+            label: None,
+            size: Extent3d{width, height, depth_or_array_layers: 1},
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        }, TextureDataOrder::LayerMajor, rgba);
+        Self::from_texture(&texture)
+    }
+
+    /// Sample a texture the caller created and keeps up to date, e.g. an
+    /// atlas glyphs are added to over time. It needs
+    /// [`TextureUsages::TEXTURE_BINDING`] and a filterable float format.
+    pub fn from_texture(texture: &Texture) -> Self {
+        SpriteTexture{view: texture.create_view(&TextureViewDescriptor::default()), size: (texture.width(), texture.height())}
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+}
+
+/// Texture coordinates for each vertex of a shape's geometry, see
+/// [`ShapeArea::sprite`].
+#[derive(Clone)]
+pub struct Sprite {
+    pub texture: Arc<SpriteTexture>,
+    /// In `0.0..=1.0` across the texture from its top left, in the order of
+    /// the tessellated vertices. Missing ones are `[0.0, 0.0]`.
+    pub uvs: Vec<[f32; 2]>,
+}
+
+/// A corner of a glyph quad, see [`GlyphBatch`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TexturedVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    /// sRGB encoded, like [`DefaultAttributes::color`].
+    pub color: [f32; 3],
+}
+
+/// Glyphs rasterized by the caller into a texture, collected into one mesh
+/// so a line or paragraph of text is a single draw, see
+/// [`ShapeArea::from_glyph_batch`].
+#[derive(Clone, Debug, Default)]
+pub struct GlyphBatch {
+    vertices: Vec<TexturedVertex>,
+    indices: Vec<u16>,
+}
+
+impl GlyphBatch {
+    /// Most glyphs a batch can hold, as its indices are 16 bit.
+    pub const MAX_GLYPHS: usize = (u16::MAX as usize + 1) / 4;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a quad covering `world_rect`, `[x, y, x2, y2]` in NDC, that shows
+    /// `uv_rect`, `[u, v, u2, v2]` of the texture, multiplied by `color`.
+    /// The corners at `(x, y)` and `(u, v)` match.
+    ///
+    /// Panics past [`Self::MAX_GLYPHS`]; split longer text into batches.
+    pub fn add_glyph(&mut self, uv_rect: [f32; 4], world_rect: [f32; 4], color: [f32; 3]) {
+        assert!(self.len() < Self::MAX_GLYPHS, "a glyph batch holds at most {} glyphs", Self::MAX_GLYPHS);
+        let [u, v, u2, v2] = uv_rect;
+        let [x, y, x2, y2] = world_rect;
+        let base = self.vertices.len() as u16;
+        self.vertices.extend([([x, y], [u, v]), ([x2, y], [u2, v]), ([x2, y2], [u2, v2]), ([x, y2], [u, v2])]
+            .map(|(position, uv)| TexturedVertex{position, uv, color}));
+        self.indices.extend([0, 1, 2, 0, 2, 3].map(|i| base + i));
+    }
+
+    pub fn len(&self) -> usize {
+        self.vertices.len() / 4
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn vertices(&self) -> &[TexturedVertex] {
+        &self.vertices
+    }
+}
+
+impl ShapeArea {
+    /// The glyphs of `batch` drawn from `texture`, scissored to `bound`.
+    /// Texels multiply the glyph colors, so an atlas of white glyphs with
+    /// coverage in alpha takes any color; they only blend into the target
+    /// with a [`CyatRendererDescriptor::blend`](crate::CyatRendererDescriptor::blend).
+    pub fn from_glyph_batch(batch: GlyphBatch, texture: Arc<SpriteTexture>, bound: impl Into<ScissorRect>) -> Self {
+        let GlyphBatch{vertices, indices} = batch;
+        let uvs = vertices.iter().map(|v| v.uv).collect();
+        let vertices = vertices.iter().map(|v| DefaultVertex::construct(v.position, DefaultAttributes{color: v.color, z: 0.0, palette: 0})).collect();
+        let mut shape = ShapeArea::from_geometry(Geometry::Mesh(VertexBuffers{vertices, indices}), Some(bound.into()));
+        shape.sprite = Some(Sprite{texture, uvs});
+        shape
+    }
+}
+
+/// The textured pipeline and the texture coordinates of the prepared frame,
+/// created the first time a shape has a sprite. Coordinates are kept in
+/// step with the renderer's vertex buffer and bound after it, so sprite
+/// shapes draw from the same ranges as every other shape.
+pub(crate) struct Sprites {
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    uv_buffer: DynamicBuffer,
+    uvs: Vec<[f32; 2]>,
+    previous_uvs: Vec<[f32; 2]>,
+    /// Textures of the prepared frame, reusing the bind groups of the
+    /// previous one.
+    textures: Vec<(Arc<SpriteTexture>, BindGroup)>,
+    previous_textures: Vec<(Arc<SpriteTexture>, BindGroup)>,
+}
+
+impl Sprites {
+    const UV_ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![4 => Float32x2];
+
+    pub(crate) fn new(device: &Device, state: &PipelineState, uniforms_layout: &BindGroupLayout, shape_layout: &BindGroupLayout) -> Self {
+        debug_assert!(!state.dual_source_blending && state.bind_group_count <= 2, "prepare checks the renderer supports sprites");
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[BindGroupLayoutEntry{
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture{
+                    sample_type: TextureSampleType::Float{filterable: true},
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }, BindGroupLayoutEntry{
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            }],
+        });
+        let pipeline = Self::create_pipeline(device, state, uniforms_layout, shape_layout, &layout);
+
+        let sampler = device.create_sampler(&SamplerDescriptor{
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+        let uv_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor{
+            label: None,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+        Sprites{pipeline, layout, sampler, uv_buffer, uvs: Vec::new(), previous_uvs: Vec::new(), textures: Vec::new(), previous_textures: Vec::new()}
+    }
+
+    fn create_pipeline(device: &Device, state: &PipelineState, uniforms_layout: &BindGroupLayout, shape_layout: &BindGroupLayout, layout: &BindGroupLayout) -> RenderPipeline {
+        let shader = builtin_shader!(device, "sprite.wgsl");
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor{
+            bind_group_layouts: &[uniforms_layout, shape_layout, layout],
+            ..PipelineLayoutDescriptor::default()
+        });
+        let uv_layout = VertexBufferLayout{
+            array_stride: std::mem::size_of::<[f32; 2]>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &Self::UV_ATTRIBS,
+        };
+        state.create_with_buffers(device, PrimitiveTopology::TriangleList, &shader, &pipeline_layout, [Some("vs_main"), Some("fs_main")], &[uv_layout])
+    }
+
+    /// Create the pipeline again for a changed `state`, keeping the frame's
+    /// coordinates and textures.
+    pub(crate) fn recreate_pipeline(&mut self, device: &Device, state: &PipelineState, uniforms_layout: &BindGroupLayout, shape_layout: &BindGroupLayout) {
+        self.pipeline = Self::create_pipeline(device, state, uniforms_layout, shape_layout, &self.layout);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        std::mem::swap(&mut self.uvs, &mut self.previous_uvs);
+        std::mem::swap(&mut self.textures, &mut self.previous_textures);
+        self.uvs.clear();
+        self.textures.clear();
+    }
+
+    /// Add the coordinates of `sprite` for the vertices from `vertex_start`
+    /// to `vertex_end`, returning the index of its texture.
+    pub(crate) fn push(&mut self, device: &Device, sprite: &Sprite, vertex_start: usize, vertex_end: usize) -> usize {
+        self.uvs.resize(vertex_start, [0.0; 2]);
+        self.uvs.extend(sprite.uvs.iter().copied().chain(std::iter::repeat([0.0; 2])).take(vertex_end - vertex_start));
+
+        if let Some(index) = self.textures.iter().position(|(texture, _)| Arc::ptr_eq(texture, &sprite.texture)) {
+            return index;
+        }
+        let bind_group = match self.previous_textures.iter().find(|(texture, _)| Arc::ptr_eq(texture, &sprite.texture)) {
+            Some((_, bind_group)) => bind_group.clone(),
+            None => device.create_bind_group(&BindGroupDescriptor{
+                label: None,
+                layout: &self.layout,
+                entries: &[
+                    BindGroupEntry{binding: 0, resource: BindingResource::TextureView(&sprite.texture.view)},
+                    BindGroupEntry{binding: 1, resource: BindingResource::Sampler(&self.sampler)},
+                ],
+            }),
+        };
+        self.textures.push((sprite.texture.clone(), bind_group));
+        self.textures.len() - 1
+    }
+
+    /// Upload the coordinates pushed since the last clear for a frame of
+    /// `vertex_count` vertices, returning whether they or the textures
+    /// differ from the previous frame's.
+    pub(crate) fn write(&mut self, device: &Device, queue: &Queue, vertex_count: usize, strategy: UploadStrategy) -> bool {
+        if self.textures.is_empty() {self.uvs.clear();} else {self.uvs.resize(vertex_count, [0.0; 2]);}
+        let textures_changed = self.textures.len() != self.previous_textures.len() ||
+            self.textures.iter().zip(&self.previous_textures).any(|((a, _), (b, _))| !Arc::ptr_eq(a, b));
+        if self.uvs == self.previous_uvs {return textures_changed;}
+        if !self.uvs.is_empty() {
+            write_changed(&mut self.uv_buffer, device, queue, bytemuck::cast_slice(&self.uvs), bytemuck::cast_slice(&self.previous_uvs), strategy);
+        }
+        true
+    }
+
+    pub(crate) fn pipeline(&self) -> &RenderPipeline {
+        &self.pipeline
+    }
+
+    pub(crate) fn bind_group(&self, texture: usize) -> &BindGroup {
+        &self.textures[texture].1
+    }
+
+    /// `None` when no vertex of the frame has coordinates.
+    pub(crate) fn uv_buffer(&self) -> Option<&Buffer> {
+        (!self.uvs.is_empty()).then(|| self.uv_buffer.as_ref())
+    }
+}
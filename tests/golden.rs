@@ -0,0 +1,1037 @@
+use std::path::Path;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use wgpu::{MultisampleState, BlendState, TextureFormat, Color, DepthStencilState, StencilState, StencilFaceState, StencilOperation, CompareFunction, DepthBiasState, Features, Maintain, RenderPass, BufferDescriptor, BufferUsages, BindGroupLayoutDescriptor, Device, Queue, ShaderModuleDescriptor, ShaderSource, PipelineLayoutDescriptor, RenderPipelineDescriptor, VertexState, FragmentState, PrimitiveState};
+
+use wgpu_cyat::{shapes, color, harness, Palette, CyatRenderer, ScissorRect, UploadStrategy, CyatError, CyatRendererDescriptor, ShapeArea, DefaultAttributes, GradientType, ConicalGradient, GradientInterpolation, MeshGradient, NoiseFill, GradientStop, SpriteTexture, GlyphBatch, PathOps, FillGeometry, FillRule, EmptyFrame, Geometry, CyatSceneGraph, ShapeId, DefaultVertex, YAxis, MAX_VIEWS};
+use wgpu_cyat::cyat::{ShapeBuilder, Shape, DrawCommand, Vertex, VertexBuffers};
+
+const SIZE: u32 = 64;
+
+const NO_ADAPTER: &str = "the golden tests need a software adapter, like lavapipe or WARP";
+
+/// The harness's software device. Without one every test fails rather than
+/// passing without checking anything.
+fn device() -> (Device, Queue) {
+    device_with(Features::empty())
+}
+
+/// Like [`device`], enabling those of `features` the adapter has.
+fn device_with(features: Features) -> (Device, Queue) {
+    harness::software_device_with(features).expect(NO_ADAPTER)
+}
+
+fn rect(color: [f32; 3], min: [f32; 2], max: [f32; 2], bound: (u32, u32, u32, u32)) -> ShapeArea {
+    ShapeArea::shape(Shape::Rectangle(DefaultAttributes{color, z: 0.0, palette: 0}, min[0], min[1], max[0], max[1]), None, bound)
+}
+
+fn check(name: &str, shapes: Vec<ShapeArea>) {
+    let (device, queue) = device();
+    let pixels = harness::render(&device, &queue, shapes, SIZE, SIZE).unwrap();
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{name}.png"));
+    if let Err(error) = harness::compare_png(&pixels, SIZE, SIZE, golden, 0) {
+        panic!("{name}: {error}");
+    }
+}
+
+#[test]
+fn solid_rect() {
+    check("solid_rect", vec![
+        rect([1.0, 0.0, 0.0], [-0.5, -0.5], [0.5, 0.5], (0, 0, SIZE, SIZE)),
+    ]);
+}
+
+#[test]
+fn overlapping_shapes() {
+    check("overlapping_shapes", vec![
+        rect([1.0, 0.0, 0.0], [-0.75, -0.75], [0.25, 0.25], (0, 0, SIZE, SIZE)),
+        rect([0.0, 0.0, 1.0], [-0.25, -0.25], [0.75, 0.75], (0, 0, SIZE, SIZE)),
+    ]);
+}
+
+#[test]
+fn scissor_clipping() {
+    check("scissor_clipping", vec![
+        rect([0.0, 1.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (8, 16, 24, 32)),
+    ]);
+}
+
+/// A five pointed star drawn in one stroke, whose center is wound twice.
+fn star(fill_rule: FillRule) -> Vec<ShapeArea> {
+    let attributes = DefaultAttributes{color: [1.0, 1.0, 0.0], z: 0.0, palette: 0};
+    let point = |i: usize| {
+        let angle = std::f32::consts::FRAC_PI_2 + (i * 2 % 5) as f32 * std::f32::consts::TAU / 5.0;
+        (0.9 * angle.cos(), 0.9 * angle.sin())
+    };
+    let commands = (1..5).map(|i| {
+        let (x, y) = point(i);
+        DrawCommand::LineTo(attributes, x, y)
+    }).collect();
+    let (x, y) = point(0);
+    vec![ShapeArea::shape(Shape::Draw(attributes, x, y, commands), None, (0, 0, SIZE, SIZE)).with_fill_rule(fill_rule)]
+}
+
+/// A square with a square loop inside wound the same way, joined by a
+/// bridge traced there and back.
+fn square_with_hole(fill_rule: FillRule) -> Vec<ShapeArea> {
+    let attributes = DefaultAttributes{color: [0.0, 1.0, 1.0], z: 0.0, palette: 0};
+    let line = |x, y| DrawCommand::LineTo(attributes, x, y);
+    let commands = vec![
+        line(0.75, -0.75), line(0.75, 0.75), line(-0.75, 0.75), line(-0.75, -0.75),
+        line(-0.25, -0.25), line(0.25, -0.25), line(0.25, 0.25), line(-0.25, 0.25), line(-0.25, -0.25),
+    ];
+    vec![ShapeArea::shape(Shape::Draw(attributes, -0.75, -0.75, commands), None, (0, 0, SIZE, SIZE)).with_fill_rule(fill_rule)]
+}
+
+#[test]
+fn star_even_odd() {
+    check("star_even_odd", star(FillRule::EvenOdd));
+}
+
+#[test]
+fn star_non_zero() {
+    check("star_non_zero", star(FillRule::NonZero));
+}
+
+#[test]
+fn builder_non_zero() {
+    let (device, queue) = device();
+    let path = || {
+        let shape = star(FillRule::EvenOdd).pop().unwrap();
+        let Geometry::Shape(path, None) = shape.geometry else {panic!("star is a path")};
+        ShapeBuilder::new(path, None, 0.001)
+    };
+    let shapes = |fill_rule| vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)),
+        ShapeArea::new(path(), (0, 0, SIZE, SIZE)).with_fill_rule(fill_rule),
+    ];
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    renderer.resize(SIZE, SIZE);
+    assert_eq!(renderer.prepare(&device, &queue, shapes(FillRule::NonZero)), Err(CyatError::UnsupportedFillRule(1)));
+    assert!(renderer.prepare(&device, &queue, shapes(FillRule::EvenOdd)).is_ok());
+
+    let mut scene = CyatSceneGraph::new(renderer);
+    scene.insert(ShapeId(7), ShapeArea::new(path(), (0, 0, SIZE, SIZE)).with_fill_rule(FillRule::NonZero));
+    assert_eq!(scene.prepare(&device, &queue), Err(CyatError::UnsupportedFillRule(7)));
+}
+
+#[test]
+fn square_with_hole_even_odd() {
+    check("square_with_hole_even_odd", square_with_hole(FillRule::EvenOdd));
+}
+
+#[test]
+fn square_with_hole_non_zero() {
+    check("square_with_hole_non_zero", square_with_hole(FillRule::NonZero));
+}
+
+#[test]
+fn conical_gradient() {
+    let (device, queue) = device();
+    let stop = |offset, color| GradientStop{offset, color};
+    let gradient = GradientType::Conical(ConicalGradient{
+        center: [0.0, 0.0],
+        start_angle: 0.0,
+        stops: vec![stop(0.0, [1.0, 0.0, 0.0, 1.0]), stop(0.5, [0.0, 0.0, 1.0, 1.0]), stop(1.0, [1.0, 0.0, 0.0, 1.0])],
+        interpolation: GradientInterpolation::LinearLight,
+    });
+    let shapes = vec![rect([1.0; 3], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)).with_gradient(gradient)];
+    let pixels = harness::render(&device, &queue, shapes, SIZE, SIZE).unwrap();
+    let pixel = |x: u32, y: u32| &pixels[((y * SIZE + x) * 4) as usize..][..4];
+
+    // Diametrically opposite pixels are half a revolution apart.
+    let (right, left) = (pixel(56, 32), pixel(8, 32));
+    assert!(right[0] > 240 && right[2] < 16, "right of center is {right:?}");
+    assert!(left[0] < 16 && left[2] > 240, "left of center is {left:?}");
+}
+
+#[test]
+fn gradient_interpolation() {
+    let (device, queue) = device();
+    // Black to white, halfway through left of the center.
+    let middle = |interpolation| {
+        let gradient = GradientType::Conical(ConicalGradient{
+            center: [0.0, 0.0],
+            start_angle: 0.0,
+            stops: vec![GradientStop{offset: 0.0, color: [0.0, 0.0, 0.0, 1.0]}, GradientStop{offset: 1.0, color: [1.0; 4]}],
+            interpolation,
+        });
+        let shapes = vec![rect([1.0; 3], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)).with_gradient(gradient)];
+        let pixels = harness::render(&device, &queue, shapes, SIZE, SIZE).unwrap();
+        pixels[((32 * SIZE + 4) * 4) as usize] as i32
+    };
+    // The target stores linear light: 0.5, sRGB 0.5 decoded and Oklab lightness 0.5 cubed.
+    let (linear, srgb, oklab) = (middle(GradientInterpolation::LinearLight), middle(GradientInterpolation::Srgb), middle(GradientInterpolation::OklabPerceptual));
+    assert!((linear - 128).abs() <= 2, "linear light is {linear}");
+    assert!((srgb - 55).abs() <= 2, "sRGB is {srgb}");
+    assert!((oklab - 32).abs() <= 2, "Oklab is {oklab}");
+}
+
+#[test]
+fn mesh_gradient() {
+    let (device, queue) = device();
+    let gradient = GradientType::Mesh(MeshGradient{
+        corners: [[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0], [1.0, 1.0, 1.0, 1.0]],
+    });
+    let shapes = vec![rect([0.0; 3], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)).with_gradient(gradient)];
+    let pixels = harness::render(&device, &queue, shapes, SIZE, SIZE).unwrap();
+    let pixel = |x: u32, y: u32| &pixels[((y * SIZE + x) * 4) as usize..][..4];
+
+    let (top_left, bottom_right) = (pixel(2, 2), pixel(61, 61));
+    assert!(top_left[0] > 220 && top_left[1] < 32 && top_left[2] < 32, "top left is {top_left:?}");
+    assert!(bottom_right[..3].iter().all(|&c| c > 220), "bottom right is {bottom_right:?}");
+}
+
+#[test]
+fn noise_fill() {
+    let (device, queue) = device();
+    let render = |seed| {
+        let noise = NoiseFill{scale: 16.0, octaves: 3, seed, color_a: [1.0, 0.0, 0.0], color_b: [0.0, 0.0, 1.0]};
+        let shapes = vec![rect([1.0; 3], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)).with_gradient(GradientType::Noise(noise))];
+        harness::render(&device, &queue, shapes, SIZE, SIZE).unwrap()
+    };
+    let pixels = render(1);
+    // Every pixel blends the two colors, with the blend varying across the shape.
+    assert!(pixels.chunks_exact(4).all(|p| p[1] == 0 && p[0] as u32 + p[2] as u32 >= 180));
+    let reds: Vec<u8> = pixels.chunks_exact(4).map(|p| p[0]).collect();
+    assert!(reds.iter().max().unwrap() - reds.iter().min().unwrap() > 64);
+    assert_eq!(render(1), pixels);
+    assert_ne!(render(2), pixels);
+}
+
+#[test]
+fn sorted_translucent_shapes() {
+    let (device, queue) = device();
+    let descriptor = CyatRendererDescriptor{blend: Some(BlendState::ALPHA_BLENDING), ..CyatRendererDescriptor::new(harness::FORMAT)}
+        .depth_format(TextureFormat::Depth32Float).unwrap();
+    let shape = |color, min: [f32; 2], z| {
+        let attributes = DefaultAttributes{color, z, palette: 0};
+        ShapeArea::shape(Shape::Rectangle(attributes, min[0], min[1], min[0] + 1.0, min[1] + 1.0), None, (0, 0, SIZE, SIZE)).with_opacity(0.5)
+    };
+    let far = || shape([1.0, 0.0, 0.0], [-0.75, -0.75], 0.75);
+    let middle = || shape([0.0, 1.0, 0.0], [-0.5, -0.5], 0.5);
+    let near = || shape([0.0, 0.0, 1.0], [-0.25, -0.25], 0.25);
+
+    let render = |shapes| {
+        let mut renderer = CyatRenderer::from_descriptor(&device, descriptor.clone()).unwrap();
+        renderer.set_translucency_sorting(true);
+        harness::render_with(&device, &queue, &mut renderer, shapes, SIZE, SIZE).unwrap()
+    };
+    let ordered = render(vec![far(), middle(), near()]);
+    let scrambled = render(vec![near(), far(), middle()]);
+    assert!(ordered == scrambled, "scrambled submission renders differently");
+}
+
+#[test]
+fn rounded_clip() {
+    let (device, queue) = device();
+    let shapes = vec![rect([0.0, 1.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)).with_rounded_clip([16.0, 0.0, 16.0, 0.0])];
+    let pixels = harness::render(&device, &queue, shapes, SIZE, SIZE).unwrap();
+    let pixel = |x: u32, y: u32| &pixels[((y * SIZE + x) * 4) as usize..][..4];
+
+    assert_eq!(pixel(1, 1), [0, 0, 0, 255], "top left corner is clipped");
+    assert_eq!(pixel(62, 62), [0, 0, 0, 255], "bottom right corner is clipped");
+    assert_eq!(pixel(62, 1), [0, 255, 0, 255], "top right corner is square");
+    assert_eq!(pixel(32, 32), [0, 255, 0, 255], "center is drawn");
+}
+
+#[test]
+fn inverted_mask() {
+    let (device, queue) = device();
+    let circle = || ShapeBuilder::new(Shape::Ellipse(DefaultAttributes{color: [1.0; 3], z: 0.0, palette: 0}, 0.0, 0.0, 0.5, 0.5), None, 0.001);
+    let background = || rect([0.0, 0.0, 1.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE));
+    let pixel = |pixels: &[u8], x: u32, y: u32| pixels[((y * SIZE + x) * 4) as usize..][..4].to_vec();
+
+    let pixels = harness::render(&device, &queue, vec![background().masked_outside(circle())], SIZE, SIZE).unwrap();
+    assert_eq!(pixel(&pixels, 32, 32), [0, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, 2, 2), [0, 0, 255, 255]);
+
+    let pixels = harness::render(&device, &queue, vec![background().masked_by(circle())], SIZE, SIZE).unwrap();
+    assert_eq!(pixel(&pixels, 32, 32), [0, 0, 255, 255]);
+    assert_eq!(pixel(&pixels, 2, 2), [0, 0, 0, 255]);
+}
+
+#[test]
+fn viewport_shape() {
+    let (device, queue) = device();
+    let half = SIZE / 2;
+    let pixel = |pixels: &[u8], x: u32, y: u32| pixels[((y * SIZE + x) * 4) as usize..][..4].to_vec();
+    let shapes = vec![
+        // Scaled into the top left quadrant and clipped to its left half.
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, half / 2, half)).with_viewport((0, 0, half, half)),
+        ShapeArea::auto_bound(ShapeBuilder::new(Shape::Rectangle(DefaultAttributes{color: [0.0, 0.0, 1.0], z: 0.0, palette: 0}, -1.0, -1.0, 1.0, 1.0), None, 0.001))
+            .with_viewport((half, 0, half, half)),
+        // Drawn after both with the full viewport again.
+        rect([0.0, 1.0, 0.0], [0.5, -1.0], [1.0, -0.5], (0, 0, SIZE, SIZE)),
+    ];
+    let pixels = harness::render(&device, &queue, shapes, SIZE, SIZE).unwrap();
+    assert_eq!(pixel(&pixels, 8, 16), [255, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, 24, 16), [0, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, 48, 16), [0, 0, 255, 255]);
+    assert_eq!(pixel(&pixels, 16, 48), [0, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, 56, 56), [0, 255, 0, 255]);
+    assert_eq!(pixel(&pixels, 40, 40), [0, 0, 0, 255]);
+
+    // Viewports are checked against the target set by resize, none at first.
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    let shape = |viewport| vec![rect([1.0, 0.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, half, half)).with_viewport(viewport)];
+    assert_eq!(renderer.prepare(&device, &queue, shape((0, 0, half, half))), Err(CyatError::InvalidViewport(0)));
+    renderer.resize(SIZE, SIZE);
+    assert_eq!(renderer.prepare(&device, &queue, shape((half, half, SIZE, SIZE))), Err(CyatError::InvalidViewport(0)));
+    assert!(renderer.prepare(&device, &queue, shape((0, 0, half, half))).is_ok());
+}
+
+#[test]
+fn desaturated_shape() {
+    let (device, queue) = device();
+    let shapes = vec![rect([0.0, 1.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)).with_saturation(0.0)];
+    let pixels = harness::render(&device, &queue, shapes, SIZE, SIZE).unwrap();
+    let center = &pixels[((32 * SIZE + 32) * 4) as usize..][..4];
+    assert!(center[0] == center[1] && center[1] == center[2] && center[0] > 0, "center is {center:?}");
+}
+
+#[test]
+fn palette_shape() {
+    let (device, queue) = device();
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    let shapes = || vec![
+        ShapeArea::shape(Shape::Rectangle(DefaultAttributes::palette(1, 0.0), -1.0, -1.0, 0.0, 1.0), None, (0, 0, SIZE, SIZE)),
+        rect([0.0, 0.0, 1.0], [0.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)),
+    ];
+    let pixel = |pixels: &[u8], x: u32| pixels[((32 * SIZE + x) * 4) as usize..][..4].to_vec();
+
+    renderer.set_palette(&queue, &[Color::BLACK, Color::RED]);
+    let red = harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+    renderer.set_palette(&queue, &[Color::BLACK, Color::GREEN]);
+    let green = harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+    assert_eq!(pixel(&red, 16), [255, 0, 0, 255]);
+    assert_eq!(pixel(&green, 16), [0, 255, 0, 255]);
+    assert_eq!(pixel(&green, 48), [0, 0, 255, 255]);
+}
+
+#[test]
+fn srgb_output_constant() {
+    let (device, queue) = device();
+    let descriptor = CyatRendererDescriptor::new(harness::FORMAT).with_constant("OUTPUT_SRGB", 1.0).unwrap();
+    let mut renderer = CyatRenderer::from_descriptor(&device, descriptor).unwrap();
+    // Constants set on the descriptor directly are checked too.
+    let mut unknown = CyatRendererDescriptor::new(harness::FORMAT);
+    unknown.constants.insert("SRGB".to_string(), 1.0);
+    assert_eq!(CyatRenderer::from_descriptor(&device, unknown).err(), Some(CyatError::UnknownConstant("SRGB".to_string())));
+    let shapes = vec![rect([0.5, 0.5, 0.5], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE))];
+    let pixels = harness::render_with(&device, &queue, &mut renderer, shapes, SIZE, SIZE).unwrap();
+    let center = &pixels[((32 * SIZE + 32) * 4) as usize..][..4];
+    assert!(center[..3].iter().all(|&c| c.abs_diff(128) <= 1), "center is {center:?}");
+}
+
+#[test]
+fn glyph_batch() {
+    let (device, queue) = device();
+    let texture = Arc::new(SpriteTexture::new(&device, &queue, 1, 1, &[0, 255, 0, 255]));
+    let mut batch = GlyphBatch::new();
+    batch.add_glyph([0.0, 0.0, 1.0, 1.0], [-1.0, 1.0, 0.0, -1.0], [1.0, 1.0, 1.0]);
+    batch.add_glyph([0.0, 0.0, 1.0, 1.0], [0.0, 1.0, 1.0, -1.0], [1.0, 0.0, 0.0]);
+    let shapes = vec![ShapeArea::from_glyph_batch(batch, texture, (0, 0, SIZE, SIZE))];
+    let pixels = harness::render(&device, &queue, shapes, SIZE, SIZE).unwrap();
+    let pixel = |x: u32| pixels[((32 * SIZE + x) * 4) as usize..][..4].to_vec();
+    assert_eq!(pixel(16), [0, 255, 0, 255]);
+    assert_eq!(pixel(48), [0, 0, 0, 255]);
+}
+
+#[test]
+fn invalid_depth() {
+    let (device, queue) = device();
+    let shape = |z| ShapeArea::shape(Shape::Rectangle(DefaultAttributes{color: [1.0; 3], z, palette: 0}, -0.5, -0.5, 0.5, 0.5), None, (0, 0, SIZE, SIZE));
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    renderer.resize(SIZE, SIZE);
+    assert_eq!(renderer.prepare(&device, &queue, vec![shape(0.5), shape(1.5)]).unwrap_err(), CyatError::InvalidDepth(1));
+    assert_eq!(renderer.prepare(&device, &queue, vec![shape(0.5), shape(f32::NAN)]).unwrap_err(), CyatError::InvalidDepth(1));
+    renderer.set_z_normalization(Some([0.0, 1.0]));
+    assert!(renderer.prepare(&device, &queue, vec![shape(-3.0), shape(1.5)]).is_ok());
+}
+
+#[test]
+fn path_difference() {
+    let rect = |min: f32, max: f32| ShapeBuilder::new(Shape::Rectangle(DefaultAttributes{color: [1.0, 0.0, 0.0], z: 0.0, palette: 0}, min, min, max, max), None, 0.001);
+    let (device, queue) = device();
+    let geometry = PathOps::difference(rect(-1.0, 1.0), rect(-0.5, 0.5)).unwrap();
+    let mut shape = ShapeArea::rect([0.0; 4], [0.0; 3], (0, 0, SIZE, SIZE));
+    shape.geometry = geometry;
+    let pixels = harness::render(&device, &queue, vec![shape], SIZE, SIZE).unwrap();
+    let pixel = |x: u32, y: u32| pixels[((y * SIZE + x) * 4) as usize..][..4].to_vec();
+    assert_eq!(pixel(32, 32), [0, 0, 0, 255]);
+    assert_eq!(pixel(4, 4), [255, 0, 0, 255]);
+    assert_eq!(pixel(32, 4), [255, 0, 0, 255]);
+}
+
+#[test]
+fn y_axis_down() {
+    let (device, queue) = device();
+    let descriptor = CyatRendererDescriptor{y_axis: YAxis::Down, ..CyatRendererDescriptor::new(harness::FORMAT)};
+    let mut renderer = CyatRenderer::from_descriptor(&device, descriptor).unwrap();
+    let builder = ShapeBuilder::new(Shape::Rectangle(DefaultAttributes{color: [1.0, 0.0, 0.0], z: 0.0, palette: 0}, -1.0, -1.0, 1.0, 0.0), None, 0.001);
+    let shapes = vec![ShapeArea::auto_bound(builder)];
+    let pixels = harness::render_with(&device, &queue, &mut renderer, shapes, SIZE, SIZE).unwrap();
+    let pixel = |y: u32| pixels[((y * SIZE + 32) * 4) as usize..][..4].to_vec();
+    assert_eq!(pixel(8), [255, 0, 0, 255]);
+    assert_eq!(pixel(56), [0, 0, 0, 255]);
+}
+
+#[test]
+fn render_target_change() {
+    let (device, queue) = device();
+    let mut renderer = CyatRenderer::new(&device, &TextureFormat::Bgra8Unorm, MultisampleState::default(), None);
+    let mismatch = CyatError::FormatMismatch{pipeline_format: TextureFormat::Bgra8Unorm, target_format: harness::FORMAT};
+    assert_eq!(renderer.validate_render_target(harness::FORMAT, 1), Err(mismatch.clone()));
+    assert_eq!(renderer.set_render_target(harness::FORMAT, 1), Err(mismatch));
+
+    renderer.set_device(Arc::new(device.clone()));
+    renderer.set_render_target(harness::FORMAT, 1).unwrap();
+    assert_eq!(renderer.validate_render_target(harness::FORMAT, 1), Ok(()));
+    let builder = ShapeBuilder::new(Shape::Rectangle(DefaultAttributes{color: [1.0, 0.0, 0.0], z: 0.0, palette: 0}, -1.0, -1.0, 1.0, 1.0), None, 0.001);
+    let pixels = harness::render_with(&device, &queue, &mut renderer, vec![ShapeArea::auto_bound(builder)], SIZE, SIZE).unwrap();
+    assert_eq!(pixels[..4], [255, 0, 0, 255]);
+}
+
+#[test]
+fn chunked_buffers() {
+    let (device, queue) = device();
+    // Four 28 byte vertices per rect, so two rects fit in 300 bytes.
+    let shapes = || (0..8).map(|i| {
+        let x = -1.0 + i as f32 * 0.25;
+        rect([1.0, i as f32 / 8.0, 0.0], [x, -1.0], [x + 0.25, 1.0], (0, 0, SIZE, SIZE))
+    }).collect::<Vec<_>>();
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    renderer.set_max_buffer_size(Some(300));
+    let stats = renderer.prepare(&device, &queue, shapes()).unwrap();
+    assert_eq!(stats.chunks, 4);
+    let pixels = harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+    assert_eq!(pixels, harness::render(&device, &queue, shapes(), SIZE, SIZE).unwrap());
+    // Every shape after the first keeps the pipeline, and the second of each
+    // chunk its buffers.
+    assert_eq!(renderer.redundant_state_changes_avoided(), 7 + 4 * 2);
+
+    renderer.set_max_buffer_size(Some(64));
+    assert_eq!(renderer.prepare(&device, &queue, shapes()), Err(CyatError::ShapeTooLarge{shape: 0, size: 112, limit: 64}));
+}
+
+#[test]
+fn reserve_capacities() {
+    let (device, queue) = device();
+    let shapes = || vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [0.0, 1.0], (0, 0, SIZE, SIZE)),
+        rect([0.0, 0.0, 1.0], [0.0, -0.5], [1.0, 0.5], (0, 0, SIZE, SIZE)),
+    ];
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    renderer.prepare(&device, &queue, shapes()).unwrap();
+    // Growing the buffers keeps the unchanged frame drawable.
+    renderer.reserve(&device, &queue, 10_000, 30_000, 1_000);
+    let capacities = renderer.capacities();
+    assert!(capacities.vertices >= 10_000 && capacities.indices >= 30_000 && capacities.shapes >= 1_000);
+    let pixels = harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+    assert_eq!(pixels, harness::render(&device, &queue, shapes(), SIZE, SIZE).unwrap());
+    assert_eq!(renderer.capacities(), capacities);
+}
+
+#[test]
+fn shapes_macro() {
+    let (device, queue) = device();
+    let line = [[-1.0, 0.75], [1.0, 0.75]];
+    let shapes = shapes![
+        rect(-1.0, -1.0, 1.0, 1.0; color = [1.0, 0.0, 0.0]; bound = (0, 0, SIZE, SIZE)),
+        rounded_rect(0.0, -1.0, 1.0, 1.0, 0.25; color = [0.0, 1.0, 0.0]; z = 0.5),
+        circle(-0.5, 0.25, 0.25; color = [0.0, 0.0, 1.0]),
+        ellipse(0.5, 0.25, 0.4, 0.2; color = [1.0, 1.0, 0.0]; bound = (32, 0, 32, SIZE)),
+        polyline(&line, 0.1; color = [1.0, 1.0, 1.0]),
+    ];
+    let expected = vec![
+        ShapeArea::rect([-1.0, -1.0, 0.0, 0.0], [1.0, 0.0, 0.0], (0, 0, SIZE, SIZE)),
+        ShapeArea::rounded_rect([0.0, -1.0, 1.0, 0.0], 0.25, [0.0, 1.0, 0.0], (0, 0, SIZE, SIZE)).with_z(0.5),
+        ShapeArea::circle([-0.5, 0.25], 0.25, [0.0, 0.0, 1.0], (0, 0, SIZE, SIZE)),
+        ShapeArea::ellipse([0.5, 0.25], [0.4, 0.2], [1.0, 1.0, 0.0], (32, 0, 32, SIZE)),
+        ShapeArea::polyline(&line, 0.1, [1.0, 1.0, 1.0], (0, 0, SIZE, SIZE)),
+    ];
+    let pixels = harness::render(&device, &queue, shapes, SIZE, SIZE).unwrap();
+    assert_eq!(pixels, harness::render(&device, &queue, expected, SIZE, SIZE).unwrap());
+    let pixel = |x: u32, y: u32| &pixels[((y * SIZE + x) * 4) as usize..][..3];
+    assert_eq!(pixel(16, 48), [255, 0, 0]);
+    assert_eq!(pixel(48, 48), [0, 255, 0]);
+    assert_eq!(pixel(63, 63), [0, 0, 0]);
+    assert_eq!(pixel(16, 24), [0, 0, 255]);
+    assert_eq!(pixel(48, 24), [255, 255, 0]);
+    assert_eq!(pixel(8, 8), [255, 255, 255]);
+
+    // One segment past what 16 bit indices reach.
+    let long: Vec<_> = (0..16386).map(|i| [i as f32 / 16386.0, 0.0]).collect();
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    let error = renderer.prepare(&device, &queue, vec![ShapeArea::polyline(&long, 0.1, [1.0; 3], (0, 0, SIZE, SIZE))]);
+    assert_eq!(error, Err(CyatError::TooManyVertices(4 * 16385)));
+}
+
+#[test]
+fn msaa_sample_count() {
+    let adapter = harness::software_adapter().expect(NO_ADAPTER);
+    let (device, _) = device();
+    let descriptor = || CyatRendererDescriptor::new(harness::FORMAT);
+    assert_eq!(descriptor().msaa(&adapter, 3).err(), Some(CyatError::UnsupportedSampleCount{count: 3, format: harness::FORMAT}));
+    assert_eq!(descriptor().msaa(&adapter, 16).err(), Some(CyatError::UnsupportedSampleCount{count: 16, format: harness::FORMAT}));
+    let renderer = CyatRenderer::from_descriptor(&device, descriptor().msaa(&adapter, 1).unwrap()).unwrap();
+    assert_eq!(renderer.sample_count(), 1);
+    if let Ok(descriptor) = descriptor().msaa(&adapter, 4) {
+        assert_eq!(CyatRenderer::from_descriptor(&device, descriptor).unwrap().sample_count(), 4);
+    }
+}
+
+#[test]
+fn bound_grouping() {
+    let (device, queue) = device();
+    let (left, right) = ((0, 0, SIZE / 2, SIZE), (SIZE / 2, 0, SIZE / 2, SIZE));
+    let panels = || (0..4).map(|i| {
+        let y = -1.0 + i as f32 * 0.5;
+        let bound = if i % 2 == 0 {left} else {right};
+        rect([1.0, i as f32 / 4.0, 0.0], [-1.0, y], [1.0, y + 0.5], bound)
+    }).collect::<Vec<_>>();
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    renderer.set_bound_grouping(true);
+    let stats = renderer.prepare(&device, &queue, panels()).unwrap();
+    assert_eq!((stats.scissor_changes_before_grouping, stats.scissor_changes), (4, 2));
+    let pixels = harness::render_with(&device, &queue, &mut renderer, panels(), SIZE, SIZE).unwrap();
+    assert_eq!(pixels, harness::render(&device, &queue, panels(), SIZE, SIZE).unwrap());
+
+    // The full bound overlaps both panels, so nothing moves past it.
+    let overlapping = || vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [1.0, 1.0], left),
+        rect([0.0, 1.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)),
+        rect([0.0, 0.0, 1.0], [-1.0, -1.0], [1.0, 1.0], left),
+    ];
+    let stats = renderer.prepare(&device, &queue, overlapping()).unwrap();
+    assert_eq!((stats.scissor_changes_before_grouping, stats.scissor_changes), (3, 3));
+}
+
+/// A source outside the crate, indexing its own vertices from 0.
+struct Quad([f32; 3], [f32; 2], [f32; 2]);
+
+impl FillGeometry for Quad {
+    fn append(&self, _tolerance: f32, out: &mut VertexBuffers<DefaultVertex, u16>) {
+        let Quad(color, [x, y], [x2, y2]) = *self;
+        let attributes = DefaultAttributes{color, z: 0.0, palette: 0};
+        out.vertices.extend([[x, y], [x2, y], [x2, y2], [x, y2]].map(|p| DefaultVertex::construct(p, attributes)));
+        out.indices.extend([0, 1, 2, 0, 2, 3]);
+    }
+}
+
+#[test]
+fn fill_geometry() {
+    let (device, queue) = device();
+    let full = (0, 0, SIZE, SIZE);
+    let shapes = vec![
+        ShapeArea::fill(Quad([1.0, 0.0, 0.0], [-1.0, -1.0], [0.0, 1.0]), full),
+        ShapeArea::fill(Quad([0.0, 1.0, 0.0], [0.0, -1.0], [1.0, 0.0]), full),
+        ShapeArea::fill(Shape::Rectangle(DefaultAttributes{color: [0.0, 0.0, 1.0], z: 0.0, palette: 0}, 0.0, 0.0, 1.0, 1.0), full),
+    ];
+    let expected = vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [0.0, 1.0], full),
+        rect([0.0, 1.0, 0.0], [0.0, -1.0], [1.0, 0.0], full),
+        rect([0.0, 0.0, 1.0], [0.0, 0.0], [1.0, 1.0], full),
+    ];
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    let pixels = harness::render_with(&device, &queue, &mut renderer, shapes, SIZE, SIZE).unwrap();
+    assert_eq!(pixels, harness::render(&device, &queue, expected, SIZE, SIZE).unwrap());
+}
+
+#[test]
+fn auto_scissor() {
+    let builder = |min: f32, max: f32| ShapeBuilder::new(Shape::Rectangle(DefaultAttributes{color: [1.0; 3], z: 0.0, palette: 0}, min, min, max, max), None, 0.001);
+    let inside = ShapeArea::auto_scissor(builder(-0.5, 0.5), (SIZE, SIZE));
+    assert_eq!(inside.bound, Some(ScissorRect::from_pos_size(16, 16, 32, 32)));
+    // Clamped to the target.
+    let partly = ShapeArea::auto_scissor(builder(0.5, 1.5), (SIZE, SIZE));
+    assert_eq!(partly.bound, Some(ScissorRect::from_pos_size(48, 0, 16, 16)));
+
+    let (device, queue) = device();
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    renderer.resize(SIZE, SIZE);
+    let outside = ShapeArea::auto_scissor(builder(1.5, 2.0), (SIZE, SIZE));
+    assert!(outside.bound.unwrap().is_empty());
+    let stats = renderer.prepare(&device, &queue, vec![inside, outside]).unwrap();
+    assert_eq!((stats.shapes, stats.culled), (1, 1));
+}
+
+#[test]
+fn indirect_draws() {
+    let (device, queue) = device_with(Features::MULTI_DRAW_INDIRECT);
+    let (left, right) = ((0, 0, SIZE / 2, SIZE), (SIZE / 2, 0, SIZE / 2, SIZE));
+    let shapes = || vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [0.0, 0.0], left),
+        rect([0.0, 1.0, 0.0], [-1.0, 0.0], [0.0, 1.0], left),
+        rect([0.0, 0.0, 1.0], [-0.5, -0.5], [0.5, 0.5], right),
+        rect([1.0, 1.0, 0.0], [0.5, -1.0], [1.0, 1.0], right).with_opacity(0.5),
+    ];
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    let stats = renderer.prepare_indirect(&device, &queue, shapes()).unwrap();
+    let expected = if device.features().contains(Features::MULTI_DRAW_INDIRECT) {3} else {0};
+    assert_eq!(stats.indirect_draw_calls, expected);
+    let pixels = harness::render_indirect_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+    assert_eq!(pixels, harness::render(&device, &queue, shapes(), SIZE, SIZE).unwrap());
+    assert_eq!(renderer.prepare(&device, &queue, shapes()).unwrap().indirect_draw_calls, 0);
+}
+
+#[test]
+fn empty_frame_policy() {
+    let (device, queue) = device();
+    let shapes = || vec![rect([1.0, 0.0, 0.0], [-0.5, -0.5], [0.5, 0.5], (0, 0, SIZE, SIZE))];
+    let descriptor = CyatRendererDescriptor{empty_frame: EmptyFrame::KeepPrevious, ..CyatRendererDescriptor::new(harness::FORMAT)};
+    let mut renderer = CyatRenderer::from_descriptor(&device, descriptor).unwrap();
+    let drawn = harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+    let stats = renderer.prepare(&device, &queue, Vec::new()).unwrap();
+    assert!(stats.stale && !stats.changed);
+    assert_eq!((stats.shapes, stats.vertices), (1, 4));
+    assert_eq!(harness::render_with(&device, &queue, &mut renderer, Vec::new(), SIZE, SIZE).unwrap(), drawn);
+
+    renderer.set_empty_frame(EmptyFrame::Clear);
+    let stats = renderer.prepare(&device, &queue, Vec::new()).unwrap();
+    assert!(!stats.stale && stats.changed);
+    assert_eq!(stats.shapes, 0);
+    assert!(harness::render_with(&device, &queue, &mut renderer, Vec::new(), SIZE, SIZE).unwrap().chunks_exact(4).all(|p| p == [0, 0, 0, 255]));
+}
+
+#[test]
+fn quantized_vertices() {
+    let (device, queue) = device();
+    let shapes = || vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [0.0, 1.0], (0, 0, SIZE, SIZE)),
+        rect([0.0, 0.0, 1.0], [0.0, -0.5], [1.0, 0.5], (0, 0, SIZE, SIZE)),
+    ];
+    let descriptor = CyatRendererDescriptor::new(harness::FORMAT).with_quantized_vertices(2.0, [-1.0, -1.0]);
+    let mut renderer = CyatRenderer::from_descriptor(&device, descriptor).unwrap();
+    let pixels = harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+    assert_eq!(pixels, harness::render(&device, &queue, shapes(), SIZE, SIZE).unwrap());
+
+    let stops = vec![GradientStop{offset: 0.0, color: [1.0; 4]}, GradientStop{offset: 1.0, color: [0.0, 0.0, 0.0, 1.0]}];
+    let gradient = GradientType::Conical(ConicalGradient{center: [0.0, 0.0], start_angle: 0.0, stops, interpolation: GradientInterpolation::LinearLight});
+    let gradients = shapes().into_iter().enumerate().map(|(i, shape)| if i == 1 {shape.with_gradient(gradient.clone())} else {shape}).collect();
+    assert_eq!(renderer.prepare(&device, &queue, gradients), Err(CyatError::UnsupportedQuantized(1)));
+}
+
+#[test]
+fn descriptor_errors() {
+    let (device, queue) = device();
+    let error = |descriptor| CyatRenderer::from_descriptor(&device, descriptor).err();
+    let descriptor = || CyatRendererDescriptor::new(harness::FORMAT);
+    if !device.features().contains(Features::DUAL_SOURCE_BLENDING) {
+        let dual_source = CyatRendererDescriptor{dual_source_blending: true, ..descriptor()};
+        assert_eq!(error(dual_source), Some(CyatError::MissingFeatures(Features::DUAL_SOURCE_BLENDING)));
+    }
+    let quantized = CyatRendererDescriptor{multiview: NonZeroU32::new(2), ..descriptor().with_quantized_vertices(1.0, [0.0, 0.0])};
+    assert_eq!(error(quantized), Some(CyatError::IncompatibleOptions{option: "quantized vertices", with: "multiview"}));
+    let multiview = CyatRendererDescriptor{multiview: NonZeroU32::new(MAX_VIEWS + 1), ..descriptor()};
+    let expected = match device.features().contains(Features::MULTIVIEW) {
+        true => CyatError::TooManyViews(MAX_VIEWS + 1),
+        false => CyatError::MissingFeatures(Features::MULTIVIEW),
+    };
+    assert_eq!(error(multiview), Some(expected));
+    let reserved = device.create_bind_group_layout(&BindGroupLayoutDescriptor{label: None, entries: &[]});
+    assert_eq!(error(descriptor().with_extra_bind_group_layout(reserved, 1)), Some(CyatError::ReservedBindGroup(1)));
+
+    // Masks, gradients and sprites bind group 2, which an extra layout takes.
+    let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{label: None, entries: &[]});
+    let mut renderer = CyatRenderer::from_descriptor(&device, descriptor().with_extra_bind_group_layout(layout, 2)).unwrap();
+    let shapes = vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)),
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)).with_gradient(GradientType::Conical(ConicalGradient{
+            center: [0.0, 0.0],
+            start_angle: 0.0,
+            stops: vec![GradientStop{offset: 0.0, color: [1.0; 4]}, GradientStop{offset: 1.0, color: [0.0, 0.0, 0.0, 1.0]}],
+            interpolation: GradientInterpolation::LinearLight,
+        })),
+    ];
+    assert_eq!(renderer.prepare(&device, &queue, shapes), Err(CyatError::UnsupportedEffect(1)));
+}
+
+#[test]
+fn stencil_reference() {
+    let (device, queue) = device();
+    // Draw where the reference is at least the stored value and store it.
+    let face = StencilFaceState{compare: CompareFunction::GreaterEqual, fail_op: StencilOperation::Keep, depth_fail_op: StencilOperation::Keep, pass_op: StencilOperation::Replace};
+    let depth_stencil = DepthStencilState{
+        format: TextureFormat::Depth24PlusStencil8,
+        depth_write_enabled: false,
+        depth_compare: CompareFunction::Always,
+        stencil: StencilState{front: face, back: face, read_mask: 0xff, write_mask: 0xff},
+        bias: DepthBiasState::default(),
+    };
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), Some(depth_stencil));
+    let shapes = vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [0.0, 1.0], (0, 0, SIZE, SIZE)).with_stencil_reference(2),
+        rect([0.0, 0.0, 1.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)).with_stencil_reference(1),
+    ];
+    let pixels = harness::render_with(&device, &queue, &mut renderer, shapes, SIZE, SIZE).unwrap();
+    let pixel = |x: u32, y: u32| &pixels[((y * SIZE + x) * 4) as usize..][..4];
+    assert_eq!(pixel(16, 32), [255, 0, 0, 255]);
+    assert_eq!(pixel(48, 32), [0, 0, 255, 255]);
+}
+
+#[test]
+fn stencil_clear() {
+    let (device, queue) = device();
+    let face = StencilFaceState{compare: CompareFunction::GreaterEqual, fail_op: StencilOperation::Keep, depth_fail_op: StencilOperation::Keep, pass_op: StencilOperation::Replace};
+    let depth_stencil = DepthStencilState{
+        format: TextureFormat::Depth24PlusStencil8,
+        depth_write_enabled: false,
+        depth_compare: CompareFunction::Always,
+        stencil: StencilState{front: face, back: face, read_mask: 0xff, write_mask: 0xff},
+        bias: DepthBiasState::default(),
+    };
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), Some(depth_stencil));
+    let shapes = || vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [0.0, 1.0], (0, 0, SIZE, SIZE)).with_stencil_reference(2),
+        rect([0.0, 0.0, 1.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)).with_stencil_reference(1),
+    ];
+    renderer.prepare(&device, &queue, shapes()).unwrap();
+    assert!(renderer.needs_stencil_clear());
+
+    // Above every reference, nothing passes; at 0 the frame is drawn as usual.
+    let clear_then_render = |value| move |renderer: &CyatRenderer, render_pass: &mut RenderPass<'_>| {
+        renderer.clear_stencil(render_pass, value);
+        renderer.render(render_pass);
+    };
+    let pixels = harness::render_pass_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE, &clear_then_render(3)).unwrap();
+    assert!(pixels.chunks_exact(4).all(|p| p == [0, 0, 0, 255]));
+    let pixels = harness::render_pass_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE, &clear_then_render(0)).unwrap();
+    assert_eq!(pixels, harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap());
+
+    let renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    assert!(!renderer.needs_stencil_clear());
+}
+
+#[test]
+fn user_data() {
+    let (device, queue) = device();
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    renderer.resize(SIZE, SIZE);
+    let shapes = vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [0.0, 1.0], (0, 0, SIZE, SIZE)).with_z_index(1).with_user_data(10),
+        rect([0.0, 1.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, 0, 0)).with_user_data(20),
+        rect([0.0, 0.0, 1.0], [0.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)).with_user_data(30),
+    ];
+    let stats = renderer.prepare(&device, &queue, shapes).unwrap();
+    assert_eq!((stats.shapes, stats.culled), (2, 1));
+    assert_eq!([0, 1, 2].map(|i| renderer.user_data(i)), [Some(30), Some(10), None]);
+}
+
+#[test]
+fn gpu_shapes() {
+    let (device, queue) = device();
+    let attributes = DefaultAttributes{color: [0.0, 1.0, 0.0], z: 0.0, palette: 0};
+    let vertices = [[-0.5, -0.5], [0.5, -0.5], [0.5, 0.5], [-0.5, 0.5]].map(|p| DefaultVertex::construct(p, attributes));
+    let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+    // Filled through the queue here, where a compute shader would write them.
+    let buffer = |contents: &[u8], usage| {
+        let buffer = device.create_buffer(&BufferDescriptor{label: None, size: contents.len() as u64, usage: usage | BufferUsages::STORAGE | BufferUsages::COPY_DST, mapped_at_creation: false});
+        queue.write_buffer(&buffer, 0, contents);
+        Arc::new(buffer)
+    };
+    let (vertex_buffer, index_buffer) = (buffer(bytemuck::cast_slice(&vertices), BufferUsages::VERTEX), buffer(bytemuck::cast_slice(&indices), BufferUsages::INDEX));
+
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    let unusable = Arc::new(device.create_buffer(&BufferDescriptor{label: None, size: 64, usage: BufferUsages::VERTEX, mapped_at_creation: false}));
+    let missing = CyatError::MissingBufferUsages{buffer: "vertex", usages: BufferUsages::STORAGE};
+    assert_eq!(renderer.bind_gpu_shapes(&device, 6, unusable, index_buffer.clone()), Err(missing));
+    renderer.bind_gpu_shapes(&device, 6, vertex_buffer, index_buffer).unwrap();
+    let stats = renderer.prepare(&device, &queue, vec![rect([1.0, 0.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE))]).unwrap();
+    assert_eq!((stats.shapes, stats.indices), (1, 6));
+    let mesh = VertexBuffers{vertices: vertices.to_vec(), indices: indices.to_vec()};
+    let expected = harness::render(&device, &queue, vec![ShapeArea::fill(mesh, (0, 0, SIZE, SIZE))], SIZE, SIZE).unwrap();
+    assert_eq!(harness::render_with(&device, &queue, &mut renderer, Vec::new(), SIZE, SIZE).unwrap(), expected);
+
+    renderer.unbind_gpu_shapes();
+    let shapes = || vec![rect([1.0, 0.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE))];
+    assert_eq!(harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap(), harness::render(&device, &queue, shapes(), SIZE, SIZE).unwrap());
+}
+
+#[test]
+fn palette_colors() {
+    check("palette_colors", vec![
+        ShapeArea::shape(Shape::Rectangle(DefaultAttributes::colored(Palette::css("orange").unwrap()), -1.0, -1.0, 0.0, 1.0), None, (0, 0, SIZE, SIZE)),
+        ShapeArea::shape(Shape::Rectangle(DefaultAttributes::colored(color!(#663399)), 0.0, -1.0, 1.0, 1.0), None, (0, 0, SIZE, SIZE)),
+    ]);
+}
+
+#[test]
+fn mapped_upload() {
+    let (device, queue) = device();
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    renderer.set_upload_strategy(UploadStrategy::Mapped);
+    let shapes = |color| vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [0.0, 1.0], (0, 0, SIZE, SIZE)),
+        rect(color, [0.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)),
+    ];
+    for color in [[0.0, 1.0, 0.0], [0.0, 0.0, 1.0]] {
+        let pixels = harness::render_with(&device, &queue, &mut renderer, shapes(color), SIZE, SIZE).unwrap();
+        assert_eq!(pixels, harness::render(&device, &queue, shapes(color), SIZE, SIZE).unwrap());
+    }
+}
+
+#[test]
+fn overdraw_heat_map() {
+    let (device, queue) = device();
+    let rect = |left, right| ShapeArea::auto_bound(ShapeBuilder::new(Shape::Rectangle(DefaultAttributes{color: [1.0, 1.0, 1.0], z: 0.0, palette: 0}, left, -1.0, right, 1.0), None, 0.001));
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    let pixels = harness::render_overdraw_with(&device, &queue, &mut renderer, vec![rect(-1.0, 0.5), rect(-0.5, 1.0)], SIZE, SIZE).unwrap();
+    let pixel = |x: u32| pixels[((32 * SIZE + x) * 4) as usize];
+    assert_eq!(pixel(4), 16);
+    assert_eq!(pixel(32), 32);
+    assert_eq!(pixel(60), 16);
+}
+
+#[cfg(feature = "color_grading")]
+#[test]
+fn color_lut() {
+    let (device, queue) = device();
+    // Inverts every channel, with red varying fastest.
+    let lut: Vec<u8> = (0..8).flat_map(|i| [0, 1, 2].map(|c| if i >> c & 1 == 1 {0} else {255}).into_iter().chain([255])).collect();
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    renderer.set_color_lut(&device, &queue, &lut, 2);
+    let builder = ShapeBuilder::new(Shape::Rectangle(DefaultAttributes{color: [1.0, 0.0, 0.0], z: 0.0, palette: 0}, -1.0, -1.0, 1.0, 1.0), None, 0.001);
+    let pixels = harness::render_with(&device, &queue, &mut renderer, vec![ShapeArea::auto_bound(builder)], SIZE, SIZE).unwrap();
+    assert_eq!(pixels[..4], [0, 255, 255, 255]);
+}
+
+#[cfg(feature = "capture")]
+#[test]
+fn capture_replay() {
+    use wgpu_cyat::cyat::DrawCommand;
+    let (device, queue) = device();
+    let attributes = |color| DefaultAttributes{color, z: 0.0, palette: 0};
+    let curve = vec![
+        DrawCommand::QuadraticBezierTo(attributes([0.0, 1.0, 0.0]), 0.5, -0.5, 0.8, 0.2),
+        DrawCommand::CubicBezierTo(attributes([0.0, 0.0, 1.0]), -0.5, -0.5, 0.0, -1.0, -0.8, -0.8),
+    ];
+    let shapes = || vec![
+        ShapeArea::auto_bound(ShapeBuilder::new(Shape::Ellipse(attributes([1.0, 0.0, 0.0]), 0.0, 0.0, 0.6, 0.4), None, 0.01)).with_bound((8, 8, 48, 48)).with_label("ellipse"),
+        ShapeArea::shape(Shape::Draw(attributes([1.0, 1.0, 0.0]), -0.5, 0.5, curve.clone()), None, (0, 0, SIZE, SIZE)).with_parent(0).with_z_index(-1),
+        ShapeArea::shape(Shape::Rectangle(attributes([1.0, 1.0, 1.0]), -0.9, -0.9, 0.5, 0.5), Some(0.02), (4, 4, 40, 40))
+            .with_gradient(GradientType::Mesh(MeshGradient{corners: [[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0], [1.0, 1.0, 1.0, 1.0]]})),
+    ];
+    let path = std::env::temp_dir().join(format!("wgpu_cyat_capture_{}.bin", std::process::id()));
+
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    renderer.capture_next_frame(&path);
+    let captured = harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+    let replayed = wgpu_cyat::replay(&path).unwrap();
+    assert_eq!(replayed.len(), 3);
+    assert_eq!(replayed[0].label.as_deref(), Some("ellipse"));
+    renderer.capture_next_frame(&path);
+    let first = std::fs::read(&path).unwrap();
+    assert_eq!(harness::render_with(&device, &queue, &mut renderer, replayed, SIZE, SIZE).unwrap(), captured);
+    assert_eq!(std::fs::read(&path).unwrap(), first);
+
+    // A field of a later version before the first shape's geometry is skipped.
+    let mut future = first.clone();
+    future.splice(16..16, [0xff, 0x7f, 3, 0, 0, 0, 1, 2, 3]);
+    std::fs::write(&path, future).unwrap();
+    assert_eq!(harness::render_with(&device, &queue, &mut renderer, wgpu_cyat::replay(&path).unwrap(), SIZE, SIZE).unwrap(), captured);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn pipeline_statistics() {
+    let (device, queue) = device_with(Features::PIPELINE_STATISTICS_QUERY);
+    let shapes = || vec![rect([1.0, 0.0, 0.0], [-1.0, -1.0], [0.0, 1.0], (0, 0, SIZE, SIZE))];
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    if !renderer.set_pipeline_statistics(&device, true) {
+        assert!(!device.features().contains(Features::PIPELINE_STATISTICS_QUERY));
+        harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+        assert_eq!(renderer.resolve_statistics(&device, &queue), None);
+        return;
+    }
+    assert_eq!(renderer.resolve_statistics(&device, &queue), None);
+    harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+    assert_eq!(renderer.resolve_statistics(&device, &queue), None);
+    device.poll(Maintain::Wait);
+    let statistics = renderer.resolve_statistics(&device, &queue).unwrap();
+    assert_eq!((statistics.clipper_invocations, statistics.clipper_primitives_out), (2, 2));
+    assert!(statistics.fragment_shader_invocations >= (SIZE * SIZE / 2) as u64);
+
+    assert!(!renderer.set_pipeline_statistics(&device, false));
+    assert_eq!(renderer.resolve_statistics(&device, &queue), None);
+}
+
+#[test]
+fn damaged_regions() {
+    let (device, queue) = device();
+    let shapes = || vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [0.0, 1.0], (0, 0, SIZE / 2, SIZE)),
+        rect([0.0, 0.0, 1.0], [-0.5, -0.5], [0.5, 0.5], (0, 0, SIZE, SIZE)),
+        rect([0.0, 1.0, 0.0], [0.5, 0.5], [1.0, 1.0], (SIZE * 3 / 4, 0, SIZE / 4, SIZE / 4)),
+    ];
+    // Overlapping damage draws the opaque shapes twice there, with the same result.
+    let damage = [ScissorRect::from_pos_size(8, 8, 24, 24), ScissorRect::from_pos_size(24, 24, 24, 24)];
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    let pixels = harness::render_pass_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE, &|renderer, render_pass| {
+        renderer.render_damaged(render_pass, &damage);
+    }).unwrap();
+    let full = harness::render(&device, &queue, shapes(), SIZE, SIZE).unwrap();
+    for (i, (pixel, expected)) in pixels.chunks_exact(4).zip(full.chunks_exact(4)).enumerate() {
+        let point = [i as u32 % SIZE, i as u32 / SIZE];
+        let damaged = damage.iter().any(|rect| rect.contains_point(point));
+        assert_eq!(pixel, if damaged {expected} else {&[0, 0, 0, 255]}, "pixel {point:?}");
+    }
+}
+
+#[test]
+fn depth_prepass() {
+    let (device, queue) = device();
+    let descriptor = CyatRendererDescriptor{blend: Some(BlendState::ALPHA_BLENDING), ..CyatRendererDescriptor::new(harness::FORMAT)}
+        .depth_format(TextureFormat::Depth32Float).unwrap();
+    let shape = |color, min: [f32; 2], z| {
+        let attributes = DefaultAttributes{color, z, palette: 0};
+        ShapeArea::shape(Shape::Rectangle(attributes, min[0], min[1], min[0] + 1.0, min[1] + 1.0), None, (0, 0, SIZE, SIZE))
+    };
+    // Opaque shapes nearest first, which the prepass hides behind each other,
+    // and a translucent one in front it leaves out.
+    let shapes = || vec![
+        shape([0.0, 0.0, 1.0], [-0.25, -0.25], 0.5),
+        shape([0.0, 1.0, 0.0], [-0.5, -0.5], 0.6),
+        shape([1.0, 0.0, 0.0], [-0.75, -0.75], 0.7),
+        shape([1.0, 1.0, 1.0], [-0.5, -0.25], 0.25).with_opacity(0.5),
+    ];
+    let mut renderer = CyatRenderer::from_descriptor(&device, descriptor.clone().with_depth_prepass()).unwrap();
+    let pixels = harness::render_depth_prepass_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+    let mut renderer = CyatRenderer::from_descriptor(&device, descriptor).unwrap();
+    assert_eq!(pixels, harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap());
+}
+
+#[test]
+fn runtime_multisample() {
+    let (device, queue) = device();
+    let shapes = || vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [0.0, 1.0], (0, 0, SIZE, SIZE)),
+        ShapeArea::circle([0.5, 0.0], 0.4, [0.0, 0.0, 1.0], (0, 0, SIZE, SIZE)),
+    ];
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    let pixels = harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+
+    renderer.set_multisample(&device, MultisampleState{count: 4, ..MultisampleState::default()});
+    assert_eq!(renderer.sample_count(), 4);
+    assert!(renderer.validate_render_target(harness::FORMAT, 4).is_ok());
+    // The prepared frame survives the new pipelines.
+    assert!(!renderer.prepare(&device, &queue, shapes()).unwrap().changed);
+
+    renderer.set_multisample(&device, MultisampleState::default());
+    assert_eq!(harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap(), pixels);
+}
+
+#[test]
+fn depth_range() {
+    let (device, queue) = device();
+    let descriptor = CyatRendererDescriptor::new(harness::FORMAT).depth_format(TextureFormat::Depth32Float).unwrap();
+    let square = |color, z| ShapeArea::rect([-0.5, -0.5, 0.5, 0.5], color, (0, 0, SIZE, SIZE)).with_z(z);
+    // Another layer sharing the depth buffer, drawn after the renderer's at a depth of 0.3.
+    let mut layer = CyatRenderer::from_descriptor(&device, descriptor.clone()).unwrap();
+    layer.resize(SIZE, SIZE);
+    layer.prepare(&device, &queue, vec![square([0.0, 0.0, 1.0], 0.3)]).unwrap();
+    let center = |pixels: &[u8]| pixels[((SIZE / 2 * SIZE + SIZE / 2) * 4) as usize..][..4].to_vec();
+
+    let mut renderer = CyatRenderer::from_descriptor(&device, descriptor).unwrap();
+    let render = |renderer: &mut CyatRenderer| harness::render_pass_with(&device, &queue, renderer, vec![square([1.0, 0.0, 0.0], 0.0), square([1.0, 0.0, 0.0], 2.0)], SIZE, SIZE, &|renderer, render_pass| {
+        renderer.render(render_pass);
+        layer.render(render_pass);
+    }).unwrap();
+    // Normalized to 0.5..=1.0 both squares are behind the layer,
+    renderer.set_z_normalization(Some([0.5, 1.0]));
+    assert_eq!(center(&render(&mut renderer)), [0, 0, 255, 255]);
+    // and remapped into 0.1..=0.2 all of the renderer's depths are in front of it.
+    renderer.set_depth_range(&queue, [0.1, 0.2]);
+    assert_eq!(center(&render(&mut renderer)), [255, 0, 0, 255]);
+}
+
+#[test]
+fn group_opacity() {
+    let (device, queue) = device();
+    let descriptor = CyatRendererDescriptor{blend: Some(BlendState::ALPHA_BLENDING), ..CyatRendererDescriptor::new(harness::FORMAT)};
+    // A red square clipped by the group's bound and a blue one above it overlapping it.
+    let shapes = || vec![
+        ShapeArea::rect([-0.75, -0.75, 0.25, 0.25], [1.0, 0.0, 0.0], (16, 16, 32, 32)).with_group_opacity(0.5),
+        ShapeArea::rect([-0.25, -0.25, 0.5, 0.5], [0.0, 0.0, 1.0], (0, 0, 32, 32)).with_parent(0),
+    ];
+    let pixel = |pixels: &[u8], x: u32, y: u32| pixels[((y * SIZE + x) * 4) as usize..][..3].to_vec();
+    let near = |pixel: Vec<u8>, expected: [u8; 3]| pixel.iter().zip(expected).all(|(&a, b)| a.abs_diff(b) <= 1);
+
+    let mut renderer = CyatRenderer::from_descriptor(&device, descriptor.clone()).unwrap();
+    for _ in 0..2 {
+        let pixels = harness::render_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE).unwrap();
+        // The blue square hides the red one where they overlap, and the layer shows half of both.
+        assert!(near(pixel(&pixels, 32, 32), [0, 0, 128]), "{:?}", pixel(&pixels, 32, 32));
+        assert!(near(pixel(&pixels, 18, 44), [128, 0, 0]), "{:?}", pixel(&pixels, 18, 44));
+        assert!(near(pixel(&pixels, 44, 18), [0, 0, 128]), "{:?}", pixel(&pixels, 44, 18));
+        assert_eq!(pixel(&pixels, 10, 44), [0, 0, 0]);
+    }
+
+    // At full opacity the shapes are drawn directly, without a layer.
+    let mut direct = CyatRenderer::from_descriptor(&device, descriptor.clone()).unwrap();
+    let opaque = harness::render_with(&device, &queue, &mut direct, shapes().into_iter().map(|shape| shape.with_group_opacity(1.0)).collect(), SIZE, SIZE).unwrap();
+    assert_eq!(pixel(&opaque, 32, 32), [0, 0, 255]);
+
+    let mut unbound = ShapeArea::circle([0.0, 0.0], 0.5, [1.0, 0.0, 0.0], (0, 0, SIZE, SIZE)).with_group_opacity(0.5);
+    unbound.bound = None;
+    assert_eq!(renderer.prepare(&device, &queue, vec![unbound]), Err(CyatError::UnboundGroup(0)));
+
+    let group = |color| ShapeArea::rect([-0.5, -0.5, 0.5, 0.5], color, (0, 0, SIZE, SIZE)).with_group_opacity(0.5);
+    let nested = vec![group([1.0, 0.0, 0.0]), group([0.0, 0.0, 1.0]).with_parent(0)];
+    assert_eq!(renderer.prepare(&device, &queue, nested), Err(CyatError::NestedGroup{group: 1, outer: 0}));
+    let viewport = vec![group([1.0, 0.0, 0.0]).with_viewport((0, 0, SIZE, SIZE))];
+    assert_eq!(renderer.prepare(&device, &queue, viewport), Err(CyatError::UnsupportedGroupShape(0)));
+
+    let mut quantized = CyatRenderer::from_descriptor(&device, descriptor.with_quantized_vertices(1.0, [0.0, 0.0])).unwrap();
+    quantized.resize(SIZE, SIZE);
+    assert_eq!(quantized.prepare(&device, &queue, vec![group([1.0, 0.0, 0.0])]), Err(CyatError::UnsupportedGroup(0)));
+}
+
+#[test]
+fn full_target_scissor() {
+    let (device, queue) = device();
+    // A full target shape drawn first, and a small one drawn last.
+    let shapes = || vec![
+        rect([1.0, 0.0, 0.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE, SIZE)),
+        rect([0.0, 0.0, 1.0], [-1.0, -1.0], [1.0, 1.0], (0, 0, SIZE / 4, SIZE / 4)),
+    ];
+    let mut renderer = CyatRenderer::new(&device, &harness::FORMAT, MultisampleState::default(), None);
+    renderer.resize(SIZE, SIZE);
+    let stats = renderer.prepare(&device, &queue, shapes()).unwrap();
+    assert_eq!(stats.scissor_changes, 2);
+
+    // A shadow pass leaves the scissor at the small bound, which render must
+    // not keep for its full target first shape.
+    let shader = device.create_shader_module(ShaderModuleDescriptor{label: None, source: ShaderSource::Wgsl(SHADOW.into())});
+    let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor{label: None, bind_group_layouts: &[], push_constant_ranges: &[]});
+    let shadow = device.create_render_pipeline(&RenderPipelineDescriptor{
+        label: None,
+        layout: Some(&layout),
+        vertex: VertexState{module: &shader, entry_point: Some("vs_main"), compilation_options: Default::default(), buffers: &[DefaultVertex::layout()]},
+        fragment: Some(FragmentState{module: &shader, entry_point: Some("fs_main"), compilation_options: Default::default(), targets: &[Some(harness::FORMAT.into())]}),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    });
+    let pixels = harness::render_pass_with(&device, &queue, &mut renderer, shapes(), SIZE, SIZE, &|renderer, render_pass| {
+        renderer.render_with_pipeline(render_pass, &shadow);
+        renderer.render(render_pass);
+    }).unwrap();
+    assert_eq!(pixels, harness::render(&device, &queue, shapes(), SIZE, SIZE).unwrap());
+}
+
+const SHADOW: &str = "
+@vertex
+fn vs_main(@location(0) position: vec2<f32>) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(position, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 1.0, 0.0, 1.0);
+}
+";